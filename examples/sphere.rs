@@ -1,5 +1,6 @@
 use nalgebra as na;
 use std::path::PathBuf;
+use tessellation::UpAxis;
 struct UnitSphere {
     bbox: tessellation::BoundingBox<f64>,
 }
@@ -25,6 +26,9 @@ impl tessellation::ImplicitFunction<f64> for UnitSphere {
     fn normal(&self, p: &na::Point3<f64>) -> na::Vector3<f64> {
         return na::Vector3::new(p.x, p.y, p.z).normalize();
     }
+    fn is_exact_distance(&self) -> bool {
+        true
+    }
 }
 
 fn main() {
@@ -32,5 +36,5 @@ fn main() {
     let mut mdc = tessellation::ManifoldDualContouring::new(&sphere, 0.2, 0.1);
     let mesh = mdc.tessellate().unwrap();
 
-    mesh.write_to_obj(&PathBuf::from("foo.obj"), false);
+    mesh.write_to_obj(&PathBuf::from("foo.obj"), false, UpAxis::ZUp);
 }
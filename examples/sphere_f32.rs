@@ -0,0 +1,44 @@
+use nalgebra as na;
+use num_traits::Float;
+use std::path::PathBuf;
+use tessellation::{RealField, UpAxis};
+
+struct UnitSphere<S: RealField> {
+    bbox: tessellation::BoundingBox<S>,
+}
+
+impl<S: RealField + Float + From<f32>> UnitSphere<S> {
+    fn new() -> UnitSphere<S> {
+        let one: S = From::from(1f32);
+        UnitSphere {
+            bbox: tessellation::BoundingBox::new(
+                &na::Point3::new(-one, -one, -one),
+                &na::Point3::new(one, one, one),
+            ),
+        }
+    }
+}
+
+impl<S: RealField + Float + From<f32>> tessellation::ImplicitFunction<S> for UnitSphere<S> {
+    fn bbox(&self) -> &tessellation::BoundingBox<S> {
+        &self.bbox
+    }
+    fn value(&self, p: &na::Point3<S>) -> S {
+        p.coords.norm() - From::from(1f32)
+    }
+    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+        p.coords.normalize()
+    }
+    fn is_exact_distance(&self) -> bool {
+        true
+    }
+}
+
+fn main() {
+    let sphere = UnitSphere::<f32>::new();
+    let mut mdc = tessellation::ManifoldDualContouring::new(&sphere, 0.2, 0.1);
+    let mesh = mdc.tessellate().unwrap();
+
+    mesh.write_to_obj(&PathBuf::from("foo.obj"), false, UpAxis::ZUp)
+        .unwrap();
+}
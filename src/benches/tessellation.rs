@@ -4,7 +4,9 @@ use implicit3d::{
 };
 use nalgebra as na;
 use num_traits::Float;
-use tessellation::{AsUSize, BoundingBox, ImplicitFunction, ManifoldDualContouring, RealField};
+use tessellation::{
+    AsUSize, BoundingBox, ImplicitFunction, ManifoldDualContouring, Mesh, RealField, RootFinder,
+};
 
 struct ObjectAdaptor<S: RealField> {
     implicit: Box<dyn implicit3d::Object<S>>,
@@ -67,15 +69,135 @@ fn create_object<S: RealField + AsUSize + Float + From<f32> + RealField + implic
     }
 }
 
-fn tessellate<S: From<f32> + AsUSize + RealField + Float + implicit3d::RealField>(b: &mut Bencher) {
+fn tessellate_with<S: From<f32> + AsUSize + RealField + Float + implicit3d::RealField>(
+    b: &mut Bencher,
+    root_finder: RootFinder,
+) {
     let o = create_object::<S>();
-    let tess = ManifoldDualContouring::new(&o, From::from(0.02), From::from(0.1));
+    let tess = ManifoldDualContouring::new(&o, From::from(0.02), From::from(0.1))
+        .with_root_finder(root_finder);
     b.iter(|| {
         let mut my_tess = tess.clone();
-        my_tess.tessellate();
+        my_tess.tessellate().unwrap();
+    });
+}
+
+// Tessellate the same function repeatedly, reusing the allocations of one instance.
+fn tessellate_reset<S: From<f32> + AsUSize + RealField + Float + implicit3d::RealField>(
+    b: &mut Bencher,
+) {
+    let o = create_object::<S>();
+    let mut tess = ManifoldDualContouring::new(&o, From::from(0.02), From::from(0.1));
+    b.iter(|| {
+        tess.reset_for(&o);
+        tess.tessellate().unwrap();
     });
 }
 
+fn tessellate<S: From<f32> + AsUSize + RealField + Float + implicit3d::RealField>(b: &mut Bencher) {
+    tessellate_with::<S>(b, RootFinder::Linear);
+}
+
+fn tessellate_secant<S: From<f32> + AsUSize + RealField + Float + implicit3d::RealField>(
+    b: &mut Bencher,
+) {
+    tessellate_with::<S>(b, RootFinder::Secant);
+}
+
+fn tessellate_brent<S: From<f32> + AsUSize + RealField + Float + implicit3d::RealField>(
+    b: &mut Bencher,
+) {
+    tessellate_with::<S>(b, RootFinder::Brent);
+}
+
+// A small sphere in a mostly empty bbox. Its value is the exact distance, so sampling skips the
+// empty cubes.
+struct SparseSphere {
+    bbox: BoundingBox<f64>,
+}
+
+impl ImplicitFunction<f64> for SparseSphere {
+    fn bbox(&self) -> &BoundingBox<f64> {
+        &self.bbox
+    }
+    fn value(&self, p: &na::Point3<f64>) -> f64 {
+        p.coords.norm() - 0.25
+    }
+    fn normal(&self, p: &na::Point3<f64>) -> na::Vector3<f64> {
+        p.coords.normalize()
+    }
+    fn is_exact_distance(&self) -> bool {
+        true
+    }
+}
+
+fn tessellate_sparse(b: &mut Bencher) {
+    let sphere = SparseSphere {
+        bbox: BoundingBox::new(
+            &na::Point3::new(-2., -2., -2.),
+            &na::Point3::new(2., 2., 2.),
+        ),
+    };
+    let tess = ManifoldDualContouring::new(&sphere, 0.02, 0.1).with_quiet(true);
+    b.iter(|| {
+        let mut my_tess = tess.clone();
+        my_tess.tessellate().unwrap();
+    });
+}
+
+// Transform a grid of n vertices.
+fn transform_with(b: &mut Bencher, n: usize, parallel: bool) {
+    let mut mesh = Mesh {
+        vertices: (0..n).map(|i| [i as f64, (i % 7) as f64, 1.]).collect(),
+        faces: Vec::new(),
+        labels: None,
+        face_normals: None,
+        vertex_colors: None,
+    };
+    let m = na::Matrix4::new_rotation(na::Vector3::new(0.1, 0.2, 0.3));
+    b.iter(|| {
+        if parallel {
+            mesh.transform_parallel(&m);
+        } else {
+            mesh.transform(&m);
+        }
+    });
+}
+
+fn transform_1k(b: &mut Bencher) {
+    transform_with(b, 1_000, false);
+}
+
+fn transform_parallel_1k(b: &mut Bencher) {
+    transform_with(b, 1_000, true);
+}
+
+fn transform_1m(b: &mut Bencher) {
+    transform_with(b, 1_000_000, false);
+}
+
+fn transform_parallel_1m(b: &mut Bencher) {
+    transform_with(b, 1_000_000, true);
+}
+
 benchmark_group!(bench_tessellation_f32, tessellate<f32>,);
-benchmark_group!(bench_tessellation_f64, tessellate<f64>,);
-benchmark_main!(bench_tessellation_f32, bench_tessellation_f64);
+benchmark_group!(
+    bench_tessellation_f64,
+    tessellate<f64>,
+    tessellate_secant<f64>,
+    tessellate_brent<f64>,
+    tessellate_reset<f64>,
+    tessellate_sparse,
+);
+benchmark_group!(
+    bench_transform,
+    transform_1k,
+    transform_parallel_1k,
+    transform_1m,
+    transform_parallel_1m,
+);
+benchmark_main!(
+    bench_tessellation_f32,
+    bench_tessellation_f64,
+    bench_transform
+);
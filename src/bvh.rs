@@ -0,0 +1,444 @@
+use crate::{mesh::TriangleMesh, RealField};
+use bbox::BoundingBox;
+use nalgebra as na;
+use num_traits::Float;
+use std::{cmp::Ordering, fmt::Debug};
+
+// Maximum number of triangles in a leaf.
+const LEAF_SIZE: usize = 4;
+
+/// The intersection of a ray with a triangle, returned from [`Bvh::raycast()`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit<S> {
+    /// The index of the triangle in [`TriangleMesh::faces`].
+    pub face: usize,
+    /// The ray parameter of the intersection, i.e. it is at `origin + dir * distance`.
+    pub distance: S,
+    /// The weights of the triangle's vertices at the intersection.
+    pub barycentric: [S; 3],
+}
+
+/// A bounding volume hierarchy over the triangles of a [`TriangleMesh`] to accelerate ray and
+/// closest point queries.
+///
+/// Built with [`TriangleMesh::build_bvh()`]. It copies the triangles, so it stays valid if the
+/// mesh is changed or dropped.
+#[derive(Clone, Debug)]
+pub struct Bvh<S: RealField + Float + Debug> {
+    triangles: Vec<[na::Point3<S>; 3]>,
+    // Indices into triangles. Each leaf covers a contiguous range.
+    order: Vec<usize>,
+    // nodes[0] is the root.
+    nodes: Vec<Node<S>>,
+}
+
+#[derive(Clone, Debug)]
+struct Node<S: RealField + Float + Debug> {
+    bbox: BoundingBox<S>,
+    content: NodeContent,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum NodeContent {
+    // Range in order.
+    Leaf { start: usize, end: usize },
+    // Indices in nodes.
+    Inner { left: usize, right: usize },
+}
+
+impl<S: RealField + Float + Debug> Bvh<S> {
+    pub(crate) fn new(mesh: &TriangleMesh<S>) -> Self {
+        let triangles: Vec<[na::Point3<S>; 3]> = mesh
+            .faces
+            .iter()
+            .map(|face| {
+                [
+                    na::Point3::from(mesh.vertices[face[0]]),
+                    na::Point3::from(mesh.vertices[face[1]]),
+                    na::Point3::from(mesh.vertices[face[2]]),
+                ]
+            })
+            .collect();
+        let mut bvh = Bvh {
+            order: (0..triangles.len()).collect(),
+            triangles,
+            nodes: Vec::new(),
+        };
+        if !bvh.triangles.is_empty() {
+            bvh.build(0, bvh.triangles.len());
+        }
+        bvh
+    }
+
+    // Build the node for the triangles order[start..end] and return its index.
+    fn build(&mut self, start: usize, end: usize) -> usize {
+        let mut bbox = BoundingBox::neg_infinity();
+        let mut centroids = BoundingBox::neg_infinity();
+        for &i in &self.order[start..end] {
+            for p in &self.triangles[i] {
+                bbox.insert(p);
+            }
+            centroids.insert(&centroid(&self.triangles[i]));
+        }
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            bbox,
+            content: NodeContent::Leaf { start, end },
+        });
+        if end - start <= LEAF_SIZE {
+            return index;
+        }
+        // Split at the median along the longest axis of the centroids.
+        let dim = centroids.dim();
+        let axis = if dim.x >= dim.y && dim.x >= dim.z {
+            0
+        } else if dim.y >= dim.z {
+            1
+        } else {
+            2
+        };
+        let triangles = &self.triangles;
+        self.order[start..end].sort_unstable_by(|&a, &b| {
+            centroid(&triangles[a])[axis]
+                .partial_cmp(&centroid(&triangles[b])[axis])
+                .unwrap_or(Ordering::Equal)
+        });
+        let middle = (start + end) / 2;
+        let left = self.build(start, middle);
+        let right = self.build(middle, end);
+        self.nodes[index].content = NodeContent::Inner { left, right };
+        index
+    }
+
+    /// Return the nearest intersection of the ray from origin in direction dir with the mesh.
+    /// Triangles are hit from both sides.
+    pub fn raycast(&self, origin: &na::Point3<S>, dir: &na::Vector3<S>) -> Option<Hit<S>> {
+        let mut best: Option<Hit<S>> = None;
+        if self.nodes.is_empty() {
+            return best;
+        }
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            let max_distance = best.map_or(<S as Float>::infinity(), |hit| hit.distance);
+            if !ray_hits_bbox(origin, dir, &node.bbox, max_distance) {
+                continue;
+            }
+            match node.content {
+                NodeContent::Leaf { start, end } => {
+                    for &face in &self.order[start..end] {
+                        if let Some((distance, u, v)) =
+                            intersect_triangle(origin, dir, &self.triangles[face])
+                        {
+                            let is_nearer = match best {
+                                Some(hit) => distance < hit.distance,
+                                None => true,
+                            };
+                            if is_nearer {
+                                best = Some(Hit {
+                                    face,
+                                    distance,
+                                    barycentric: [S::one() - u - v, u, v],
+                                });
+                            }
+                        }
+                    }
+                }
+                NodeContent::Inner { left, right } => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+            }
+        }
+        best
+    }
+
+    /// Return the point on the mesh closest to p along with the index of its triangle. None, if
+    /// the mesh has no triangles.
+    pub fn closest_point(&self, p: &na::Point3<S>) -> Option<(usize, na::Point3<S>)> {
+        let mut best = None;
+        let mut best_distance_squared = <S as Float>::infinity();
+        if self.nodes.is_empty() {
+            return best;
+        }
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if distance_squared_to_bbox(p, &node.bbox) >= best_distance_squared {
+                continue;
+            }
+            match node.content {
+                NodeContent::Leaf { start, end } => {
+                    for &face in &self.order[start..end] {
+                        let [a, b, c] = &self.triangles[face];
+                        let q = closest_point_on_triangle(p, a, b, c);
+                        let distance_squared = (q - p).norm_squared();
+                        if distance_squared < best_distance_squared {
+                            best_distance_squared = distance_squared;
+                            best = Some((face, q));
+                        }
+                    }
+                }
+                NodeContent::Inner { left, right } => {
+                    // Visit the nearer child first, so the other one is more likely culled.
+                    let distance =
+                        |node: usize| distance_squared_to_bbox(p, &self.nodes[node].bbox);
+                    if distance(left) <= distance(right) {
+                        stack.push(right);
+                        stack.push(left);
+                    } else {
+                        stack.push(left);
+                        stack.push(right);
+                    }
+                }
+            }
+        }
+        best
+    }
+}
+
+// The squared distance from p to the closest point in bbox, zero inside.
+fn distance_squared_to_bbox<S: RealField + Float + Debug>(
+    p: &na::Point3<S>,
+    bbox: &BoundingBox<S>,
+) -> S {
+    (0..3).fold(S::zero(), |sum, i| {
+        let d = Float::max(
+            Float::max(bbox.min[i] - p[i], p[i] - bbox.max[i]),
+            S::zero(),
+        );
+        sum + d * d
+    })
+}
+
+// Returns the point on triangle abc closest to p.
+// See Christer Ericson, Real-Time Collision Detection, 5.1.5.
+pub(crate) fn closest_point_on_triangle<S: RealField + Float + Debug>(
+    p: &na::Point3<S>,
+    a: &na::Point3<S>,
+    b: &na::Point3<S>,
+    c: &na::Point3<S>,
+) -> na::Point3<S> {
+    let ab = b - a;
+    let ac = c - a;
+    let ap = p - a;
+    let d1 = ab.dot(&ap);
+    let d2 = ac.dot(&ap);
+    if d1 <= S::zero() && d2 <= S::zero() {
+        return *a;
+    }
+    let bp = p - b;
+    let d3 = ab.dot(&bp);
+    let d4 = ac.dot(&bp);
+    if d3 >= S::zero() && d4 <= d3 {
+        return *b;
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= S::zero() && d1 >= S::zero() && d3 <= S::zero() {
+        return a + ab * (d1 / (d1 - d3));
+    }
+    let cp = p - c;
+    let d5 = ab.dot(&cp);
+    let d6 = ac.dot(&cp);
+    if d6 >= S::zero() && d5 <= d6 {
+        return *c;
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= S::zero() && d2 >= S::zero() && d6 <= S::zero() {
+        return a + ac * (d2 / (d2 - d6));
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= S::zero() && d4 - d3 >= S::zero() && d5 - d6 >= S::zero() {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+    let denom = S::one() / (va + vb + vc);
+    a + ab * (vb * denom) + ac * (vc * denom)
+}
+
+fn centroid<S: RealField + Float + Debug>(triangle: &[na::Point3<S>; 3]) -> na::Point3<S> {
+    let three: S = S::one() + S::one() + S::one();
+    na::Point3::from((triangle[0].coords + triangle[1].coords + triangle[2].coords) / three)
+}
+
+// Returns true, if the ray enters bbox at a ray parameter between 0 and max_distance.
+fn ray_hits_bbox<S: RealField + Float + Debug>(
+    origin: &na::Point3<S>,
+    dir: &na::Vector3<S>,
+    bbox: &BoundingBox<S>,
+    max_distance: S,
+) -> bool {
+    let mut near = S::zero();
+    let mut far = max_distance;
+    for i in 0..3 {
+        if dir[i] == S::zero() {
+            if origin[i] < bbox.min[i] || origin[i] > bbox.max[i] {
+                return false;
+            }
+            continue;
+        }
+        let t1 = (bbox.min[i] - origin[i]) / dir[i];
+        let t2 = (bbox.max[i] - origin[i]) / dir[i];
+        near = Float::max(near, Float::min(t1, t2));
+        far = Float::min(far, Float::max(t1, t2));
+        if near > far {
+            return false;
+        }
+    }
+    true
+}
+
+// Möller–Trumbore ray triangle intersection. Returns the ray parameter and the barycentric
+// coordinates of the 2nd and 3rd vertex.
+fn intersect_triangle<S: RealField + Float + Debug>(
+    origin: &na::Point3<S>,
+    dir: &na::Vector3<S>,
+    triangle: &[na::Point3<S>; 3],
+) -> Option<(S, S, S)> {
+    let edge1 = triangle[1] - triangle[0];
+    let edge2 = triangle[2] - triangle[0];
+    let p = dir.cross(&edge2);
+    let determinant = edge1.dot(&p);
+    if determinant == S::zero() {
+        // The ray is parallel to the triangle.
+        return None;
+    }
+    let inverse = S::one() / determinant;
+    let s = origin - triangle[0];
+    let u = s.dot(&p) * inverse;
+    if u < S::zero() || u > S::one() {
+        return None;
+    }
+    let q = s.cross(&edge1);
+    let v = dir.dot(&q) * inverse;
+    if v < S::zero() || u + v > S::one() {
+        return None;
+    }
+    let distance = edge2.dot(&q) * inverse;
+    if distance < S::zero() {
+        return None;
+    }
+    Some((distance, u, v))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{closest_point_on_triangle, intersect_triangle};
+    use crate::TriangleMesh;
+    use nalgebra as na;
+
+    // Two layers of n x n quads at z = 0 and z = 1, split into triangles.
+    fn two_layers(n: usize) -> TriangleMesh<f64> {
+        let mut mesh = TriangleMesh {
+            vertices: Vec::new(),
+            faces: Vec::new(),
+        };
+        for z in 0..2 {
+            let first = mesh.vertices.len();
+            for y in 0..=n {
+                for x in 0..=n {
+                    mesh.vertices.push([x as f64, y as f64, z as f64]);
+                }
+            }
+            let p = |x: usize, y: usize| first + x + (n + 1) * y;
+            for y in 0..n {
+                for x in 0..n {
+                    mesh.faces.push([p(x, y), p(x + 1, y), p(x + 1, y + 1)]);
+                    mesh.faces.push([p(x + 1, y + 1), p(x, y + 1), p(x, y)]);
+                }
+            }
+        }
+        mesh
+    }
+
+    #[test]
+    fn raycast() {
+        let mesh = two_layers(8);
+        let bvh = mesh.build_bvh();
+        for i in 0..50 {
+            let origin = na::Point3::new(
+                (i * 7 % 80) as f64 / 10. + 0.03,
+                (i * 13 % 80) as f64 / 10. + 0.01,
+                if i % 2 == 0 { -1. } else { 0.5 },
+            );
+            let dir = na::Vector3::new(((i % 5) as f64 - 2.) / 10., 0.05, 1.);
+            // Brute force reference.
+            let expected = mesh
+                .faces
+                .iter()
+                .enumerate()
+                .filter_map(|(face, f)| {
+                    let triangle = [
+                        na::Point3::from(mesh.vertices[f[0]]),
+                        na::Point3::from(mesh.vertices[f[1]]),
+                        na::Point3::from(mesh.vertices[f[2]]),
+                    ];
+                    intersect_triangle(&origin, &dir, &triangle).map(|(t, _, _)| (t, face))
+                })
+                .fold(None, |best: Option<(f64, usize)>, hit| match best {
+                    Some(b) if b.0 <= hit.0 => Some(b),
+                    _ => Some(hit),
+                });
+            let hit = bvh.raycast(&origin, &dir);
+            assert_eq!(hit.map(|h| h.face), expected.map(|e| e.1), "ray {}", i);
+            if let Some(hit) = hit {
+                let face = mesh.faces[hit.face];
+                let p = (0..3).fold(na::Vector3::zeros(), |p, k| {
+                    p + na::Vector3::from(mesh.vertices[face[k]]) * hit.barycentric[k]
+                });
+                assert!((p - (origin.coords + dir * hit.distance)).norm() < 1e-9);
+                // Rays start below or between the layers and point upwards.
+                assert_eq!(p.z.round(), (origin.z + 0.5).ceil());
+            }
+        }
+        // Rays pointing away from the mesh or passing it miss.
+        assert_eq!(
+            bvh.raycast(&na::Point3::new(1., 1., 2.), &na::Vector3::new(0., 0., 1.)),
+            None
+        );
+        assert_eq!(
+            bvh.raycast(&na::Point3::new(1., 1., 0.5), &na::Vector3::new(1., 0., 0.)),
+            None
+        );
+    }
+
+    #[test]
+    fn closest_point() {
+        let mesh = two_layers(8);
+        let bvh = mesh.build_bvh();
+        for i in 0..50 {
+            let p = na::Point3::new(
+                (i * 7 % 100) as f64 / 10. - 1.,
+                (i * 13 % 100) as f64 / 10. - 1.,
+                (i * 3 % 40) as f64 / 10. - 1.5,
+            );
+            // Brute force reference.
+            let expected = mesh
+                .faces
+                .iter()
+                .map(|f| {
+                    let [a, b, c] = [0, 1, 2].map(|k| na::Point3::from(mesh.vertices[f[k]]));
+                    (closest_point_on_triangle(&p, &a, &b, &c) - p).norm()
+                })
+                .fold(f64::INFINITY, f64::min);
+            let (face, q) = bvh.closest_point(&p).unwrap();
+            assert!(((q - p).norm() - expected).abs() < 1e-12, "point {}", i);
+            let f = mesh.faces[face];
+            let [a, b, c] = [0, 1, 2].map(|k| na::Point3::from(mesh.vertices[f[k]]));
+            assert!((closest_point_on_triangle(&p, &a, &b, &c) - q).norm() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn empty() {
+        let mesh = TriangleMesh::<f64> {
+            vertices: Vec::new(),
+            faces: Vec::new(),
+        };
+        let bvh = mesh.build_bvh();
+        assert_eq!(
+            bvh.raycast(&na::Point3::origin(), &na::Vector3::new(0., 0., 1.)),
+            None
+        );
+        assert_eq!(bvh.closest_point(&na::Point3::origin()), None);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::{
+    manifold_dual_contouring::DualContouringError, AsUSize, BoundingBox, ImplicitFunction,
+    ManifoldDualContouring, Mesh, RealField,
+};
+use nalgebra as na;
+use num_traits::Float;
+
+/// An implicit function defined by values on a dense, regular grid, e.g. a voxel grid sampled
+/// by another tool.
+///
+/// Values between grid points are interpolated trilinearly, normals are computed by central
+/// differences. Outside of the grid the value at the closest point of the grid is increased by
+/// the distance to it, so the function is positive outside, if the values at the sides of the
+/// grid are.
+#[derive(Clone, Debug)]
+pub struct DenseGrid<'v, S: RealField> {
+    values: &'v [S],
+    dims: [usize; 3],
+    origin: na::Point3<S>,
+    res: S,
+    bbox: BoundingBox<S>,
+}
+
+impl<'v, S: From<f32> + RealField + Float> DenseGrid<'v, S> {
+    /// Create a function from values on a grid with dims grid points along each axis, whose
+    /// grid point [0, 0, 0] is at origin and whose neighboring grid points are res apart. The
+    /// value of grid point [x, y, z] is `values[x + dims[0] * (y + dims[1] * z)]`.
+    ///
+    /// Fails with [`DualContouringError::InvalidGrid`], if values does not have one value per grid
+    /// point or the grid has less than two grid points along an axis.
+    pub fn new(
+        values: &'v [S],
+        dims: [usize; 3],
+        origin: na::Point3<S>,
+        res: S,
+    ) -> Result<Self, DualContouringError> {
+        if dims.iter().any(|&d| d < 2) {
+            return Err(DualContouringError::InvalidGrid(format!(
+                "dims {:?} has less than two grid points along an axis",
+                dims
+            )));
+        }
+        let num_points = dims[0]
+            .checked_mul(dims[1])
+            .and_then(|n| n.checked_mul(dims[2]));
+        if num_points != Some(values.len()) {
+            return Err(DualContouringError::InvalidGrid(format!(
+                "got {} values for dims {:?}",
+                values.len(),
+                dims
+            )));
+        }
+        let extent = na::Vector3::new(
+            From::from((dims[0] - 1) as f32),
+            From::from((dims[1] - 1) as f32),
+            From::from((dims[2] - 1) as f32),
+        ) * res;
+        Ok(DenseGrid {
+            values,
+            dims,
+            origin,
+            res,
+            bbox: BoundingBox::new(&origin, &(origin + extent)),
+        })
+    }
+
+    fn grid_value(&self, index: [usize; 3]) -> S {
+        self.values[index[0] + self.dims[0] * (index[1] + self.dims[1] * index[2])]
+    }
+}
+
+impl<'v, S: From<f32> + RealField + Float> ImplicitFunction<S> for DenseGrid<'v, S> {
+    fn bbox(&self) -> &BoundingBox<S> {
+        &self.bbox
+    }
+
+    fn value(&self, p: &na::Point3<S>) -> S {
+        let mut clamped = *p;
+        for i in 0..3 {
+            clamped[i] = Float::min(Float::max(p[i], self.bbox.min[i]), self.bbox.max[i]);
+        }
+        let relative = (clamped - self.origin) / self.res;
+        let mut cell = [0; 3];
+        let mut weights = na::Vector3::zeros();
+        for i in 0..3 {
+            cell[i] = Float::floor(relative[i])
+                .to_usize()
+                .unwrap_or(0)
+                .min(self.dims[i] - 2);
+            weights[i] = relative[i] - From::from(cell[i] as f32);
+        }
+        let mut sum = S::zero();
+        for corner in 0..8 {
+            let mut index = cell;
+            let mut weight = S::one();
+            for (i, index) in index.iter_mut().enumerate() {
+                if corner >> i & 1 == 1 {
+                    *index += 1;
+                    weight *= weights[i];
+                } else {
+                    weight *= S::one() - weights[i];
+                }
+            }
+            sum += self.grid_value(index) * weight;
+        }
+        sum + (p - clamped).norm()
+    }
+
+    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+        let h = self.res / From::from(2f32);
+        let mut gradient = na::Vector3::zeros();
+        for i in 0..3 {
+            let mut offset = na::Vector3::zeros();
+            offset[i] = h;
+            gradient[i] = self.value(&(p + offset)) - self.value(&(p - offset));
+        }
+        let norm = gradient.norm();
+        if norm > S::zero() {
+            gradient / norm
+        } else {
+            gradient
+        }
+    }
+}
+
+/// Tessellate the function defined by values on a dense grid, see [`DenseGrid::new()`], with the
+/// grid's resolution res and the acceptable relative_error, like
+/// [`ManifoldDualContouring::new()`]. Fails like [`DenseGrid::new()`] on invalid grids.
+pub fn from_dense_grid<S: From<f32> + RealField + Float + AsUSize>(
+    values: &[S],
+    dims: [usize; 3],
+    origin: na::Point3<S>,
+    res: S,
+    relative_error: S,
+) -> Result<Mesh<S>, DualContouringError> {
+    let grid = DenseGrid::new(values, dims, origin, res)?;
+    let mut mdc = ManifoldDualContouring::new(&grid, res, relative_error);
+    mdc.tessellate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_dense_grid, DenseGrid};
+    use crate::{DualContouringError, ImplicitFunction};
+    use nalgebra as na;
+
+    #[test]
+    fn sphere() {
+        let dims = [21, 21, 21];
+        let origin = na::Point3::new(-1.5, -1.5, -1.5);
+        let res = 0.15;
+        let mut values = Vec::new();
+        for z in 0..dims[2] {
+            for y in 0..dims[1] {
+                for x in 0..dims[0] {
+                    let p = origin + na::Vector3::new(x as f64, y as f64, z as f64) * res;
+                    values.push(p.coords.norm() - 1.);
+                }
+            }
+        }
+        let grid = DenseGrid::new(&values, dims, origin, res).unwrap();
+        assert!((grid.value(&na::Point3::new(-1.5, 0., 0.)) - 0.5).abs() < 1e-12);
+        // Outside of the grid.
+        assert!((grid.value(&na::Point3::new(-2.5, 0., 0.)) - 1.5).abs() < 1e-12);
+        assert!((grid.normal(&na::Point3::new(0., 0.8, 0.)) - na::Vector3::y()).norm() < 1e-6);
+
+        let mesh = from_dense_grid(&values, dims, origin, res, 0.1).unwrap();
+        assert!(!mesh.faces.is_empty());
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|v| (na::Vector3::from(*v).norm() - 1.).abs() < 0.05));
+        assert!(mesh
+            .to_half_edge()
+            .half_edges
+            .iter()
+            .all(|e| e.twin.is_some()));
+    }
+
+    #[test]
+    fn invalid_grid() {
+        let values = vec![1f64; 8];
+        let origin = na::Point3::origin();
+        assert!(DenseGrid::new(&values, [2, 2, 2], origin, 0.1).is_ok());
+        for dims in [[2, 2, 1], [2, 2, 3], [usize::MAX, 2, 2]] {
+            assert!(matches!(
+                DenseGrid::new(&values, dims, origin, 0.1),
+                Err(DualContouringError::InvalidGrid(_))
+            ));
+        }
+        assert!(matches!(
+            from_dense_grid(&values[..7], [2, 2, 2], origin, 0.1, 0.1),
+            Err(DualContouringError::InvalidGrid(_))
+        ));
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+
+/// A directed edge of a face in a [`HalfEdgeMesh`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HalfEdge {
+    /// The vertex this half-edge starts at.
+    pub vertex: usize,
+    /// The next half-edge of the same face.
+    pub next: usize,
+    /// The oppositely directed half-edge of the neighboring face. `None` on the boundary.
+    pub twin: Option<usize>,
+    /// The face this half-edge belongs to.
+    pub face: usize,
+}
+
+/// A half-edge representation of a polygon mesh.
+///
+/// Returned from [`Mesh::to_half_edge()`](crate::Mesh::to_half_edge()).
+#[derive(Clone, Debug, PartialEq)]
+pub struct HalfEdgeMesh<S: Clone> {
+    /// The list of vertices.
+    pub vertices: Vec<[S; 3]>,
+    /// The list of half-edges.
+    pub half_edges: Vec<HalfEdge>,
+    /// One outgoing half-edge per vertex. `None` for vertices not used by any face.
+    pub vertex_half_edges: Vec<Option<usize>>,
+    /// One half-edge per face.
+    pub face_half_edges: Vec<usize>,
+}
+
+impl<S: Clone> HalfEdgeMesh<S> {
+    /// Build the half-edge structure from vertices and faces.
+    pub fn new<F: AsRef<[usize]>>(vertices: Vec<[S; 3]>, faces: &[F]) -> HalfEdgeMesh<S> {
+        let mut half_edges = Vec::new();
+        let mut vertex_half_edges = vec![None; vertices.len()];
+        let mut face_half_edges = Vec::with_capacity(faces.len());
+        let mut edge_map: HashMap<(usize, usize), usize> = HashMap::new();
+        for (face_index, face) in faces.iter().enumerate() {
+            let face = face.as_ref();
+            let first = half_edges.len();
+            face_half_edges.push(first);
+            for (i, &vertex) in face.iter().enumerate() {
+                let next_vertex = face[(i + 1) % face.len()];
+                let half_edge_index = first + i;
+                half_edges.push(HalfEdge {
+                    vertex,
+                    next: first + (i + 1) % face.len(),
+                    twin: None,
+                    face: face_index,
+                });
+                if vertex_half_edges[vertex].is_none() {
+                    vertex_half_edges[vertex] = Some(half_edge_index);
+                }
+                if let Some(&twin) = edge_map.get(&(next_vertex, vertex)) {
+                    let twin_edge = &mut half_edges[twin];
+                    // Non-manifold edges may have more than one candidate. Keep the first pair.
+                    if twin_edge.twin.is_none() {
+                        twin_edge.twin = Some(half_edge_index);
+                        half_edges[half_edge_index].twin = Some(twin);
+                    }
+                }
+                edge_map
+                    .entry((vertex, next_vertex))
+                    .or_insert(half_edge_index);
+            }
+        }
+        HalfEdgeMesh {
+            vertices,
+            half_edges,
+            vertex_half_edges,
+            face_half_edges,
+        }
+    }
+
+    /// Returns the vertex the half-edge at index `half_edge` points to.
+    pub fn destination(&self, half_edge: usize) -> usize {
+        self.half_edges[self.half_edges[half_edge].next].vertex
+    }
+
+    /// Returns true, if the half-edge at index `half_edge` has no twin.
+    pub fn is_boundary(&self, half_edge: usize) -> bool {
+        self.half_edges[half_edge].twin.is_none()
+    }
+
+    /// Iterate over the half-edges of the face at index `face`.
+    pub fn face_half_edges(&self, face: usize) -> impl Iterator<Item = usize> + '_ {
+        let first = self.face_half_edges[face];
+        let mut current = Some(first);
+        std::iter::from_fn(move || {
+            let result = current?;
+            let next = self.half_edges[result].next;
+            current = if next == first { None } else { Some(next) };
+            Some(result)
+        })
+    }
+
+    /// Iterate over the vertex indices of the face at index `face`.
+    pub fn face_vertices(&self, face: usize) -> impl Iterator<Item = usize> + '_ {
+        self.face_half_edges(face)
+            .map(move |half_edge| self.half_edges[half_edge].vertex)
+    }
+
+    /// Returns the indices of the vertices connected to `vertex` by an edge.
+    ///
+    /// On the boundary the fan around `vertex` is walked from both sides.
+    pub fn vertex_neighbors(&self, vertex: usize) -> Vec<usize> {
+        let mut result = Vec::new();
+        let start = match self.vertex_half_edges[vertex] {
+            Some(half_edge) => half_edge,
+            None => return result,
+        };
+        // Rotate clockwise: outgoing -> twin -> next.
+        let mut current = start;
+        loop {
+            result.push(self.destination(current));
+            match self.half_edges[current].twin {
+                Some(twin) => {
+                    current = self.half_edges[twin].next;
+                    if current == start {
+                        return result;
+                    }
+                }
+                None => break,
+            }
+        }
+        // Hit the boundary. Rotate counter-clockwise from start: previous -> twin.
+        current = start;
+        loop {
+            let previous = self.previous(current);
+            result.push(self.half_edges[previous].vertex);
+            match self.half_edges[previous].twin {
+                Some(twin) => current = twin,
+                None => break,
+            }
+        }
+        result
+    }
+
+    /// Returns the indices of the faces using `vertex`.
+    ///
+    /// Like [`vertex_neighbors()`](Self::vertex_neighbors()), the fan
+    /// around `vertex` is walked from both sides on the boundary.
+    pub fn vertex_faces(&self, vertex: usize) -> Vec<usize> {
+        let mut result = Vec::new();
+        let start = match self.vertex_half_edges[vertex] {
+            Some(half_edge) => half_edge,
+            None => return result,
+        };
+        // Rotate clockwise: outgoing -> twin -> next.
+        let mut current = start;
+        loop {
+            result.push(self.half_edges[current].face);
+            match self.half_edges[current].twin {
+                Some(twin) => {
+                    current = self.half_edges[twin].next;
+                    if current == start {
+                        return result;
+                    }
+                }
+                None => break,
+            }
+        }
+        // Hit the boundary. Rotate counter-clockwise from start: previous -> twin.
+        current = start;
+        while let Some(twin) = self.half_edges[self.previous(current)].twin {
+            current = twin;
+            result.push(self.half_edges[current].face);
+        }
+        result
+    }
+
+    // Returns the half-edge preceding half_edge in its face.
+    fn previous(&self, half_edge: usize) -> usize {
+        let mut current = half_edge;
+        loop {
+            let next = self.half_edges[current].next;
+            if next == half_edge {
+                return current;
+            }
+            current = next;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HalfEdgeMesh;
+
+    #[test]
+    fn quad_from_two_triangles() {
+        let m = HalfEdgeMesh::new(
+            vec![[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]],
+            &[[0, 1, 2], [2, 3, 0]],
+        );
+        assert_eq!(m.half_edges.len(), 6);
+        // The diagonal is the only interior edge.
+        let interior: Vec<_> = (0..6).filter(|&he| !m.is_boundary(he)).collect();
+        assert_eq!(interior.len(), 2);
+        for &he in &interior {
+            let twin = m.half_edges[he].twin.unwrap();
+            assert_eq!(m.half_edges[twin].twin, Some(he));
+            assert_eq!(m.destination(twin), m.half_edges[he].vertex);
+        }
+        assert_eq!(m.face_vertices(1).collect::<Vec<_>>(), vec![2, 3, 0]);
+        let mut neighbors = m.vertex_neighbors(0);
+        neighbors.sort_unstable();
+        assert_eq!(neighbors, vec![1, 2, 3]);
+        let mut neighbors = m.vertex_neighbors(1);
+        neighbors.sort_unstable();
+        assert_eq!(neighbors, vec![0, 2]);
+        let mut faces = m.vertex_faces(0);
+        faces.sort_unstable();
+        assert_eq!(faces, vec![0, 1]);
+        assert_eq!(m.vertex_faces(3), vec![1]);
+    }
+}
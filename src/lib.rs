@@ -31,6 +31,9 @@
 //!   fn normal(&self, p: &na::Point3<f64>) -> na::Vector3<f64> {
 //!     return na::Vector3::new(p.x, p.y, p.z).normalize();
 //!   }
+//!   fn is_exact_distance(&self) -> bool {
+//!     true
+//!   }
 //! }
 //!
 //! let sphere = UnitSphere::new();
@@ -44,16 +47,41 @@ use nalgebra as na;
 use std::fmt::Debug;
 
 mod bitset;
+mod bvh;
 mod cell_configs;
+mod dense_grid;
+mod half_edge;
 mod manifold_dual_contouring;
 mod mesh;
+mod normal_grid;
+mod operators;
 mod plane;
 mod qef;
+#[cfg(feature = "shapes")]
+mod shapes;
+mod sign_field;
+#[cfg(test)]
+mod test_functions;
 mod vertex_index;
 
-pub use self::manifold_dual_contouring::ManifoldDualContouring;
-pub use self::mesh::Mesh;
-pub use self::mesh::TriangleMesh;
+pub use self::bvh::{Bvh, Hit};
+pub use self::dense_grid::{from_dense_grid, DenseGrid};
+pub use self::half_edge::{HalfEdge, HalfEdgeMesh};
+pub use self::manifold_dual_contouring::{
+    tessellate_batch, Axis, CoordinateSystem, DualContouringError, ManifoldDualContouring, Method,
+    RootFinder, Sign, SignCheck, TessellationStats,
+};
+#[cfg(feature = "gpu")]
+pub use self::mesh::GpuVertex;
+pub use self::mesh::{DistanceQuery, TooManyVertices, TriangleMesh};
+pub use self::mesh::{Mesh, NormalWeighting, RepairOptions, RepairReport, UpAxis, UvMode, Winding};
+pub use self::normal_grid::NormalGrid;
+pub use self::operators::{Offset, Shell, SplitFunction, SymmetricDifference};
+pub use self::plane::Plane;
+#[cfg(feature = "shapes")]
+pub use self::shapes::{BoxSDF, Gyroid, Torus};
+pub use self::sign_field::{sample, SignField};
+pub use self::vertex_index::Index;
 
 /// A Combination of alga::general::RealField and na::RealField.
 pub trait RealField: alga::general::RealField + na::RealField {}
@@ -68,11 +96,27 @@ pub trait ImplicitFunction<S: Debug + RealField> {
     /// Evaluate the function on p and return the value. A value of zero signifies that p is on the
     /// surface to be tessellated. A negative value means p in inside the object. A positive value
     /// means p is outside the object.
-    /// The magnitude of value must be continuous. Furthermore value has to be equal or greater
-    /// than the euclidean distance between p and the surface.
+    /// The magnitude of value must be continuous. See
+    /// [`is_exact_distance()`](Self::is_exact_distance()) for how it is used while sampling.
     fn value(&self, p: &na::Point3<S>) -> S;
+    /// Evaluate the function on each of points and store the values in out, which has the same
+    /// length. Sampling evaluates the function in batches via this method. Override it, if the
+    /// function can be evaluated faster that way, e.g. with SIMD or on a GPU. The default calls
+    /// [`value()`](Self::value()) for each point.
+    fn values(&self, points: &[na::Point3<S>], out: &mut [S]) {
+        for (p, value) in points.iter().zip(out.iter_mut()) {
+            *value = self.value(p);
+        }
+    }
     /// Compute the normal of the function at p.
     fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S>;
+    /// Return true, if the magnitude of value never exceeds the euclidean distance between p and
+    /// the surface, e.g. for exact signed distance fields. Sampling then skips regions far away
+    /// from the surface. Otherwise every cell of the grid is sampled, which is slower, but does
+    /// not miss thin features. The default returns false.
+    fn is_exact_distance(&self) -> bool {
+        false
+    }
 }
 
 /// Trait which allows to convert Self to usize, since To<usize> is not implemented by f32 and f64.
@@ -1,26 +1,40 @@
 use crate::{
     bitset::BitSet,
     cell_configs::CELL_CONFIGS,
-    mesh::Mesh,
+    mesh::{grid_cell, neighbor_cells, triangulate, Mesh, TriangleMesh, Winding},
+    normal_grid::NormalGrid,
     plane::Plane,
     qef,
+    sign_field::SignField,
     vertex_index::{neg_offset, offset, Index, VarIndex, VertexIndex, EDGES_ON_FACE},
     AsUSize, ImplicitFunction, RealField,
 };
 use bbox::BoundingBox;
 use nalgebra as na;
 use num_traits::Float;
-use rayon::prelude::*;
+use rayon::{prelude::*, ThreadPool};
 use smallvec::SmallVec;
 use std::{
-    cell::{Cell, RefCell},
+    cell::RefCell,
     cmp,
-    collections::{BTreeSet, HashMap},
-    error, fmt,
+    collections::{BTreeSet, HashMap, HashSet},
+    error, fmt, mem,
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+#[cfg(feature = "serde")]
+use {
+    serde::{de::DeserializeOwned, Serialize},
+    std::io::{Read, Write},
 };
 
 // How accurately find zero crossings.
 const PRECISION: f32 = 0.05;
+// Number of crossing edges, whose quads are computed in one parallel batch.
+const QUAD_BATCH_SIZE: usize = 1 << 16;
+// Maximum number of results remembered by each memo, see with_memoization().
+const MEMO_CAPACITY: usize = 1 << 22;
 
 //  Edge indexes
 //
@@ -39,6 +53,7 @@ const PRECISION: f32 = 0.05;
 // Point o is the reference point of the current cell.
 // All edges go from lower indexes to higher indexes.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Edge {
     A = 0,
     B = 1,
@@ -114,15 +129,74 @@ static OUTSIDE_EDGES_PER_CORNER: Lazy<[BitSet; 8]> = Lazy::new(|| {
     ]
 });
 
+/// Errors that can occur while setting up or running a tessellation.
 #[derive(Debug)]
 pub enum DualContouringError {
+    /// The function evaluated to exactly zero on a grid point.
     HitZero(String),
+    /// The supplied bounding box is empty or not finite.
+    InvalidBoundingBox(String),
+    /// The values of a [`DenseGrid`](crate::DenseGrid) do not match its dimensions.
+    InvalidGrid(String),
+    /// The function seems to be positive inside and negative outside.
+    InvertedSign,
+    /// The function evaluated to NaN or infinity at position.
+    NonFiniteValue {
+        /// The position the function was evaluated at.
+        position: [f64; 3],
+    },
+    /// The function has the same sign everywhere in the sampled region, so there is no surface
+    /// to tessellate. Check the bounding box and the sign convention.
+    NoSurfaceInBounds,
+    /// Tessellation took longer than the duration set via
+    /// [`with_timeout()`](ManifoldDualContouring::with_timeout()).
+    Timeout,
+    /// The sampling grid has less than two grid points along an axis, because res is larger than
+    /// the extent of the (dilated) bounding box. Use a smaller res.
+    ResolutionTooCoarse {
+        /// The number of grid points along each axis.
+        dim: [usize; 3],
+    },
+    /// The sampling grid has more cells than can be addressed, because res is tiny compared to
+    /// the extent of the (dilated) bounding box. Use a larger res.
+    ResolutionTooFine {
+        /// The number of grid points along each axis.
+        dim: [usize; 3],
+    },
+    /// An internal invariant of the algorithm does not hold, which is a bug. Only checked with
+    /// [`with_invariant_checks()`](ManifoldDualContouring::with_invariant_checks()).
+    InternalInvariantViolated(String),
+    /// A vertex, that a face or cell refers to, is missing in the octtree, which is a bug. Unlike
+    /// [`InternalInvariantViolated`](Self::InternalInvariantViolated), the lookups of vertices in
+    /// the octtree while generating faces are always checked.
+    InternalState(String),
+    /// A leaf vertex of the octtree lists another one as neighbor, but not the other way round,
+    /// which yields cracks in the mesh, e.g. at sharp discontinuities of the function. Only
+    /// checked with
+    /// [`with_invariant_checks()`](ManifoldDualContouring::with_invariant_checks()).
+    AsymmetricNeighbors {
+        /// The grid index of the cell of the vertex.
+        vertex: Index,
+        /// The grid index of the cell of its neighbor.
+        neighbor: Index,
+    },
 }
 
 impl error::Error for DualContouringError {
     fn description(&self) -> &str {
         match *self {
             DualContouringError::HitZero(_) => "Hit zero value during grid sampling.",
+            DualContouringError::InvalidBoundingBox(_) => "Invalid bounding box.",
+            DualContouringError::InvalidGrid(_) => "Invalid grid.",
+            DualContouringError::InvertedSign => "Function has inverted sign.",
+            DualContouringError::NonFiniteValue { .. } => "Function value is not finite.",
+            DualContouringError::NoSurfaceInBounds => "No surface in bounding box.",
+            DualContouringError::Timeout => "Tessellation timed out.",
+            DualContouringError::ResolutionTooCoarse { .. } => "Resolution too coarse.",
+            DualContouringError::ResolutionTooFine { .. } => "Resolution too fine.",
+            DualContouringError::InternalInvariantViolated(_) => "Internal invariant violated.",
+            DualContouringError::InternalState(_) => "Invalid internal state.",
+            DualContouringError::AsymmetricNeighbors { .. } => "Asymmetric neighbors.",
         }
     }
 }
@@ -131,48 +205,189 @@ impl fmt::Display for DualContouringError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
             DualContouringError::HitZero(ref s) => write!(f, "Hit zero value for {}", s),
+            DualContouringError::InvalidBoundingBox(ref s) => {
+                write!(f, "Invalid bounding box {}", s)
+            }
+            DualContouringError::InvalidGrid(ref s) => write!(f, "Invalid grid: {}", s),
+            DualContouringError::InvertedSign => write!(
+                f,
+                "Function is positive at the bbox center and negative at its corner"
+            ),
+            DualContouringError::NonFiniteValue { position } => {
+                write!(f, "Function value is not finite at {:?}", position)
+            }
+            DualContouringError::NoSurfaceInBounds => write!(
+                f,
+                "Function has the same sign everywhere in the bounding box"
+            ),
+            DualContouringError::Timeout => write!(f, "Tessellation exceeded its timeout"),
+            DualContouringError::ResolutionTooCoarse { dim } => write!(
+                f,
+                "Sampling grid of {:?} points is too coarse, use a smaller res",
+                dim
+            ),
+            DualContouringError::ResolutionTooFine { dim } => write!(
+                f,
+                "Sampling grid of {:?} points is too large to address, use a larger res",
+                dim
+            ),
+            DualContouringError::InternalInvariantViolated(ref s) => {
+                write!(f, "Internal invariant violated: {}", s)
+            }
+            DualContouringError::InternalState(ref s) => write!(f, "Invalid internal state: {}", s),
+            DualContouringError::AsymmetricNeighbors { vertex, neighbor } => write!(
+                f,
+                "Vertex in cell {:?} has a neighbor in cell {:?}, which does not list it",
+                vertex, neighbor
+            ),
+        }
+    }
+}
+
+/// What to do, if the function seems to use an inverted sign convention, i.e. it is positive
+/// inside and negative outside the object.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SignCheck {
+    /// Do not check the sign convention.
+    Disabled,
+    /// Reverse the winding of all faces, if the sign convention is inverted.
+    Flip,
+    /// Fail with [`DualContouringError::InvertedSign`], if the sign convention is inverted.
+    Error,
+}
+
+/// The sign of the function inside the object, see
+/// [`ManifoldDualContouring::with_inside_sign()`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Sign {
+    /// Negative inside and positive outside the object. This is the default.
+    Negative,
+    /// Positive inside and negative outside the object.
+    Positive,
+}
+
+/// A coordinate axis.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Axis {
+    /// The x axis.
+    X,
+    /// The y axis.
+    Y,
+    /// The z axis.
+    Z,
+}
+
+/// Strategy to refine the point, where the surface crosses an edge of the sampling grid.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RootFinder {
+    /// Repeatedly interpolate linearly between the ends of the bracket (regula falsi).
+    Linear,
+    /// Secant steps through the last two points, falling back to bisection, if a step leaves the
+    /// bracket.
+    Secant,
+    /// Brent's method, combining bisection, secant steps and inverse quadratic interpolation.
+    Brent,
+}
+
+/// How the vertices of the mesh are computed, see
+/// [`ManifoldDualContouring::with_method()`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Method {
+    /// Simplify the mesh with an octree of QEFs, keeping it 2-manifold and sharp features.
+    ManifoldDualContouring,
+    /// Place each vertex at the mean of the surface crossings of its cell, without simplifying
+    /// the mesh. This is faster, but smooths out sharp features and yields more faces.
+    SurfaceNets,
+}
+
+/// The coordinate system of the mesh, see
+/// [`ManifoldDualContouring::with_coordinate_system()`]. The function is always evaluated in its
+/// own coordinates, which are taken to be right-handed with Z up.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CoordinateSystem {
+    /// Right-handed with Z up, e.g. for Blender. The coordinates of the function are kept.
+    RightHandedZUp,
+    /// Right-handed with Y up, e.g. for Maya or glTF. `(x, y, z)` becomes `(x, z, -y)`, like
+    /// for [`UpAxis::YUp`](crate::UpAxis::YUp) on export.
+    RightHandedYUp,
+    /// Left-handed with Y up, e.g. for Unity. `(x, y, z)` becomes `(x, z, y)`.
+    LeftHandedYUp,
+    /// Left-handed with Z up, e.g. for Unreal Engine. `(x, y, z)` becomes `(x, -y, z)`.
+    LeftHandedZUp,
+}
+
+impl CoordinateSystem {
+    // Convert a position or direction from the coordinates of the function.
+    fn convert<S: From<f32> + RealField>(self, [x, y, z]: [S; 3]) -> [S; 3] {
+        let zero: S = From::from(0f32);
+        match self {
+            CoordinateSystem::RightHandedZUp => [x, y, z],
+            CoordinateSystem::RightHandedYUp => [x, z, zero - y],
+            CoordinateSystem::LeftHandedYUp => [x, z, y],
+            CoordinateSystem::LeftHandedZUp => [x, zero - y, z],
         }
     }
+
+    // Converting to a left-handed system mirrors the mesh, which reverses the orientation of the
+    // faces, unless their winding is reversed, too.
+    fn is_left_handed(self) -> bool {
+        matches!(
+            self,
+            CoordinateSystem::LeftHandedYUp | CoordinateSystem::LeftHandedZUp
+        )
+    }
+}
+
+/// Counters collected during tessellation, returned from
+/// [`ManifoldDualContouring::stats()`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct TessellationStats {
+    /// Number of cubes, that were not refined because of
+    /// [`with_min_cell_size()`](ManifoldDualContouring::with_min_cell_size()).
+    pub num_clipped_cubes: usize,
+    /// Number of QEFs, that were not finite or had a solution, that was not finite, e.g. because
+    /// the function returned NaN normals. Their vertex is placed at the mean of the edge
+    /// crossings of its cell instead.
+    pub num_qef_fallbacks: usize,
+    /// Number of edges crossing the surface, whose quad would need cells outside of the sampled
+    /// grid, e.g. where the surface of a sign field reaches its boundary. They generate no
+    /// face, so the mesh is open there.
+    pub num_boundary_edges: usize,
+    /// Number of quads, that were dropped, since less than three of their vertices are distinct.
+    /// This is expected, where simplification merged the vertices of neighboring cells, but at
+    /// pinch points of the surface it can leave a hole in the mesh.
+    pub num_degenerate_quads: usize,
 }
 
+// Returns the color of a vertex of the mesh, see with_vertex_color().
+type VertexColorFn<'a, S> = dyn Fn([S; 3]) -> [f32; 3] + 'a;
+
 // A vertex of the mesh. This can be either a primary vertex of the sampled mesh or a vertex
 // generated by joining multiple vertices in the octree.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vertex<S: RealField> {
     index: Index,
-    qef: RefCell<qef::Qef<S>>,
+    qef: qef::Qef<S>,
     neighbors: [Vec<VarIndex>; 6],
-    parent: Cell<Option<usize>>,
+    parent: Option<usize>,
     children: Vec<usize>,
-    // Index of this vertex in the final mesh.
-    mesh_index: Cell<Option<usize>>,
     edge_intersections: [u32; 12],
     euler_characteristic: i32,
 }
 
-impl<S: RealField> Clone for Vertex<S> {
-    fn clone(&self) -> Vertex<S> {
-        Vertex {
-            index: self.index,
-            qef: self.qef.clone(),
-            neighbors: [
-                self.neighbors[0].clone(),
-                self.neighbors[1].clone(),
-                self.neighbors[2].clone(),
-                self.neighbors[3].clone(),
-                self.neighbors[4].clone(),
-                self.neighbors[5].clone(),
-            ],
-            parent: self.parent.clone(),
-            children: self.children.clone(),
-            mesh_index: self.mesh_index.clone(),
-            edge_intersections: self.edge_intersections,
-            euler_characteristic: self.euler_characteristic,
-        }
-    }
-}
+// The Euler characteristic of vertices, that must never be collapsed into, i.e. vertices at the
+// boundary of the grid, some of whose crossing edges leave it, and with
+// with_manifold_only_merges(true) parents, that would not be 2-manifold. The topology of their
+// cells is unknown or broken, so they and all their ancestors are not 2-manifold and their QEFs
+// are not solved.
+const UNMERGEABLE_EULER_CHARACTERISTIC: i32 = i32::MIN;
 
 impl<S: RealField> Vertex<S> {
+    fn is_unmergeable(&self) -> bool {
+        self.euler_characteristic == UNMERGEABLE_EULER_CHARACTERISTIC
+    }
+
     fn is_2manifold(&self) -> bool {
         if self.euler_characteristic != 1 {
             return false;
@@ -191,6 +406,7 @@ impl<S: RealField> Vertex<S> {
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EdgeIndex {
     edge: Edge,
     index: Index,
@@ -203,15 +419,37 @@ impl EdgeIndex {
             index: offset(self.index, EDGE_OFFSET[self.edge as usize]),
         }
     }
+
+    // The indices of the four cells around this edge, which contribute the vertices of its quad,
+    // in the order of QUADS. None for cells, that would have negative indices, because the edge
+    // is on one of the minimal boundary planes of the grid.
+    fn quad_cells(&self) -> [Option<Index>; 4] {
+        let mut cells = [None; 4];
+        for (cell, &quad_egde) in cells.iter_mut().zip(&QUADS[self.edge as usize]) {
+            let offset = EDGE_OFFSET[quad_egde as usize];
+            if (0..3).all(|i| self.index[i] >= offset[i]) {
+                *cell = Some(neg_offset(self.index, offset));
+            }
+        }
+        cells
+    }
 }
 
 /// Struct containing all the intermediary state for the different stages of tessellation.
 #[derive(Clone)]
 pub struct ManifoldDualContouring<'a, S: RealField> {
     function: &'a dyn ImplicitFunction<S>,
+    // The bounding box to tessellate (before dilation).
+    bbox: BoundingBox<S>,
+    // Clips the bounding box of the function, e.g. along axes it is infinite on.
+    region: Option<BoundingBox<S>>,
+    // How much bbox is grown on each side before sampling.
+    dilation: S,
     origin: na::Point3<S>,
     dim: [usize; 3],
-    mesh: RefCell<Mesh<S>>,
+    mesh: Mesh<S>,
+    // The triangles of the faces of mesh, collected along with them for tessellate_both().
+    triangles: Option<Vec<[usize; 3]>>,
     res: S,
     error: S,
     value_grid: HashMap<Index, S>,
@@ -221,19 +459,119 @@ pub struct ManifoldDualContouring<'a, S: RealField> {
     vertex_octtree: Vec<Vec<Vertex<S>>>,
     // Map from VertexIndex to vertex_octtree[0]
     vertex_index_map: HashMap<VertexIndex, usize>,
+    sign_check: SignCheck,
+    // The sign of the function inside the object.
+    inside_sign: Sign,
+    // Sampling does not descend into cubes with an edge length below this.
+    min_cell_size: Option<S>,
+    // Number of cubes, that were not refined because of min_cell_size.
+    num_clipped_cubes: usize,
+    // Number of QEF solutions, that were not finite and replaced by the mass point.
+    num_qef_fallbacks: usize,
+    // Number of crossing edges, whose quad would need cells outside of the grid.
+    num_boundary_edges: usize,
+    // Number of quads with less than three distinct vertices, which generated no face.
+    num_degenerate_quads: usize,
+    // Whether to remove values without a sign change in their neighborhood from value_grid.
+    compact: bool,
+    // Called with the centroid of each face to compute Mesh::labels.
+    face_labeler: Option<Rc<dyn Fn([S; 3]) -> u32 + 'a>>,
+    // Called with each vertex to compute Mesh::vertex_colors.
+    vertex_color: Option<Rc<VertexColorFn<'a, S>>>,
+    // Called with the solution of each QEF to get the relative error allowed there.
+    error_field: Option<Rc<dyn Fn([S; 3]) -> S + 'a>>,
+    // The error threshold of each vertex of vertex_octtree from error_field, computed while
+    // solving the QEFs. Empty without error_field.
+    error_thresholds: Vec<Vec<S>>,
+    // Whether to fail on function values, that are not finite.
+    nan_guard: bool,
+    // Whether value_grid was supplied via with_sign_field and sampling is skipped.
+    presampled: bool,
+    // Whether to compute Mesh::face_normals.
+    face_normals: bool,
+    // Precomputed normals used instead of function.normal(), where available.
+    normal_grid: Option<Rc<NormalGrid<S>>>,
+    // Values sampled on the grid and normals of the function, to reuse them after moving the
+    // origin. None, unless enabled via with_memoization.
+    value_memo: Option<RefCell<Memo<S, S>>>,
+    normal_memo: Option<RefCell<Memo<S, na::Vector3<S>>>>,
+    // Whether the winding of the mesh is fixed in one pass at the end instead of per quad.
+    deferred_winding: bool,
+    // Whether sampling also checks the center of cubes, it would not refine.
+    thin_feature_detection: bool,
+    // Factor for the diagonal of a cube, within which sampling refines exact distance fields.
+    descent_margin: S,
+    // Whether subsampling marks parents, that would not be 2-manifold, as unmergeable.
+    merge_manifold_only: bool,
+    // Whether invariants are checked in release builds too.
+    invariant_checks: bool,
+    // Weight of the bias of QEF solutions toward the mass point.
+    qef_regularization: S,
+    // The value of the function on the surface.
+    isovalue: S,
+    root_finder: RootFinder,
+    method: Method,
+    // The coordinate system, the mesh is converted to.
+    coordinate_system: CoordinateSystem,
+    // Whether to suppress all diagnostic output.
+    quiet: bool,
+    // The pool to run parallel sections in instead of the global one.
+    thread_pool: Option<Arc<ThreadPool>>,
+    timeout: Option<Duration>,
+    // When the current call times out. Set from timeout at the start of each public phase.
+    deadline: Option<Instant>,
+}
+
+// Faces of a box with outward winding. Corners are indexed as x + 2 * y + 4 * z.
+const BOX_FACES: [[usize; 4]; 6] = [
+    [0, 2, 3, 1],
+    [4, 5, 7, 6],
+    [0, 1, 5, 4],
+    [2, 6, 7, 3],
+    [0, 4, 6, 2],
+    [1, 3, 7, 5],
+];
+
+// println!, unless self.quiet is set. In quiet mode the arguments are not evaluated.
+macro_rules! log {
+    ($self:ident, $($arg:tt)*) => {
+        if !$self.quiet {
+            println!($($arg)*);
+        }
+    };
 }
 
-// Returns the next largest power of 2
-fn pow2roundup(x: usize) -> usize {
-    let mut x = x;
-    x -= 1;
-    x |= x >> 1;
-    x |= x >> 2;
-    x |= x >> 4;
-    x |= x >> 8;
-    x |= x >> 16;
-    x |= x >> 32;
-    x + 1
+// Like debug_assert!, but if checks is set, a violation returns an InternalInvariantViolated error
+// or the given error instead, even in release builds.
+macro_rules! check_invariant {
+    ($checks:expr, $cond:expr => $error:expr, $($arg:tt)+) => {
+        if ($checks || cfg!(debug_assertions)) && !$cond {
+            if $checks {
+                return Err($error);
+            }
+            panic!($($arg)+);
+        }
+    };
+    ($checks:expr, $cond:expr, $($arg:tt)+) => {
+        check_invariant!(
+            $checks,
+            $cond => DualContouringError::InternalInvariantViolated(format!($($arg)+)),
+            $($arg)+
+        )
+    };
+}
+
+// Returns the largest absolute component of v.
+fn max_abs_component<S: RealField + Float>(v: &na::Vector3<S>) -> S {
+    Float::max(
+        Float::max(Float::abs(v.x), Float::abs(v.y)),
+        Float::abs(v.z),
+    )
+}
+
+// Returns the next largest power of 2, or None, if it does not fit into usize.
+fn pow2roundup(x: usize) -> Option<usize> {
+    x.checked_next_power_of_two()
 }
 
 // Returns a BitSet containing all egdes connected to "edge" in this cell.
@@ -300,7 +638,7 @@ fn add_child_to_parent<S: RealField + Float + From<f32>>(
     child: &Vertex<S>,
     parent: &mut Vertex<S>,
 ) {
-    parent.qef.borrow_mut().merge(&*child.qef.borrow());
+    parent.qef.merge(&child.qef);
     for dim in 0..3 {
         let relevant_neighbor = dim * 2 + (child.index[dim] & 1);
         for neighbor in &child.neighbors[relevant_neighbor] {
@@ -314,10 +652,21 @@ fn add_child_to_parent<S: RealField + Float + From<f32>>(
 fn subsample_euler_characteristics<S: RealField>(
     children: &BTreeSet<usize>,
     vertices: &[Vertex<S>],
-) -> ([u32; 12], i32) {
+    checks: bool,
+) -> Result<([u32; 12], i32), DualContouringError> {
     let mut intersections = [0u32; 12];
     let mut euler = 0i32;
     let mut inner_sum = 0;
+    if children.iter().any(|&i| vertices[i].is_unmergeable()) {
+        // Cells outside of the grid are missing, so the inner edges are not counted completely.
+        // Parents of unmergeable vertices are never collapsed into, so their sums do not matter.
+        for vertex in children.iter().map(|&i| &vertices[i]) {
+            for (i, intersection) in vertex.edge_intersections.iter().enumerate() {
+                intersections[i] += intersection;
+            }
+        }
+        return Ok((intersections, UNMERGEABLE_EULER_CHARACTERISTIC));
+    }
     for vertex in children.iter().map(|i| &vertices[*i]) {
         let i = vertex.index;
         let corner_index = (i[2] & 1) << 2 | (i[1] & 1) << 1 | (i[0] & 1);
@@ -331,27 +680,72 @@ fn subsample_euler_characteristics<S: RealField>(
         }
         euler += vertex.euler_characteristic;
     }
-    debug_assert_eq!(
-        inner_sum % 4,
-        0,
+    check_invariant!(
+        checks,
+        inner_sum % 4 == 0,
         "inner_sum {} is not divisible by 4.",
         inner_sum
     );
     euler -= inner_sum as i32 / 4;
-    (intersections, euler)
+    Ok((intersections, euler))
+}
+
+// Checks, that each vertex is a neighbor of its neighbors in the opposite direction.
+fn check_neighbor_symmetry<S: RealField>(
+    vertices: &[Vertex<S>],
+    checks: bool,
+) -> Result<(), DualContouringError> {
+    for vi in 0..vertices.len() {
+        for np in 0..vertices[vi].neighbors.len() {
+            for ni in 0..vertices[vi].neighbors[np].len() {
+                match vertices[vi].neighbors[np][ni] {
+                    VarIndex::VertexIndex(_) => panic!("unexpected VertexIndex."),
+                    VarIndex::Index(i) => {
+                        check_invariant!(
+                            checks,
+                            vertices[i].neighbors[np ^ 1].contains(&VarIndex::Index(vi))
+                                => DualContouringError::AsymmetricNeighbors {
+                                    vertex: vertices[vi].index,
+                                    neighbor: vertices[i].index,
+                                },
+                            "vertex[{}].neighbors[{}][{}]=={:?},
+                             but vertex[{}].neighbors[{}]=={:?}\n{:?} vs. {:?}",
+                            vi,
+                            np,
+                            ni,
+                            vertices[vi].neighbors[np][ni],
+                            i,
+                            np ^ 1,
+                            vertices[i].neighbors[np ^ 1],
+                            vertices[vi],
+                            vertices[i]
+                        );
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
 }
 
-fn subsample_octtree<S: RealField + Float + From<f32>>(base: &[Vertex<S>]) -> Vec<Vertex<S>> {
+// Builds the next layer of the octtree from base. If manifold_only is set, parents, that would not
+// be 2-manifold, are marked unmergeable.
+fn subsample_octtree<S: RealField + Float + From<f32>>(
+    base: &mut [Vertex<S>],
+    manifold_only: bool,
+    checks: bool,
+) -> Result<Vec<Vertex<S>>, DualContouringError> {
     let mut result = Vec::new();
-    for (i, vertex) in base.iter().enumerate() {
-        if vertex.parent.get() == None {
+    for i in 0..base.len() {
+        if base[i].parent.is_none() {
             let mut neighbor_set = BTreeSet::new();
             neighbor_set.insert(i);
-            add_connected_vertices_in_subcell(base, vertex, &mut neighbor_set);
-            let (intersections, euler) = subsample_euler_characteristics(&neighbor_set, base);
+            add_connected_vertices_in_subcell(base, &base[i], &mut neighbor_set);
+            let (intersections, euler) =
+                subsample_euler_characteristics(&neighbor_set, base, checks)?;
             let mut parent = Vertex {
-                index: half_index(&vertex.index),
-                qef: RefCell::new(qef::Qef::new(&[], BoundingBox::neg_infinity())),
+                index: half_index(&base[i].index),
+                qef: qef::Qef::new(&[], BoundingBox::neg_infinity()),
                 neighbors: [
                     Vec::new(),
                     Vec::new(),
@@ -360,24 +754,32 @@ fn subsample_octtree<S: RealField + Float + From<f32>>(base: &[Vertex<S>]) -> Ve
                     Vec::new(),
                     Vec::new(),
                 ],
-                parent: Cell::new(None),
+                parent: None,
                 children: Vec::new(),
-                mesh_index: Cell::new(None),
                 edge_intersections: intersections,
                 euler_characteristic: euler,
             };
+            if manifold_only && !parent.is_2manifold() {
+                parent.euler_characteristic = UNMERGEABLE_EULER_CHARACTERISTIC;
+            }
             for &neighbor_index in &neighbor_set {
-                let child = &base[neighbor_index];
-                debug_assert!(
-                    child.parent.get() == None,
+                let child = &mut base[neighbor_index];
+                check_invariant!(
+                    checks,
+                    child.parent.is_none(),
                     "child #{:?} already has parent #{:?}",
                     neighbor_index,
-                    child.parent.get().unwrap()
+                    child.parent.unwrap()
+                );
+                check_invariant!(
+                    checks,
+                    !parent.children.contains(&neighbor_index),
+                    "child #{:?} was added twice",
+                    neighbor_index
                 );
-                debug_assert!(!parent.children.contains(&neighbor_index));
                 parent.children.push(neighbor_index);
                 add_child_to_parent(child, &mut parent);
-                child.parent.set(Some(result.len()));
+                child.parent = Some(result.len());
             }
             result.push(parent);
         }
@@ -387,31 +789,104 @@ fn subsample_octtree<S: RealField + Float + From<f32>>(base: &[Vertex<S>]) -> Ve
             for neighbor in neighbor_vec.iter_mut() {
                 match *neighbor {
                     VarIndex::VertexIndex(_) => panic!("unexpected VertexIndex in normal node."),
-                    VarIndex::Index(i) => {
-                        *neighbor = VarIndex::Index(base[i].parent.get().unwrap())
-                    }
+                    VarIndex::Index(i) => *neighbor = VarIndex::Index(base[i].parent.unwrap()),
                 }
             }
         }
     }
-    result
+    Ok(result)
 }
 
+// Results of the function by position, to reuse them for points within epsilon after moving the
+// origin. Only results of earlier attempts are looked up, so points of the same attempt never
+// share a result. Entries are bucketed in a grid with spacing epsilon. At most capacity results
+// are remembered, later ones are dropped.
+#[derive(Clone)]
+struct Memo<S: RealField, T> {
+    epsilon: S,
+    capacity: usize,
+    entries: HashMap<[i64; 3], Vec<(na::Point3<S>, T)>>,
+    // The number of results in entries.
+    len: usize,
+    // Results of the current attempt, moved to entries by retry().
+    recent: Vec<(na::Point3<S>, T)>,
+}
+
+impl<S: RealField + Float, T: Copy> Memo<S, T> {
+    fn new(epsilon: S, capacity: usize) -> Self {
+        Memo {
+            epsilon,
+            capacity,
+            entries: HashMap::new(),
+            len: 0,
+            recent: Vec::new(),
+        }
+    }
+
+    fn cell(&self, p: &na::Point3<S>) -> [i64; 3] {
+        grid_cell(&[p.x, p.y, p.z], self.epsilon)
+    }
+
+    // The entry of an earlier attempt closest to p, if it is within epsilon.
+    fn get(&self, p: &na::Point3<S>) -> Option<T> {
+        let mut best = (self.epsilon, None);
+        for neighbor in neighbor_cells(self.cell(p)) {
+            for &(q, entry) in self.entries.get(&neighbor).into_iter().flatten() {
+                let distance = (q - p).norm();
+                if distance <= best.0 {
+                    best = (distance, Some(entry));
+                }
+            }
+        }
+        best.1
+    }
+
+    fn insert(&mut self, p: na::Point3<S>, entry: T) {
+        if self.len + self.recent.len() < self.capacity {
+            self.recent.push((p, entry));
+        }
+    }
+
+    // Make the results of the current attempt available to the next one.
+    fn retry(&mut self) {
+        self.len += self.recent.len();
+        for (p, entry) in std::mem::take(&mut self.recent) {
+            let cell = self.cell(&p);
+            self.entries.entry(cell).or_default().push((p, entry));
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.len = 0;
+        self.recent.clear();
+    }
+}
+
+// Measures the time between calls to elapsed(). Disabled timers never query the clock.
 struct Timer {
-    t: std::time::Instant,
+    t: Option<std::time::Instant>,
 }
 
 impl Timer {
-    fn new() -> Timer {
+    fn new(enabled: bool) -> Timer {
         Timer {
-            t: std::time::Instant::now(),
+            t: if enabled {
+                Some(std::time::Instant::now())
+            } else {
+                None
+            },
         }
     }
     fn elapsed(&mut self) -> std::time::Duration {
-        let now = std::time::Instant::now();
-        let result = now - self.t;
-        self.t = now;
-        result
+        match self.t {
+            Some(t) => {
+                let now = std::time::Instant::now();
+                self.t = Some(now);
+                now - t
+            }
+            None => std::time::Duration::default(),
+        }
     }
 }
 
@@ -424,574 +899,2650 @@ impl<'a, S: From<f32> + RealField + Float + AsUSize> ManifoldDualContouring<'a,
         f: &'a dyn ImplicitFunction<S>,
         res: S,
         relative_error: S,
+    ) -> ManifoldDualContouring<'a, S> {
+        Self::from_bbox(f, f.bbox().clone(), res, relative_error)
+    }
+
+    /// Constructor which tessellates inside bbox instead of f.bbox().
+    /// This is useful, if f.bbox() is overly conservative or unreliable.
+    /// f: function to tessellate
+    /// bbox: bounding box to search for surfaces in. Must be finite and non-empty.
+    /// res: resolution
+    /// relative_error: acceptable error threshold when simplifying the mesh.
+    pub fn new_with_bbox(
+        f: &'a dyn ImplicitFunction<S>,
+        bbox: BoundingBox<S>,
+        res: S,
+        relative_error: S,
+    ) -> Result<ManifoldDualContouring<'a, S>, DualContouringError> {
+        for i in 0..3 {
+            if !Float::is_finite(bbox.min[i]) || !Float::is_finite(bbox.max[i]) {
+                return Err(DualContouringError::InvalidBoundingBox(format!(
+                    "{:?} is not finite",
+                    bbox
+                )));
+            }
+            if bbox.max[i] <= bbox.min[i] {
+                return Err(DualContouringError::InvalidBoundingBox(format!(
+                    "{:?} is empty",
+                    bbox
+                )));
+            }
+        }
+        Ok(Self::from_bbox(f, bbox, res, relative_error))
+    }
+
+    fn from_bbox(
+        f: &'a dyn ImplicitFunction<S>,
+        bbox: BoundingBox<S>,
+        res: S,
+        relative_error: S,
     ) -> ManifoldDualContouring<'a, S> {
         let one: S = From::from(1f32);
-        let mut bbox = f.bbox().clone();
-        bbox.dilate(one + res * From::from(1.1f32));
-        ManifoldDualContouring {
+        let mut mdc = ManifoldDualContouring {
             function: f,
-            origin: bbox.min,
-            dim: [
-                Float::ceil(bbox.dim()[0] / res).as_usize(),
-                Float::ceil(bbox.dim()[1] / res).as_usize(),
-                Float::ceil(bbox.dim()[2] / res).as_usize(),
-            ],
-            mesh: RefCell::new(Mesh {
+            bbox,
+            region: None,
+            dilation: one + res * From::from(1.1f32),
+            origin: na::Point3::origin(),
+            dim: [0, 0, 0],
+            mesh: Mesh {
                 vertices: Vec::new(),
                 faces: Vec::new(),
-            }),
+                labels: None,
+                face_normals: None,
+                vertex_colors: None,
+            },
+            triangles: None,
             res,
             error: res * relative_error,
             value_grid: HashMap::new(),
             edge_grid: RefCell::new(HashMap::new()),
             vertex_octtree: Vec::new(),
             vertex_index_map: HashMap::new(),
+            sign_check: SignCheck::Disabled,
+            inside_sign: Sign::Negative,
+            min_cell_size: None,
+            num_clipped_cubes: 0,
+            num_qef_fallbacks: 0,
+            num_boundary_edges: 0,
+            num_degenerate_quads: 0,
+            compact: true,
+            face_labeler: None,
+            vertex_color: None,
+            error_field: None,
+            error_thresholds: Vec::new(),
+            nan_guard: false,
+            presampled: false,
+            face_normals: false,
+            normal_grid: None,
+            value_memo: None,
+            normal_memo: None,
+            thin_feature_detection: false,
+            descent_margin: one,
+            merge_manifold_only: false,
+            invariant_checks: false,
+            qef_regularization: S::zero(),
+            isovalue: S::zero(),
+            deferred_winding: false,
+            root_finder: RootFinder::Linear,
+            method: Method::ManifoldDualContouring,
+            coordinate_system: CoordinateSystem::RightHandedZUp,
+            quiet: false,
+            thread_pool: None,
+            timeout: None,
+            deadline: None,
+        };
+        mdc.dilate_bbox(mdc.dilation);
+        mdc
+    }
+
+    /// Prepare to tessellate f instead of the current function, keeping all settings. The
+    /// sampled state, the results remembered via
+    /// [`with_memoization()`](Self::with_memoization()) and the mesh are cleared, but their
+    /// allocations are reused, which saves reallocating them when tessellating many functions in
+    /// a row. Only the sampled values are shrunk, if they are compacted, see
+    /// [`with_compact_value_grid()`](Self::with_compact_value_grid()). A sign field supplied via
+    /// [`with_sign_field()`](Self::with_sign_field()) is dropped, so f is sampled.
+    pub fn reset_for(&mut self, f: &'a dyn ImplicitFunction<S>) {
+        self.function = f;
+        self.bbox = match self.region {
+            Some(ref region) => f.bbox().intersection(region),
+            None => f.bbox().clone(),
+        };
+        self.dilate_bbox(self.dilation);
+        self.value_grid.clear();
+        self.edge_grid.borrow_mut().clear();
+        self.vertex_octtree.clear();
+        self.vertex_index_map.clear();
+        self.clear_mesh();
+        self.num_clipped_cubes = 0;
+        self.presampled = false;
+        if let Some(ref memo) = self.value_memo {
+            memo.borrow_mut().clear();
+        }
+        if let Some(ref memo) = self.normal_memo {
+            memo.borrow_mut().clear();
         }
     }
-    /// Tessellate the given function.
-    pub fn tessellate(&mut self) -> Option<Mesh<S>> {
-        println!(
-            "ManifoldDualContouring: res: {:} {:?}",
-            self.res,
-            self.function.bbox()
-        );
-        loop {
-            match self.try_tessellate() {
-                Ok(mesh) => return Some(mesh),
-                // Tessellation failed, b/c the value in one of the grid cells was exactly zero.
-                // Retry with some random padding and hope for the best.
-                Err(e) => {
-                    let padding = na::Vector3::new(
-                        -self.res / From::from(10. + rand::random::<f32>().abs()),
-                        -self.res / From::from(10. + rand::random::<f32>().abs()),
-                        -self.res / From::from(10. + rand::random::<f32>().abs()),
-                    );
-                    println!("Error: {:?}. moving by {:?} and retrying.", e, padding);
-                    self.origin += padding;
-                    self.value_grid.clear();
-                    self.mesh.borrow_mut().vertices.clear();
-                    self.mesh.borrow_mut().faces.clear();
-                    self.vertex_octtree.clear();
-                    self.vertex_index_map.clear();
-                }
+
+    // Set origin and dim to sample bbox grown by dilation on each side.
+    fn dilate_bbox(&mut self, dilation: S) {
+        self.dilation = dilation;
+        let mut dilated_bbox = self.bbox.clone();
+        dilated_bbox.dilate(dilation);
+        self.origin = dilated_bbox.min;
+        // Infinite axes are rejected with an error before sampling, see check_bbox().
+        self.dim = [0, 1, 2].map(|i| {
+            let dim = Float::ceil(dilated_bbox.dim()[i] / self.res);
+            if Float::is_finite(dim) {
+                dim.as_usize()
+            } else {
+                0
             }
-        }
+        });
     }
 
-    fn tessellation_step1(&mut self) -> Option<DualContouringError> {
-        let maxdim = cmp::max(self.dim[0], cmp::max(self.dim[1], self.dim[2]));
-        let origin = self.origin;
-        let origin_value = self.function.value(&origin);
+    // Fail, if the bounding box to sample is not finite or empty.
+    fn check_bbox(&self) -> Option<DualContouringError> {
+        for (i, axis) in ["x", "y", "z"].iter().enumerate() {
+            if !Float::is_finite(self.bbox.min[i]) || !Float::is_finite(self.bbox.max[i]) {
+                return Some(DualContouringError::InvalidBoundingBox(format!(
+                    "{:?} is not finite along {}, restrict it via with_region()",
+                    self.bbox, axis
+                )));
+            }
+            if self.bbox.max[i] < self.bbox.min[i] {
+                return Some(DualContouringError::InvalidBoundingBox(format!(
+                    "{:?} is empty",
+                    self.bbox
+                )));
+            }
+        }
+        None
+    }
 
-        self.sample_value_grid([0, 0, 0], origin, pow2roundup(maxdim), origin_value)
+    /// Only tessellate the function within region, i.e. clip the function's bounding box to it.
+    /// This is needed for functions, whose bounding box is infinite along some axis, e.g. an
+    /// infinite cylinder. The mesh is open, where the surface leaves the (dilated) region.
+    /// Otherwise tessellation fails with [`DualContouringError::InvalidBoundingBox`]. Unlike
+    /// [`new_with_bbox()`](Self::new_with_bbox()) this never grows the bounding box, and the
+    /// region is kept by [`reset_for()`](Self::reset_for()).
+    pub fn with_region(mut self, region: BoundingBox<S>) -> Self {
+        self.bbox = self.function.bbox().intersection(&region);
+        self.region = Some(region);
+        if !self.presampled {
+            self.dilate_bbox(self.dilation);
+        }
+        self
     }
 
-    // This method does the main work of tessellation.
-    // It may fail, if the value in one of the grid cells yields exactly zero.
-    fn try_tessellate(&mut self) -> Result<Mesh<S>, DualContouringError> {
-        let mut t = Timer::new();
-        if let Some(e) = self.tessellation_step1() {
-            return Err(e);
+    /// Grow the bounding box by dilation on each side before sampling. Defaults to 1 + 1.1 * res.
+    /// Sampling and memory cost grow with the sampled volume, so for a large res a smaller
+    /// dilation can be much cheaper. If the padding is too small, the surface may touch the
+    /// boundary of the sampled region and is not closed there. Has no effect on a sign field
+    /// supplied via [`with_sign_field()`](Self::with_sign_field()).
+    pub fn with_bbox_dilation(mut self, dilation: S) -> Self {
+        if !self.presampled {
+            self.dilate_bbox(dilation);
         }
-        let total_cells = self.dim[0] * self.dim[1] * self.dim[2];
-        println!(
-            "generated value_grid with {:} % of {:} cells in {:?}.",
-            (100 * self.value_grid.len()) as f64 / total_cells as f64,
-            total_cells,
-            t.elapsed()
-        );
+        self
+    }
 
-        self.compact_value_grid();
-        println!(
-            "compacted value_grid, now {:} % of {:} cells in {:?}.",
-            (100 * self.value_grid.len()) as f64 / total_cells as f64,
-            total_cells,
-            t.elapsed()
-        );
+    /// Check whether the function is positive inside and negative outside the object,
+    /// by sampling the center and a corner of the bounding box. Disabled by default.
+    pub fn with_sign_check(mut self, sign_check: SignCheck) -> Self {
+        self.sign_check = sign_check;
+        self
+    }
 
-        self.generate_edge_grid();
+    /// Treat grid points with inside_sign as inside the object, [`Sign::Negative`] by default.
+    /// With [`Sign::Positive`] the faces point from positive to negative values and the
+    /// connectivity within the cells is resolved from the positive side, like for the negated
+    /// function. A [`SignCheck`] then checks for the inverse convention.
+    pub fn with_inside_sign(mut self, inside_sign: Sign) -> Self {
+        self.inside_sign = inside_sign;
+        self
+    }
 
-        println!(
-            "generated edge_grid with {} edges: {:?}",
-            self.edge_grid.borrow().len(),
-            t.elapsed()
-        );
+    /// Stop refining the sampling grid once a cube's edge length reaches min_cell_size, even if
+    /// the cube might contain surface. This bounds the number of sampled cells for functions with
+    /// very thin or spiky features, at the cost of dropping those features. The mesh will have
+    /// holes where this happens.
+    pub fn with_min_cell_size(mut self, min_cell_size: S) -> Self {
+        self.min_cell_size = Some(min_cell_size);
+        self
+    }
 
-        let (leafs, index_map) = self.generate_leaf_vertices();
-        self.vertex_index_map = index_map;
-        self.vertex_octtree.push(leafs);
+    /// Whether to delete sampled values, that have no neighbor with opposing sign, before
+    /// generating the mesh. Enabled by default. Disable it to keep the full sampled sign field
+    /// available via [`occupied_cells()`](Self::occupied_cells()) or
+    /// [`sample_sign_field()`](Self::sample_sign_field()), e.g. for inside/outside queries. The
+    /// mesh is the same either way, but the uncompacted grid typically uses about ten times more
+    /// memory.
+    pub fn with_compact_value_grid(mut self, compact: bool) -> Self {
+        self.compact = compact;
+        self
+    }
 
-        println!(
-            "generated {:?} leaf vertices: {:?}",
-            self.vertex_octtree[0].len(),
-            t.elapsed()
-        );
+    /// Label each face of the mesh with the result of labeler evaluated at the face's centroid.
+    /// The labels are returned in [`Mesh::labels`], e.g. to tell which part of a union a face
+    /// belongs to.
+    pub fn with_face_labeler<F: Fn([S; 3]) -> u32 + 'a>(mut self, labeler: F) -> Self {
+        self.face_labeler = Some(Rc::new(labeler));
+        self
+    }
 
-        loop {
-            let next = subsample_octtree(self.vertex_octtree.last().unwrap());
-            if next.len() == self.vertex_octtree.last().unwrap().len() {
-                break;
-            }
-            self.vertex_octtree.push(next);
-        }
-        println!("subsampled octtree {:?}", t.elapsed());
+    /// Color each vertex of the mesh with the result of f evaluated at the vertex, e.g. to
+    /// visualize a secondary field or the curvature on the surface. The colors are returned in
+    /// [`Mesh::vertex_colors`] and written to OBJ and PLY files.
+    pub fn with_vertex_color<F: Fn([S; 3]) -> [f32; 3] + 'a>(mut self, f: F) -> Self {
+        self.vertex_color = Some(Rc::new(f));
+        self
+    }
 
-        let num_qefs_solved = self.solve_qefs();
+    /// Vary the relative error spatially: a vertex of the octtree may replace its children, if the
+    /// error of its QEF is at most f of the vertex position times res, instead of relative_error
+    /// times res. E.g. return a small error close to a region of interest and a larger one
+    /// elsewhere to get a mesh, that is only dense there. This replaces relative_error, also
+    /// the errors passed to [`tessellate_lods()`](Self::tessellate_lods()).
+    pub fn with_error_field<F: Fn([S; 3]) -> S + 'a>(mut self, f: F) -> Self {
+        self.error_field = Some(Rc::new(f));
+        self
+    }
 
-        println!("solved {} qefs: {:?}", num_qefs_solved, t.elapsed());
+    /// Compute a normal for each face of the mesh, returned in [`Mesh::face_normals`]. Each face is
+    /// dual to an edge of the sampling grid and gets the function's normal where the surface
+    /// crosses that edge. This is closer to the function than normals computed from the
+    /// (possibly degenerate) mesh geometry. Disabled by default.
+    pub fn with_face_normals(mut self, face_normals: bool) -> Self {
+        self.face_normals = face_normals;
+        self
+    }
 
-        for edge_index in self.edge_grid.borrow().keys() {
-            self.compute_quad(*edge_index);
-        }
-        println!("generated quads: {:?}", t.elapsed());
+    /// Choose how to refine the points, where the surface crosses the edges of the sampling grid.
+    /// For smooth functions [`RootFinder::Secant`] and [`RootFinder::Brent`] need fewer function
+    /// evaluations. Defaults to [`RootFinder::Linear`].
+    pub fn with_root_finder(mut self, root_finder: RootFinder) -> Self {
+        self.root_finder = root_finder;
+        self
+    }
 
-        println!(
-            "computed mesh with {:?} faces.",
-            self.mesh.borrow().faces.len()
-        );
+    /// Choose how to compute the vertices of the mesh. [`Method::SurfaceNets`] skips subsampling
+    /// the octree and solving QEFs, which makes it faster, if neither sharp features nor a
+    /// simplified mesh are needed. Vertices are still separated per manifold sheet within each
+    /// cell. relative_error has no effect then. Defaults to
+    /// [`Method::ManifoldDualContouring`].
+    pub fn with_method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
 
-        Ok(self.mesh.borrow().clone())
+    /// Convert the vertices and face normals of the mesh to coordinate_system once it is
+    /// generated, instead of on each export. This also applies to the faces passed to the sink of
+    /// [`tessellate_streaming()`](Self::tessellate_streaming()). For a left-handed system the
+    /// winding of the faces is reversed, too, so they keep facing in the direction of their face
+    /// normals. Face labels and vertex colors are computed from the coordinates of the function.
+    /// Defaults to [`CoordinateSystem::RightHandedZUp`], which keeps the coordinates of the
+    /// function.
+    pub fn with_coordinate_system(mut self, coordinate_system: CoordinateSystem) -> Self {
+        self.coordinate_system = coordinate_system;
+        self
     }
 
-    fn sample_value_grid(
-        &mut self,
-        idx: Index,
-        pos: na::Point3<S>,
-        size: usize,
-        val: S,
-    ) -> Option<DualContouringError> {
-        debug_assert!(size > 1);
-        let mut midx = idx;
-        let size = size / 2;
-        let size_s: S = From::from(size as f32);
-        let vpos = [
-            pos,
-            pos + na::Vector3::new(self.res, self.res, self.res) * size_s,
-        ];
-        let sub_cube_diagonal = size_s * self.res * Float::sqrt(From::from(3f32));
+    /// Suppress all diagnostic output and timing. In quiet mode no messages are formatted and the
+    /// clock is never queried.
+    pub fn with_quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
 
-        for z in 0..2 {
-            for y in 0..2 {
-                for x in 0..2 {
-                    let mpos = na::Point3::new(vpos[x].x, vpos[y].y, vpos[z].z);
-                    let value = if midx == idx {
-                        val
-                    } else {
-                        self.function.value(&mpos)
-                    };
+    /// Instead of orienting each quad by looking up the sign of the grid point at its edge, orient
+    /// all faces in one pass at the end via [`Mesh::make_winding_consistent()`]. This saves a
+    /// lookup per quad on large meshes and yields outwards facing components regardless of the
+    /// sign convention, so a [`SignCheck::Flip`] is not applied. Disabled by default.
+    pub fn with_deferred_winding(mut self, deferred_winding: bool) -> Self {
+        self.deferred_winding = deferred_winding;
+        self
+    }
 
-                    if value == From::from(0f32) {
-                        return Some(DualContouringError::HitZero(format!("{}", mpos)));
-                    }
+    /// Check the internal invariants of the algorithm, e.g. that neighbors in the octtree refer
+    /// to each other, even in release builds, where they are skipped otherwise. A violation,
+    /// which is a bug, fails instead of producing a broken mesh: with
+    /// [`DualContouringError::AsymmetricNeighbors`] for neighbors, that do not refer to each
+    /// other, and with [`DualContouringError::InternalInvariantViolated`] for the other
+    /// invariants. Disabled by default.
+    pub fn with_invariant_checks(mut self, invariant_checks: bool) -> Self {
+        self.invariant_checks = invariant_checks;
+        self
+    }
 
-                    if size > 1 && Float::abs(value) <= sub_cube_diagonal {
-                        if let Some(e) = self.sample_value_grid(midx, mpos, size, value) {
-                            return Some(e);
-                        }
+    /// While sampling, also evaluate the center of each cube, that would not be refined, because
+    /// its value is too far from zero, and refine it anyway, if the center has a different sign.
+    /// This catches thin walls and sheets, that the corner values alone miss, e.g. if the
+    /// function overestimates the distance. Afterwards the surface is followed into neighboring
+    /// cubes, that were not refined, by sampling the missing corners of all cells next to a sign
+    /// change. It costs one extra evaluation per cube, that is not refined, which roughly doubles
+    /// the evaluations away from the surface, plus the evaluations for following the surface.
+    /// Disabled by default.
+    pub fn with_thin_feature_detection(mut self, thin_feature_detection: bool) -> Self {
+        self.thin_feature_detection = thin_feature_detection;
+        self
+    }
+
+    /// Scale the distances from the surface, within which cubes are refined while sampling, by
+    /// margin. A cube is refined, if the magnitude of the value at its first corner is at most
+    /// margin times the length of its diagonal. Before its corners are evaluated, a cube is
+    /// skipped along with its sub-cubes, if the magnitude of its center value exceeds margin times
+    /// the diagonal of a sub-cube grown by res on each side, i.e. of a cube with an edge length of
+    /// half the cube's plus 2 * res. Both are exact for distance fields. A margin above 1 is
+    /// conservative for functions, that slightly overestimate the distance, and avoids missing
+    /// features. A margin below 1 prunes more cubes and is faster, but may miss parts of the
+    /// surface. This only applies to functions, whose
+    /// [`is_exact_distance()`](ImplicitFunction::is_exact_distance()) returns true. Defaults to 1.
+    pub fn with_descent_margin(mut self, margin: S) -> Self {
+        self.descent_margin = margin;
+        self
+    }
+
+    /// If manifold_only_merges is true, children are only merged into a parent vertex of the
+    /// octree, if the parent's cell is 2-manifold. A parent, that would not be, and all its
+    /// ancestors are marked as unmergeable while subsampling, so their QEFs are never solved and
+    /// the mesh vertices of their descendants never collapse into them. Otherwise non-manifold
+    /// parents are built and solved, and only rejected when collapsing vertices. This avoids
+    /// wasting QEF solves on surfaces with many non-manifold cells, e.g. thin features or high
+    /// genus, but also stops collapsing above them, which may yield more faces. Defaults to false.
+    pub fn with_manifold_only_merges(mut self, manifold_only_merges: bool) -> Self {
+        self.merge_manifold_only = manifold_only_merges;
+        self
+    }
+
+    /// Bias the solutions of the QEFs, which place the vertices, toward the mass point of the
+    /// surface crossings in their cells. regularization weights the squared distance to the mass
+    /// point against the squared distances to the tangent planes of the crossings. Higher values
+    /// round off corners and edges, but are safer with noisy normals and nearly singular QEFs.
+    /// Lower values keep features sharper, but noisy normals may yield spikes. Zero solves the
+    /// plain QEFs. Defaults to 0.
+    pub fn with_qef_regularization(mut self, regularization: S) -> Self {
+        self.qef_regularization = regularization;
+        self
+    }
+
+    /// Extract the surface, where the function evaluates to isovalue instead of zero, e.g. a
+    /// level set of a density grid. Values below isovalue are inside. The sampled values, e.g. in
+    /// a [`SignField`] from [`sample_sign_field()`](Self::sample_sign_field()), are relative to
+    /// isovalue. Defaults to 0.
+    pub fn with_isovalue(mut self, isovalue: S) -> Self {
+        self.isovalue = isovalue;
+        self
+    }
+
+    /// Give up with [`DualContouringError::Timeout`], if a call to
+    /// [`tessellate()`](Self::tessellate()), [`build_octree()`](Self::build_octree()) or
+    /// [`tessellate_from_state()`](Self::tessellate_from_state()) takes longer than timeout. The
+    /// time is checked between the phases of tessellation and after each level of sampling, so
+    /// a single slow phase may overrun it. No timeout by default.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Remember the values sampled on the grid and the normals of the function, and reuse them
+    /// for points within epsilon, when sampling is retried, because it hit a value of exactly
+    /// zero. Only results of earlier attempts are reused, so points of the same attempt never
+    /// share a result. The retry moves the origin by about a tenth of res along each axis, so an
+    /// epsilon of res / 4 skips almost all evaluations of the first attempt. Reused values and
+    /// normals are those of a point up to epsilon away, so larger epsilons cost accuracy. Values
+    /// of zero are never remembered. At most 2^22 values and as many normals are remembered.
+    /// This saves time for expensive functions at the cost of memory. None disables it, which is
+    /// the default.
+    pub fn with_memoization(mut self, epsilon: Option<S>) -> Self {
+        self.value_memo = epsilon.map(|epsilon| RefCell::new(Memo::new(epsilon, MEMO_CAPACITY)));
+        self.normal_memo = epsilon.map(|epsilon| RefCell::new(Memo::new(epsilon, MEMO_CAPACITY)));
+        self
+    }
+
+    /// Check every function value while sampling and fail with
+    /// [`DualContouringError::NonFiniteValue`] on the first one, that is NaN or infinite.
+    /// Without this, such values silently corrupt the mesh. Disabled by default.
+    pub fn with_nan_guard(mut self, nan_guard: bool) -> Self {
+        self.nan_guard = nan_guard;
+        self
+    }
+
+    /// Tessellate a previously sampled sign field instead of sampling the function again. The
+    /// function is still used to locate the surface between grid points, so field must have been
+    /// sampled from the same function. The resolution of field replaces res.
+    pub fn with_sign_field(mut self, field: SignField<S>) -> Self {
+        self.error = self.error / self.res * field.res;
+        self.origin = field.origin;
+        self.res = field.res;
+        self.dim = field.dim;
+        self.value_grid = field.values;
+        self.num_clipped_cubes = field.num_clipped_cubes;
+        self.presampled = true;
+        self
+    }
+
+    /// Run the parallel sections of tessellation in pool instead of rayon's global thread pool,
+    /// e.g. to bound the number of threads used by each of many concurrent tessellations. The
+    /// pool can be shared between tessellations. Methods of the resulting [`Mesh`] use the
+    /// global pool, unless they are called from within
+    /// [`ThreadPool::install()`](rayon::ThreadPool::install()).
+    pub fn with_thread_pool(mut self, pool: Arc<ThreadPool>) -> Self {
+        self.thread_pool = Some(pool);
+        self
+    }
+
+    /// Use the normals in grid to locate the surface instead of evaluating
+    /// [`normal()`](ImplicitFunction::normal()) of the function, which can be expensive. The
+    /// normal at each crossing of the surface with the sampling grid is interpolated
+    /// trilinearly from the grid. The function's normal is only evaluated, where the grid does
+    /// not cover all corners of the cell around the crossing. The grid does not need to be
+    /// aligned with the sampling grid.
+    pub fn with_normal_grid(mut self, grid: NormalGrid<S>) -> Self {
+        self.normal_grid = Some(Rc::new(grid));
+        self
+    }
+
+    /// Sample the function without generating a mesh, e.g. to tessellate it later via
+    /// [`with_sign_field()`](Self::with_sign_field()).
+    pub fn sample_sign_field(&mut self) -> Result<SignField<S>, DualContouringError> {
+        loop {
+            match self.tessellation_step1() {
+                None => break,
+                Some(e @ DualContouringError::HitZero(_)) => self.move_origin(&e),
+                Some(e) => return Err(e),
+            }
+        }
+        if self.compact {
+            self.compact_value_grid();
+        }
+        Ok(SignField {
+            origin: self.origin,
+            res: self.res,
+            dim: self.dim,
+            values: self.value_grid.clone(),
+            num_clipped_cubes: self.num_clipped_cubes,
+            inside_sign: self.inside_sign,
+        })
+    }
+
+    /// Intersect the surface with the plane perpendicular to axis at coord and return the
+    /// intersection as line segments. Segment end points are given in the remaining two
+    /// coordinates in ascending order, e.g. (x, z) for [`Axis::Y`].
+    ///
+    /// This only needs the sampled grid, so it is a cheap preview: call
+    /// [`sample_sign_field()`](Self::sample_sign_field()) (or [`tessellate()`](Self::tessellate()))
+    /// first, otherwise the result is empty.
+    pub fn slice(&self, axis: Axis, coord: S) -> Vec<[[S; 2]; 2]> {
+        let axis = axis as usize;
+        let (u, v) = match axis {
+            0 => (1, 2),
+            1 => (0, 2),
+            _ => (0, 1),
+        };
+        let mut result = Vec::new();
+        let layer = (coord - self.origin[axis]) / self.res;
+        if Float::is_nan(layer) || layer < S::zero() {
+            return result;
+        }
+        let layer = Float::floor(layer).as_usize();
+        // The position of a grid point projected onto the plane and the function value there.
+        let mut corners = HashMap::new();
+        let mut corner = |idx: Index| {
+            *corners.entry(idx).or_insert_with(|| {
+                let mut p = self.index_to_world(idx);
+                p[axis] = coord;
+                (p, self.function_value(&p))
+            })
+        };
+        for idx in self.value_grid.keys().filter(|idx| idx[axis] == layer) {
+            // Only look at cells, that are sampled completely and contain the surface.
+            let mut num_inside = 0;
+            let mut complete = true;
+            for offset in 0..8 {
+                let mut corner_idx = *idx;
+                corner_idx[u] += offset & 1;
+                corner_idx[v] += (offset >> 1) & 1;
+                corner_idx[axis] += offset >> 2;
+                match self.value_grid.get(&corner_idx) {
+                    Some(&value) if value < S::zero() => num_inside += 1,
+                    Some(_) => {}
+                    None => complete = false,
+                }
+            }
+            if !complete || num_inside == 0 || num_inside == 8 {
+                continue;
+            }
+            // Marching squares on the cell's face in the plane. Corners are ordered
+            // counterclockwise, edge i connects corners i and (i + 1) % 4.
+            let mut square = [(na::Point3::origin(), S::zero()); 4];
+            for (i, &(du, dv)) in [(0, 0), (1, 0), (1, 1), (0, 1)].iter().enumerate() {
+                let mut corner_idx = *idx;
+                corner_idx[u] += du;
+                corner_idx[v] += dv;
+                square[i] = corner(corner_idx);
+            }
+            let mut crossings = [None; 4];
+            for i in 0..4 {
+                let (a, av) = square[i];
+                let (b, bv) = square[(i + 1) % 4];
+                if let Ok(Some(plane)) = self.find_zero(a, av, b, bv) {
+                    crossings[i] = Some([plane.p[u], plane.p[v]]);
+                }
+            }
+            match crossings.iter().filter(|c| c.is_some()).count() {
+                2 => {
+                    let mut points = crossings.iter().filter_map(|c| *c);
+                    result.push([points.next().unwrap(), points.next().unwrap()]);
+                }
+                4 => {
+                    // Saddle: decide by the value at the center, which corners are connected.
+                    let center = na::center(&square[0].0, &square[2].0);
+                    let pairs = if Float::signum(self.function_value(&center))
+                        == Float::signum(square[0].1)
+                    {
+                        [(0, 1), (2, 3)]
                     } else {
-                        self.value_grid.insert(midx, value);
+                        [(3, 0), (1, 2)]
+                    };
+                    for &(i, j) in &pairs {
+                        result.push([crossings[i].unwrap(), crossings[j].unwrap()]);
                     }
-                    midx[0] += size;
                 }
-                midx[0] -= 2 * size;
-                midx[1] += size;
+                _ => {}
             }
-            midx[1] -= 2 * size;
-            midx[2] += size;
         }
-        None
+        result
     }
 
-    // Delete all values from value grid that do not have a value of opposing signum in any
-    // neighboring index.
-    // This might reduces memory usage by ~10x.
-    fn compact_value_grid(&mut self) {
-        // Collect all indexes to remove.
-        let value_grid = &mut self.value_grid;
-        let keys_to_remove: Vec<_> = value_grid
-            .par_iter()
-            .filter(|&(idx, &v)| {
-                if idx[0] == 0 || idx[1] == 0 || idx[2] == 0 {
-                    // This grid cell does not have neighbors in some directions. Ignore.
-                    return false;
+    /// Return line segments connecting the points, where the surface crosses the edges of the
+    /// sampling grid, on each face of each grid cell. This is a wire preview of the surface, that
+    /// shows the sampling without the simplification and the QEF solving. It is empty until
+    /// [`build_octree()`](Self::build_octree()) or [`tessellate()`](Self::tessellate()) was
+    /// called.
+    pub fn edge_crossings_as_lines(&self) -> Vec<([S; 3], [S; 3])> {
+        let edge_grid = self.edge_grid.borrow();
+        // Each face is given by its normal axis and its lowest corner. Collect the faces touching
+        // any crossing edge.
+        let mut faces = BTreeSet::new();
+        for edge_index in edge_grid.keys() {
+            let a = edge_index.edge as usize;
+            for n in (0..3).filter(|&n| n != a) {
+                let w = 3 - a - n;
+                faces.insert((n, edge_index.index));
+                if edge_index.index[w] > 0 {
+                    let mut idx = edge_index.index;
+                    idx[w] -= 1;
+                    faces.insert((n, idx));
                 }
-                for z in 0..3 {
-                    for y in 0..3 {
-                        for x in 0..3 {
-                            let adjacent_idx = [idx[0] + x - 1, idx[1] + y - 1, idx[2] + z - 1];
-                            if let Some(&adjacent_value) = value_grid.get(&adjacent_idx) {
-                                if Float::signum(v) != Float::signum(adjacent_value) {
-                                    // Don't collect indexes with
-                                    // opposing signum.
-                                    return false;
-                                }
-                            }
-                        }
-                    }
+            }
+        }
+        let mut result = Vec::new();
+        for (n, idx) in faces {
+            let (u, v) = ((n + 1) % 3, (n + 2) % 3);
+            let mut idx_u = idx;
+            idx_u[u] += 1;
+            let mut idx_v = idx;
+            idx_v[v] += 1;
+            // The edges of the face, ordered counterclockwise around it.
+            let edges = [(u, idx), (v, idx_u), (u, idx_v), (v, idx)];
+            let crossings: SmallVec<[Option<[S; 3]>; 4]> = edges
+                .iter()
+                .map(|&(axis, index)| {
+                    edge_grid
+                        .get(&EdgeIndex {
+                            edge: Edge::from_usize(axis),
+                            index,
+                        })
+                        .map(|plane| [plane.p.x, plane.p.y, plane.p.z])
+                })
+                .collect();
+            match crossings.iter().filter(|c| c.is_some()).count() {
+                2 => {
+                    let mut points = crossings.iter().filter_map(|c| *c);
+                    result.push((points.next().unwrap(), points.next().unwrap()));
                 }
-                true
-            })
-            .map(|(k, _)| *k)
-            .collect();
-        for k in keys_to_remove {
-            value_grid.remove(&k);
-        }
-        value_grid.shrink_to_fit();
-    }
-
-    // Store crossing positions of edges in edge_grid
-    fn generate_edge_grid(&mut self) {
-        let mut edge_grid = self.edge_grid.borrow_mut();
-        for (&point_idx, &point_value) in &self.value_grid {
-            for &edge in &[Edge::A, Edge::B, Edge::C] {
-                let mut adjacent_idx = point_idx;
-                adjacent_idx[edge as usize] += 1;
-                if let Some(&adjacent_value) = self.value_grid.get(&adjacent_idx) {
-                    let point_pos = self.origin
-                        + na::Vector3::new(
-                            From::from(point_idx[0] as f32),
-                            From::from(point_idx[1] as f32),
-                            From::from(point_idx[2] as f32),
-                        ) * self.res;
-                    let mut adjacent_pos = point_pos;
-                    adjacent_pos[edge as usize] += self.res;
-                    if let Some(plane) =
-                        self.find_zero(point_pos, point_value, adjacent_pos, adjacent_value)
+                4 => {
+                    // Saddle: decide by the value at the center, which corners are connected.
+                    let corner = |index: Index| self.index_to_world(index);
+                    let mut idx_uv = idx_u;
+                    idx_uv[v] += 1;
+                    let center = na::center(&corner(idx), &corner(idx_uv));
+                    let pairs = if Float::signum(self.function_value(&center))
+                        == Float::signum(self.value_grid[&idx])
                     {
-                        edge_grid.insert(
-                            EdgeIndex {
-                                edge,
-                                index: point_idx,
-                            },
-                            plane,
-                        );
+                        [(0, 1), (2, 3)]
+                    } else {
+                        [(3, 0), (1, 2)]
+                    };
+                    for &(i, j) in &pairs {
+                        result.push((crossings[i].unwrap(), crossings[j].unwrap()));
                     }
                 }
+                _ => {}
             }
         }
+        result
     }
 
-    // Solves QEFs in vertex stack, starting at the highest level, down all layers until the qef
-    // error is below threshold.
-    // Returns the number of solved QEFs.
-    fn solve_qefs(&self) -> usize {
-        let mut num_solved = 0;
-        if let Some(top_layer) = self.vertex_octtree.last() {
-            for i in 0..top_layer.len() {
-                num_solved += self.recursively_solve_qefs(&self.vertex_octtree.len() - 1, i);
-            }
-        }
-        num_solved
-    }
-
-    fn recursively_solve_qefs(&self, layer: usize, index_in_layer: usize) -> usize {
-        let vertex = &self.vertex_octtree[layer][index_in_layer];
-        assert!(vertex.children.is_empty() || layer > 0);
-        let error;
-        {
-            // Solve qef and store error.
-            let mut qef = vertex.qef.borrow_mut();
-            // Make sure we never solve a qef twice.
-            debug_assert!(
-                qef.error.is_nan(),
-                "found solved qef layer {:?} index {:?} {:?} parent: {:?}",
-                layer,
-                index_in_layer,
-                vertex.index,
-                vertex.parent
-            );
-            qef.solve();
-            error = qef.error;
-        }
-        let mut num_solved = 1;
-        // If error exceed threshold, recurse into subvertices.
-        if Float::abs(error) > self.error {
-            for &child_index in &vertex.children {
-                num_solved += self.recursively_solve_qefs(layer - 1, child_index);
-            }
-        }
-        num_solved
+    /// Evaluate f at each vertex of mesh in parallel and return the results in the order of
+    /// [`Mesh::vertices`], e.g. to bake a secondary field like a velocity or a temperature onto a
+    /// tessellated mesh. Runs in the pool set via [`with_thread_pool()`](Self::with_thread_pool()).
+    pub fn sample_attribute<T: Send, F: Fn([S; 3]) -> T + Sync>(
+        &self,
+        mesh: &Mesh<S>,
+        f: F,
+    ) -> Vec<T> {
+        install(&self.thread_pool, || {
+            mesh.vertices.par_iter().map(|&v| f(v)).collect()
+        })
     }
 
-    // Generates leaf vertices along with a map that points VertexIndices to the index in the leaf
-    // vertex vec.
-    fn generate_leaf_vertices(&self) -> (Vec<Vertex<S>>, HashMap<VertexIndex, usize>) {
-        let mut index_map = HashMap::new();
-        let mut vertices = Vec::new();
-        for edge_index in self.edge_grid.borrow().keys() {
-            self.add_vertices_for_minimal_egde(edge_index, &mut vertices, &mut index_map);
+    /// Build a mesh with one box for each octtree cell, whose vertex ends up in the tessellated
+    /// mesh. The faces of each box are labeled with its layer in the octtree, 0 being the finest
+    /// cells of size res. This is a diagnostic to see how much the mesh was simplified, e.g. to
+    /// tune relative_error. It is empty until [`tessellate()`](Self::tessellate()) was called.
+    /// Fails with [`DualContouringError::InternalState`], if a vertex is missing in the octtree.
+    pub fn debug_cell_mesh(&self) -> Result<Mesh<S>, DualContouringError> {
+        let mut mesh = Mesh {
+            vertices: Vec::new(),
+            faces: Vec::new(),
+            labels: Some(Vec::new()),
+            face_normals: None,
+            vertex_colors: None,
+        };
+        if self.vertex_octtree.is_empty() {
+            return Ok(mesh);
         }
-        for vertex in &mut vertices {
-            for neighbor_vec in &mut vertex.neighbors {
-                for neighbor in neighbor_vec.iter_mut() {
-                    match *neighbor {
-                        VarIndex::VertexIndex(vi) => *neighbor = VarIndex::Index(index_map[&vi]),
-                        VarIndex::Index(_) => panic!("unexpected Index in fresh leaf map."),
+        let generator = QuadGenerator {
+            value_grid: &self.value_grid,
+            vertex_octtree: &self.vertex_octtree,
+            vertex_index_map: &self.vertex_index_map,
+            error: self.error,
+            error_thresholds: &self.error_thresholds,
+            deferred_winding: self.deferred_winding,
+            inside_sign: self.inside_sign,
+        };
+        let cells = (0..self.vertex_octtree[0].len())
+            .map(|i| generator.collapse_leaf_vertex(i))
+            .collect::<Result<BTreeSet<(usize, usize)>, _>>()?;
+        for (layer, index) in cells {
+            let vertex = &self.vertex_octtree[layer][index];
+            let size = self.res * From::from((1 << layer) as f32);
+            let corner = |offset: [usize; 3]| {
+                let p = self.origin
+                    + na::Vector3::new(
+                        From::from((vertex.index[0] + offset[0]) as f32),
+                        From::from((vertex.index[1] + offset[1]) as f32),
+                        From::from((vertex.index[2] + offset[2]) as f32),
+                    ) * size;
+                [p.x, p.y, p.z]
+            };
+            let first = mesh.vertices.len();
+            for z in 0..2 {
+                for y in 0..2 {
+                    for x in 0..2 {
+                        mesh.vertices.push(corner([x, y, z]));
                     }
                 }
             }
-        }
-        for vi in 0..vertices.len() {
-            for np in 0..vertices[vi].neighbors.len() {
-                for ni in 0..vertices[vi].neighbors[np].len() {
-                    match vertices[vi].neighbors[np][ni] {
-                        VarIndex::VertexIndex(_) => panic!("unexpected VertexIndex."),
-                        VarIndex::Index(i) => {
-                            debug_assert!(
-                                vertices[i].neighbors[np ^ 1].contains(&VarIndex::Index(vi)),
-                                "vertex[{}].neighbors[{}][{}]=={:?},
-                                 but vertex[{}].neighbors[{}]=={:?}\n{:?} vs. {:?}",
-                                vi,
-                                np,
-                                ni,
-                                vertices[vi].neighbors[np][ni],
-                                i,
-                                np ^ 1,
-                                vertices[i].neighbors[np ^ 1],
-                                vertices[vi],
-                                vertices[i]
-                            );
-                        }
-                    }
-                }
+            for face in &BOX_FACES {
+                mesh.faces.push(face.iter().map(|&i| first + i).collect());
             }
+            mesh.labels
+                .as_mut()
+                .unwrap()
+                .extend(BOX_FACES.iter().map(|_| layer as u32));
         }
-        (vertices, index_map)
+        Ok(mesh)
     }
-    fn add_vertices_for_minimal_egde(
-        &self,
-        edge_index: &EdgeIndex,
-        vertices: &mut Vec<Vertex<S>>,
-        index_map: &mut HashMap<VertexIndex, usize>,
-    ) {
-        debug_assert!((edge_index.edge as usize) < 4);
-        let cell_size = na::Vector3::new(self.res, self.res, self.res);
-        for &quad_egde in &QUADS[edge_index.edge as usize] {
-            let idx = neg_offset(edge_index.index, EDGE_OFFSET[quad_egde as usize]);
 
-            let edge_set = get_connected_edges(quad_egde, self.bitset_for_cell(idx));
-            let vertex_index = VertexIndex {
-                edges: edge_set,
-                index: idx,
-            };
-            index_map.entry(vertex_index).or_insert_with(|| {
-                let mut neighbors = [
-                    Vec::new(),
-                    Vec::new(),
-                    Vec::new(),
-                    Vec::new(),
-                    Vec::new(),
-                    Vec::new(),
-                ];
-                for (i, neighbor) in neighbors.iter_mut().enumerate().take(6) {
-                    if let Some(mut neighbor_index) = vertex_index.neighbor(i) {
-                        for edges in get_connected_edges_from_edge_set(
-                            neighbor_index.edges,
-                            self.bitset_for_cell(neighbor_index.index),
-                        ) {
-                            neighbor_index.edges = edges;
-                            let idx = VarIndex::VertexIndex(neighbor_index);
-                            if !neighbor.contains(&idx) {
-                                neighbor.push(idx);
+    /// Check the leaf vertices of the octtree against the assumptions of the algorithm and return
+    /// the indices of the cells violating them, sorted. A cell violates them, if the surface in it
+    /// is not a disk, i.e. it does not cross each face of the cell at exactly 0 or 2 edges, or if
+    /// the neighborhood of its vertex is not symmetric. This typically happens for functions with
+    /// discontinuities. In debug builds these are asserted while tessellating, this check also
+    /// works in release builds. It is `Ok`, until [`tessellate()`](Self::tessellate()) or
+    /// [`build_octree()`](Self::build_octree()) was called.
+    pub fn verify_manifold_invariants(&self) -> Result<(), Vec<Index>> {
+        let leafs = match self.vertex_octtree.first() {
+            Some(leafs) => leafs,
+            None => return Ok(()),
+        };
+        let mut violations: Vec<Index> = leafs
+            .iter()
+            .enumerate()
+            .filter(|&(vi, vertex)| {
+                !(vertex.is_2manifold() || vertex.is_unmergeable())
+                    || vertex.neighbors.iter().enumerate().any(|(np, neighbors)| {
+                        neighbors.iter().any(|neighbor| match *neighbor {
+                            VarIndex::Index(i) => {
+                                !leafs[i].neighbors[np ^ 1].contains(&VarIndex::Index(vi))
                             }
-                        }
-                    }
-                }
-                let mut intersections = [0u32; 12];
-                for edge in edge_set {
-                    intersections[edge] = 1;
-                }
-                let tangent_planes: Vec<_> = edge_set
-                    .map(|edge| {
-                        self.get_edge_tangent_plane(&EdgeIndex {
-                            edge: Edge::from_usize(edge),
-                            index: idx,
+                            VarIndex::VertexIndex(_) => true,
                         })
                     })
-                    .collect();
-                let cell_origin = self.origin
-                    + na::Vector3::new(
-                        From::from(idx[0] as f32),
-                        From::from(idx[1] as f32),
-                        From::from(idx[2] as f32),
-                    ) * self.res;
-                vertices.push(Vertex {
-                    index: idx,
-                    qef: RefCell::new(qef::Qef::new(
-                        &tangent_planes,
-                        BoundingBox::new(&cell_origin, &(cell_origin + cell_size)),
-                    )),
-                    neighbors,
-                    parent: Cell::new(None),
-                    children: Vec::new(),
-                    mesh_index: Cell::new(None),
-                    edge_intersections: intersections,
-                    euler_characteristic: 1,
-                });
-                vertices.len() - 1
-            });
+            })
+            .map(|(_, vertex)| vertex.index)
+            .collect();
+        if violations.is_empty() {
+            return Ok(());
         }
+        violations.sort_unstable();
+        violations.dedup();
+        Err(violations)
     }
 
-    fn get_edge_tangent_plane(&self, edge_index: &EdgeIndex) -> Plane<S> {
-        if let Some(ref plane) = self.edge_grid.borrow().get(&edge_index.base()) {
-            return **plane;
+    /// Counters collected by the last tessellation. See also
+    /// [`estimated_memory_bytes()`](Self::estimated_memory_bytes()).
+    pub fn stats(&self) -> TessellationStats {
+        TessellationStats {
+            num_clipped_cubes: self.num_clipped_cubes,
+            num_qef_fallbacks: self.num_qef_fallbacks,
+            num_boundary_edges: self.num_boundary_edges,
+            num_degenerate_quads: self.num_degenerate_quads,
         }
-        panic!(
-            "could not find edge_point: {:?} -> {:?}",
-            edge_index,
-            edge_index.base()
+    }
+
+    /// Estimate the number of bytes allocated by the intermediate state in its current phase: the
+    /// sampled values, the crossing edges and the vertex octtree including the neighbors and
+    /// children of its vertices. Hash maps are counted by their capacity. The mesh is not
+    /// included. Call it after [`build_octree()`](Self::build_octree()), which allocates most of
+    /// it, to decide on the resolution of later tessellations.
+    pub fn estimated_memory_bytes(&self) -> usize {
+        // Hash maps store one control byte per bucket in addition to the entry.
+        fn hash_map_bytes<K, V>(map: &HashMap<K, V>) -> usize {
+            map.capacity() * (mem::size_of::<(K, V)>() + 1)
+        }
+        let octtree: usize = self
+            .vertex_octtree
+            .iter()
+            .map(|layer| {
+                layer.capacity() * mem::size_of::<Vertex<S>>()
+                    + layer
+                        .iter()
+                        .map(|vertex| {
+                            vertex
+                                .neighbors
+                                .iter()
+                                .map(|neighbors| neighbors.capacity() * mem::size_of::<VarIndex>())
+                                .sum::<usize>()
+                                + vertex.children.capacity() * mem::size_of::<usize>()
+                        })
+                        .sum::<usize>()
+            })
+            .sum();
+        hash_map_bytes(&self.value_grid)
+            + hash_map_bytes(&*self.edge_grid.borrow())
+            + hash_map_bytes(&self.vertex_index_map)
+            + octtree
+    }
+
+    /// The position of grid point [0, 0, 0], i.e. the lower corner of the sampled (dilated)
+    /// bounding box. It moves slightly, when sampling is retried after hitting a value of exactly
+    /// zero.
+    pub fn origin(&self) -> na::Point3<S> {
+        self.origin
+    }
+
+    /// The distance between neighboring grid points, i.e. res as passed to
+    /// [`new()`](Self::new()).
+    pub fn resolution(&self) -> S {
+        self.res
+    }
+
+    /// The position of the grid point idx, e.g. from [`occupied_cells()`](Self::occupied_cells())
+    /// or [`verify_manifold_invariants()`](Self::verify_manifold_invariants()).
+    pub fn index_to_world(&self, idx: Index) -> na::Point3<S> {
+        self.origin
+            + na::Vector3::new(
+                From::from(idx[0] as f32),
+                From::from(idx[1] as f32),
+                From::from(idx[2] as f32),
+            ) * self.res
+    }
+
+    // The positions of the grid points at both ends of the edge.
+    fn edge_index_to_world(&self, edge_index: EdgeIndex) -> (na::Point3<S>, na::Point3<S>) {
+        let edge_index = edge_index.base();
+        let a = self.index_to_world(edge_index.index);
+        let mut b = a;
+        b[edge_index.edge as usize] += self.res;
+        (a, b)
+    }
+
+    /// Iterate over the sampled grid points along with whether each of them is inside the object,
+    /// i.e. whether the function has the sign set via
+    /// [`with_inside_sign()`](Self::with_inside_sign()) there.
+    ///
+    /// Grid points are in units of the resolution, relative to the lower corner of the sampled
+    /// (dilated) bounding box, see [`index_to_world()`](Self::index_to_world()). Sampling is
+    /// adaptive, so far from the surface only a sparse subset of grid points is present. This is
+    /// empty until [`tessellate()`](Self::tessellate()) was called.
+    pub fn occupied_cells(&self) -> impl Iterator<Item = (Index, bool)> + '_ {
+        self.value_grid
+            .iter()
+            .map(move |(&index, &value)| (index, is_inside(value, self.inside_sign)))
+    }
+
+    /// Tessellate the given function.
+    pub fn tessellate(&mut self) -> Result<Mesh<S>, DualContouringError> {
+        log!(
+            self,
+            "ManifoldDualContouring: res: {:} {:?}",
+            self.res,
+            self.bbox
         );
+        self.tessellate_mesh()?;
+        Ok(self.mesh.clone())
     }
 
-    // Return the Point index (in self.mesh.vertices) the the point belonging to edge/idx.
-    fn lookup_cell_point(&self, edge: Edge, idx: Index) -> usize {
-        // Generate the proper vertex Index from a single edge and an Index.
-        let edge_set = get_connected_edges(edge, self.bitset_for_cell(idx));
-        let vertex_index = VertexIndex {
-            edges: edge_set,
-            index: idx,
-        };
+    /// Tessellate the given function into mesh, like [`tessellate()`](Self::tessellate()), but
+    /// instead of returning a new mesh, mesh is cleared and refilled. Its vertices and faces keep
+    /// their allocations, so re-tessellating every frame, e.g. together with
+    /// [`reset_for()`](Self::reset_for()), does not allocate them again after the first frame.
+    /// On error, mesh is left empty.
+    pub fn tessellate_into(&mut self, mesh: &mut Mesh<S>) -> Result<(), DualContouringError> {
+        mem::swap(&mut self.mesh, mesh);
+        let result = self.tessellate_mesh();
+        if result.is_err() {
+            self.clear_mesh();
+        }
+        mem::swap(&mut self.mesh, mesh);
+        result
+    }
 
-        // Convert the vertex index to index and layer in the Octtree.
-        let mut octtree_index = self.vertex_index_map[&vertex_index];
-        let mut octtree_layer = 0;
-        // Walk up the chain of parents
+    // Tessellate the given function into self.mesh.
+    fn tessellate_mesh(&mut self) -> Result<(), DualContouringError> {
+        self.start_timeout();
+        let flip_winding = self.flip_winding()?;
+        self.clear_mesh();
         loop {
-            let next_index = self.vertex_octtree[octtree_layer][octtree_index]
-                .parent
-                .get()
-                .unwrap();
-            let next_vertex = &self.vertex_octtree[octtree_layer + 1][next_index];
-            let error = next_vertex.qef.borrow().error;
-            if (!error.is_nan() && error > (self.error))
-                || (octtree_layer == self.vertex_octtree.len() - 2)
-                || !next_vertex.is_2manifold()
-            {
-                // Stop, if either the error is too large or we will reach the top.
-                break;
+            match self.try_tessellate() {
+                Ok(()) => {
+                    if flip_winding {
+                        self.reverse_winding();
+                    }
+                    return Ok(());
+                }
+                // Tessellation failed, b/c the value in one of the grid cells was exactly zero.
+                // Retry with some random padding and hope for the best.
+                Err(e @ DualContouringError::HitZero(_)) => self.move_origin(&e),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Sample the function and build the octtree, but stop before solving the QEFs and generating
+    /// the mesh. These are the expensive phases. The result can be stored via `save_state()` (with
+    /// the `serde` feature) and turned into a mesh via
+    /// [`tessellate_from_state()`](Self::tessellate_from_state()).
+    pub fn build_octree(&mut self) -> Result<(), DualContouringError> {
+        self.start_timeout();
+        loop {
+            let mut t = Timer::new(!self.quiet);
+            match self.try_build_octree(&mut t) {
+                Ok(()) => return Ok(()),
+                Err(e @ DualContouringError::HitZero(_)) => self.move_origin(&e),
+                Err(e) => return Err(e),
             }
-            octtree_layer += 1;
-            octtree_index = next_index;
         }
-        let vertex = &self.vertex_octtree[octtree_layer][octtree_index];
-        // If the vertex exists in mesh, return its index.
-        if let Some(mesh_index) = vertex.mesh_index.get() {
-            return mesh_index;
+    }
+
+    /// Generate the mesh from the octtree built by [`build_octree()`](Self::build_octree()),
+    /// loaded via `load_state()` or left over from a previous [`tessellate()`](Self::tessellate()).
+    /// This only runs the cheap final phases, solving the QEFs and generating the quads, with the
+    /// current relative_error. The mesh is empty, if there is no octtree.
+    pub fn tessellate_from_state(&mut self) -> Result<Mesh<S>, DualContouringError> {
+        self.start_timeout();
+        let flip_winding = self.flip_winding()?;
+        for layer in &mut self.vertex_octtree {
+            for vertex in layer {
+                vertex.qef.reset();
+            }
         }
-        // If not, store it in mesh and return its index.
-        if vertex.qef.borrow().error.is_nan() {
-            // Maybe the qef was not solved, since the error in the layer above was below the
-            // threshold. But it seems, manifold criterion has catched and we need to solve it now.
-            vertex.qef.borrow_mut().solve()
+        self.clear_mesh();
+        self.extract_mesh(&mut Timer::new(!self.quiet))?;
+        if flip_winding {
+            self.reverse_winding();
         }
-        let qef_solution = vertex.qef.borrow().solution;
-        let vertex_list = &mut self.mesh.borrow_mut().vertices;
-        let result = vertex_list.len();
-        vertex.mesh_index.set(Some(result));
-        vertex_list.push([qef_solution.x, qef_solution.y, qef_solution.z]);
-        result
+        Ok(self.mesh.clone())
     }
 
-    fn bitset_for_cell(&self, idx: Index) -> BitSet {
-        let mut idx = idx;
-        let mut result = BitSet::zero();
-        for z in 0..2 {
-            for y in 0..2 {
-                for x in 0..2 {
-                    if let Some(&v) = self.value_grid.get(&idx) {
-                        if v < From::from(0f32) {
-                            result.set(z << 2 | y << 1 | x);
-                        }
-                    } else {
-                        panic!("did not find value_grid[{:?}]", idx);
-                    }
-                    idx[0] += 1;
-                }
-                idx[0] -= 2;
-                idx[1] += 1;
+    /// Tessellate the given function without building the mesh. Instead sink is called for each
+    /// face with the positions of its vertices, as soon as it is generated, along with the index
+    /// of the face, so faces can be written to disk incrementally, e.g. for meshes too large to
+    /// hold in memory. Vertices shared by several faces are passed to each of them. Returns the
+    /// number of faces.
+    ///
+    /// Faces are wound consistently per quad, even with
+    /// [`with_deferred_winding()`](Self::with_deferred_winding()), which needs the whole mesh.
+    /// Face labels, face normals and vertex colors are not generated.
+    pub fn tessellate_streaming<F: FnMut(&[[S; 3]], usize)>(
+        &mut self,
+        mut sink: F,
+    ) -> Result<usize, DualContouringError> {
+        self.build_octree()?;
+        // flip_winding() leaves inverted signs to deferred winding, which is not applied here.
+        let flip_winding = match self.sign_check {
+            SignCheck::Flip => self.has_inverted_sign(),
+            _ => self.flip_winding()?,
+        };
+        let num_qefs_solved = self.solve_qefs()?;
+        self.check_timeout()?;
+        log!(self, "solved {} qefs.", num_qefs_solved);
+        // The positions of the vertices passed to sink so far, by (layer, index) in the octtree.
+        let mut positions = HashMap::new();
+        let mut num_faces = 0;
+        let system = self.coordinate_system;
+        let flip_winding = flip_winding != system.is_left_handed();
+        self.for_each_quad(false, |mdc, _, quad| {
+            let mut face: SmallVec<[[S; 3]; 4]> = quad
+                .iter()
+                .map(|&(layer, index)| {
+                    *positions
+                        .entry((layer, index))
+                        .or_insert_with(|| system.convert(mdc.mesh_vertex(layer, index)))
+                })
+                .collect();
+            if flip_winding {
+                face.reverse();
             }
-            idx[1] -= 2;
-            idx[2] += 1;
+            sink(&face, num_faces);
+            num_faces += 1;
+        })?;
+        log!(self, "streamed {} faces.", num_faces);
+        Ok(num_faces)
+    }
+
+    /// Tessellate the function at several levels of detail, one for each relative error in errors,
+    /// e.g. from coarsest to finest. The function is sampled and the octtree is built only once,
+    /// so all levels share the same sampled field. relative_error is restored afterwards.
+    pub fn tessellate_lods(&mut self, errors: &[S]) -> Result<Vec<Mesh<S>>, DualContouringError> {
+        self.build_octree()?;
+        let error = self.error;
+        let lods = errors
+            .iter()
+            .map(|&relative_error| {
+                self.error = self.res * relative_error;
+                self.tessellate_from_state()
+            })
+            .collect();
+        self.error = error;
+        lods
+    }
+
+    fn start_timeout(&mut self) {
+        self.deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+    }
+
+    fn check_timeout(&self) -> Result<(), DualContouringError> {
+        match self.deadline {
+            Some(deadline) if Instant::now() > deadline => Err(DualContouringError::Timeout),
+            _ => Ok(()),
         }
-        result
     }
 
-    // Compute a quad for the given edge and append it to the list.
-    fn compute_quad(&self, edge_index: EdgeIndex) {
-        debug_assert!((edge_index.edge as usize) < 4);
-        debug_assert!(edge_index.index.iter().all(|&i| i > 0));
+    // Whether the face winding of the mesh needs to be reversed, according to sign_check.
+    fn flip_winding(&self) -> Result<bool, DualContouringError> {
+        match self.sign_check {
+            SignCheck::Disabled => Ok(false),
+            SignCheck::Flip => Ok(!self.deferred_winding && self.has_inverted_sign()),
+            SignCheck::Error => {
+                if self.has_inverted_sign() {
+                    return Err(DualContouringError::InvertedSign);
+                }
+                Ok(false)
+            }
+        }
+    }
 
-        let mut p = SmallVec::with_capacity(4);
-        for &quad_egde in &QUADS[edge_index.edge as usize] {
-            let point_index = self.lookup_cell_point(
-                quad_egde,
-                neg_offset(edge_index.index, EDGE_OFFSET[quad_egde as usize]),
-            );
-            // Dedup points before insertion (two minimal vertices might end up in the same parent
-            // vertex).
-            if !p.contains(&point_index) {
-                p.push(point_index)
+    fn reverse_winding(&mut self) {
+        log!(self, "Function has inverted sign. Reversing face winding.");
+        self.reverse_faces(|_| true);
+        if let Some(ref mut face_normals) = self.mesh.face_normals {
+            for n in face_normals {
+                *n = [-n[0], -n[1], -n[2]];
             }
         }
-        // Avoid generating degenerate faces.
-        if p.len() < 3 {
+    }
+
+    // Convert the mesh from the coordinates of the function to coordinate_system.
+    fn convert_coordinates(&mut self) {
+        let system = self.coordinate_system;
+        if system == CoordinateSystem::RightHandedZUp {
             return;
         }
-        // Reverse order, if the edge is reversed.
-        if let Some(&v) = self.value_grid.get(&edge_index.index) {
-            if v < From::from(0f32) {
-                p.reverse();
+        for v in &mut self.mesh.vertices {
+            *v = system.convert(*v);
+        }
+        if let Some(ref mut face_normals) = self.mesh.face_normals {
+            for n in face_normals {
+                *n = system.convert(*n);
             }
         }
-
-        // Add the face to the mesh.
-        self.mesh.borrow_mut().faces.push(p);
+        if system.is_left_handed() {
+            self.reverse_faces(|_| true);
+        }
     }
 
-    // If a is inside the object and b outside - this method returns the point on the line between
-    // a and b where the object edge is. It also returns the normal on that point.
-    // av and bv represent the object values at a and b.
-    fn find_zero(&self, a: na::Point3<S>, av: S, b: na::Point3<S>, bv: S) -> Option<Plane<S>> {
-        assert!(a != b);
-        if Float::signum(av) == Float::signum(bv) {
-            return None;
+    // Reverse the faces of the mesh, for which reverse is true, and their triangles, if they are
+    // collected. Face normals are left alone.
+    fn reverse_faces(&mut self, reverse: impl Fn(usize) -> bool) {
+        let mut first_triangle = 0;
+        for (i, face) in self.mesh.faces.iter_mut().enumerate() {
+            let num_triangles = face.len() - 2;
+            if reverse(i) {
+                face.reverse();
+                if let Some(ref mut triangles) = self.triangles {
+                    for (slot, triangle) in triangles[first_triangle..]
+                        .iter_mut()
+                        .zip(triangulate(face))
+                    {
+                        *slot = triangle;
+                    }
+                }
+            }
+            first_triangle += num_triangles;
         }
-        let d = a - b;
-        let mut distance = Float::max(
-            Float::max(Float::abs(d.x), Float::abs(d.y)),
-            Float::abs(d.z),
+    }
+
+    // Move the origin by some random padding and reset all state, to retry after sampling hit a
+    // zero value.
+    fn move_origin(&mut self, e: &DualContouringError) {
+        let padding = na::Vector3::new(
+            -self.res / From::from(10. + rand::random::<f32>().abs()),
+            -self.res / From::from(10. + rand::random::<f32>().abs()),
+            -self.res / From::from(10. + rand::random::<f32>().abs()),
         );
-        distance = Float::min(Float::min(distance, Float::abs(av)), Float::abs(bv));
-        let precision: S = From::from(PRECISION);
-        if distance < precision * self.res {
-            let result = if Float::abs(bv) < Float::abs(av) {
-                &b
-            } else {
-                &a
-            };
-            return Some(Plane {
-                p: *result,
-                // We need a precise normal here.
-                n: self.function.normal(result),
-            });
+        log!(
+            self,
+            "Error: {:?}. moving by {:?} and retrying.",
+            e,
+            padding
+        );
+        self.origin += padding;
+        if let Some(ref memo) = self.value_memo {
+            memo.borrow_mut().retry();
         }
-        // Linear interpolation of the zero crossing.
-        let n = a + (b - a) * (Float::abs(av) / Float::abs(bv - av));
-        let nv = self.function.value(&n);
+        if let Some(ref memo) = self.normal_memo {
+            memo.borrow_mut().retry();
+        }
+        self.value_grid.clear();
+        self.clear_mesh();
+        self.vertex_octtree.clear();
+        self.vertex_index_map.clear();
+    }
 
-        if Float::signum(av) != Float::signum(nv) {
-            self.find_zero(a, av, n, nv)
-        } else {
-            self.find_zero(n, nv, b, bv)
+    // Clear the mesh, keeping the allocations of its vertices and faces.
+    fn clear_mesh(&mut self) {
+        self.mesh.vertices.clear();
+        self.mesh.faces.clear();
+        if let Some(ref mut triangles) = self.triangles {
+            triangles.clear();
         }
+        self.mesh.labels = None;
+        self.mesh.face_normals = None;
+        self.mesh.vertex_colors = None;
+    }
+
+    /// Tessellate the given function and return the mesh along with its triangulation, see
+    /// [`Mesh::to_triangle_mesh()`]. The triangles are generated along with the faces, instead of
+    /// in a second pass over them.
+    pub fn tessellate_both(&mut self) -> Result<(Mesh<S>, TriangleMesh<S>), DualContouringError> {
+        self.triangles = Some(Vec::new());
+        let mesh = self.tessellate();
+        let triangles = self.triangles.take().unwrap_or_default();
+        let mesh = mesh?;
+        let triangle_mesh = TriangleMesh {
+            vertices: mesh.vertices.clone(),
+            faces: triangles,
+        };
+        Ok((mesh, triangle_mesh))
+    }
+
+    /// Recommend how to wind the faces of the mesh returned from
+    /// [`tessellate()`](Self::tessellate()) on export, e.g. via [`Mesh::to_obj()`], so they face
+    /// outwards, i.e. away from the center of the bounding box.
+    ///
+    /// Faces point from inside to outside, see [`with_inside_sign()`](Self::with_inside_sign()),
+    /// unless [`with_sign_check()`](Self::with_sign_check()) flips them or
+    /// [`with_deferred_winding()`](Self::with_deferred_winding()) orients them outwards. Whether
+    /// the inside is inverted is checked like for the sign check, i.e. by evaluating the function
+    /// at the center of the bounding box and at a corner of the dilated one. Returns
+    /// [`Winding::Keep`] or [`Winding::Reverse`].
+    pub fn recommended_winding(&self) -> Winding {
+        if !self.deferred_winding
+            && self.has_inverted_sign()
+            && !matches!(self.flip_winding(), Ok(true))
+        {
+            Winding::Reverse
+        } else {
+            Winding::Keep
+        }
+    }
+
+    // An object should be inside at the center of its bbox and outside at the corners of the
+    // dilated bbox. If it is the other way round, the sign convention is likely inverted.
+    fn has_inverted_sign(&self) -> bool {
+        let zero: S = From::from(0f32);
+        let two: S = From::from(2f32);
+        let center = na::Point3::new(
+            (self.bbox.min.x + self.bbox.max.x) / two,
+            (self.bbox.min.y + self.bbox.max.y) / two,
+            (self.bbox.min.z + self.bbox.max.z) / two,
+        );
+        let (center, corner) = (
+            self.function_value(&center),
+            self.function_value(&self.origin),
+        );
+        match self.inside_sign {
+            Sign::Negative => center > zero && corner < zero,
+            Sign::Positive => center < zero && corner > zero,
+        }
+    }
+
+    // The value of the function at p relative to isovalue.
+    fn function_value(&self, p: &na::Point3<S>) -> S {
+        self.function.value(p) - self.isovalue
+    }
+
+    // Evaluate the function at p. Fails on values, that are not finite, if nan_guard is set.
+    fn value(&self, p: &na::Point3<S>) -> Result<S, DualContouringError> {
+        let value = self.function_value(p);
+        if self.nan_guard && !Float::is_finite(value) {
+            return Err(non_finite_value(p));
+        }
+        Ok(value)
+    }
+
+    // Evaluate the function at points in one batch. Fails like value(). With memoization only the
+    // points without remembered value are evaluated.
+    fn values(&self, points: &[na::Point3<S>], out: &mut [S]) -> Result<(), DualContouringError> {
+        match self.value_memo {
+            Some(ref memo) => {
+                let mut memo = memo.borrow_mut();
+                let mut missing = Vec::new();
+                for (i, (p, value)) in points.iter().zip(out.iter_mut()).enumerate() {
+                    match memo.get(p) {
+                        Some(v) => *value = v,
+                        None => missing.push(i),
+                    }
+                }
+                let missing_points: Vec<_> = missing.iter().map(|&i| points[i]).collect();
+                let mut missing_values = vec![S::zero(); missing.len()];
+                self.function.values(&missing_points, &mut missing_values);
+                for ((&i, p), &value) in missing.iter().zip(missing_points).zip(&missing_values) {
+                    out[i] = value;
+                    if value != self.isovalue && Float::is_finite(value) {
+                        memo.insert(p, value);
+                    }
+                }
+            }
+            None => self.function.values(points, out),
+        }
+        if self.isovalue != S::zero() {
+            for value in out.iter_mut() {
+                *value -= self.isovalue;
+            }
+        }
+        if self.nan_guard {
+            if let Some(i) = out.iter().position(|&value| !Float::is_finite(value)) {
+                return Err(non_finite_value(&points[i]));
+            }
+        }
+        Ok(())
+    }
+
+    fn tessellation_step1(&mut self) -> Option<DualContouringError> {
+        if let Some(e) = self.check_bbox() {
+            return Some(e);
+        }
+        if self.dim.iter().any(|&d| d < 2) {
+            return Some(DualContouringError::ResolutionTooCoarse { dim: self.dim });
+        }
+        let maxdim = cmp::max(self.dim[0], cmp::max(self.dim[1], self.dim[2]));
+        let origin = self.origin;
+        let origin_value = match self.value(&origin) {
+            Ok(value) => value,
+            Err(e) => return Some(e),
+        };
+        self.num_clipped_cubes = 0;
+
+        // The octtree spans size^3 cells, which must be addressable.
+        let size = match pow2roundup(maxdim).filter(|&size| {
+            size.checked_mul(size)
+                .and_then(|s| s.checked_mul(size))
+                .is_some()
+        }) {
+            Some(size) => size,
+            None => return Some(DualContouringError::ResolutionTooFine { dim: self.dim }),
+        };
+        if let Some(e) = self.sample_value_grid([0, 0, 0], origin, size, origin_value) {
+            return Some(e);
+        }
+        if self.thin_feature_detection {
+            return self.complete_value_grid(size);
+        }
+        None
+    }
+
+    // Sample the missing corners of all cells next to a sign change in value_grid, until there
+    // are none left. Refining a cube because of a sign change at its center leaves its neighbors
+    // coarse, but the surface found inside the cube may extend into them.
+    fn complete_value_grid(&mut self, size: usize) -> Option<DualContouringError> {
+        let mut queue: Vec<Index> = self.value_grid.keys().cloned().collect();
+        while !queue.is_empty() {
+            let mut missing = BTreeSet::new();
+            for &idx in &queue {
+                for axis in 0..3 {
+                    // The edges from and to idx along axis.
+                    for &lower in &[idx[axis].checked_sub(1), Some(idx[axis])] {
+                        let mut lower_idx = idx;
+                        lower_idx[axis] = match lower {
+                            Some(lower) => lower,
+                            None => continue,
+                        };
+                        let mut upper_idx = lower_idx;
+                        upper_idx[axis] += 1;
+                        match (
+                            self.value_grid.get(&lower_idx),
+                            self.value_grid.get(&upper_idx),
+                        ) {
+                            (Some(&a), Some(&b)) if Float::signum(a) != Float::signum(b) => (),
+                            _ => continue,
+                        }
+                        // All corners of the four cells around the edge.
+                        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+                        for i in 0..4 {
+                            let mut cell = lower_idx;
+                            if i & 1 == 1 {
+                                cell[u] = match cell[u].checked_sub(1) {
+                                    Some(c) => c,
+                                    None => continue,
+                                };
+                            }
+                            if i & 2 == 2 {
+                                cell[v] = match cell[v].checked_sub(1) {
+                                    Some(c) => c,
+                                    None => continue,
+                                };
+                            }
+                            for corner in 0..8 {
+                                let corner_idx = [
+                                    cell[0] + (corner & 1),
+                                    cell[1] + ((corner >> 1) & 1),
+                                    cell[2] + ((corner >> 2) & 1),
+                                ];
+                                if corner_idx.iter().all(|&c| c <= size)
+                                    && !self.value_grid.contains_key(&corner_idx)
+                                {
+                                    missing.insert(corner_idx);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            queue = missing.into_iter().collect();
+            let points: Vec<_> = queue.iter().map(|&idx| self.index_to_world(idx)).collect();
+            let mut values = vec![S::zero(); points.len()];
+            if let Err(e) = self
+                .values(&points, &mut values)
+                .and_then(|_| self.check_timeout())
+            {
+                return Some(e);
+            }
+            for ((&idx, &p), &value) in queue.iter().zip(points.iter()).zip(values.iter()) {
+                if value == From::from(0f32) {
+                    return Some(DualContouringError::HitZero(format!("{}", p)));
+                }
+                self.value_grid.insert(idx, value);
+            }
+        }
+        None
+    }
+
+    // This method does the main work of tessellation.
+    // It may fail, if the value in one of the grid cells yields exactly zero.
+    fn try_tessellate(&mut self) -> Result<(), DualContouringError> {
+        let mut t = Timer::new(!self.quiet);
+        self.try_build_octree(&mut t)?;
+        self.extract_mesh(&mut t)
+    }
+
+    // Sample value_grid, unless presampled, and build the octtree from it.
+    fn try_build_octree(&mut self, t: &mut Timer) -> Result<(), DualContouringError> {
+        if self.presampled {
+            return self.build_octree_from_value_grid(t);
+        }
+        if let Some(e) = self.tessellation_step1() {
+            return Err(e);
+        }
+        self.check_timeout()?;
+        let total_cells = self.dim[0] * self.dim[1] * self.dim[2];
+        log!(
+            self,
+            "generated value_grid with {:} % of {:} cells in {:?}.",
+            (100 * self.value_grid.len()) as f64 / total_cells as f64,
+            total_cells,
+            t.elapsed()
+        );
+        if self.num_clipped_cubes > 0 {
+            log!(
+                self,
+                "Warning: min_cell_size stopped refinement of {} cubes. Features smaller than {:} \
+                 might be missing.",
+                self.num_clipped_cubes,
+                self.min_cell_size.unwrap()
+            );
+        }
+
+        if self.compact {
+            self.compact_value_grid();
+            self.check_timeout()?;
+            log!(
+                self,
+                "compacted value_grid, now {:} % of {:} cells in {:?}.",
+                (100 * self.value_grid.len()) as f64 / total_cells as f64,
+                total_cells,
+                t.elapsed()
+            );
+        }
+        self.build_octree_from_value_grid(t)
+    }
+
+    // Generate the edge_grid and the vertex octtree from the sampled value_grid.
+    fn build_octree_from_value_grid(&mut self, t: &mut Timer) -> Result<(), DualContouringError> {
+        self.generate_edge_grid()?;
+        self.check_timeout()?;
+
+        log!(
+            self,
+            "generated edge_grid with {} edges: {:?}",
+            self.edge_grid.borrow().len(),
+            t.elapsed()
+        );
+
+        let (leafs, index_map) = self.generate_leaf_vertices()?;
+        self.vertex_index_map = index_map;
+        self.vertex_octtree.push(leafs);
+        self.check_timeout()?;
+
+        log!(
+            self,
+            "generated {:?} leaf vertices: {:?}",
+            self.vertex_octtree[0].len(),
+            t.elapsed()
+        );
+
+        while self.method == Method::ManifoldDualContouring {
+            let next = subsample_octtree(
+                self.vertex_octtree.last_mut().unwrap(),
+                self.merge_manifold_only,
+                self.invariant_checks,
+            )?;
+            if next.len() == self.vertex_octtree.last().unwrap().len() {
+                break;
+            }
+            self.vertex_octtree.push(next);
+        }
+        log!(self, "subsampled octtree {:?}", t.elapsed());
+        Ok(())
+    }
+
+    // Solve the qefs of the octtree and generate the mesh.
+    fn extract_mesh(&mut self, t: &mut Timer) -> Result<(), DualContouringError> {
+        let num_qefs_solved = self.solve_qefs()?;
+        self.check_timeout()?;
+
+        log!(self, "solved {} qefs: {:?}", num_qefs_solved, t.elapsed());
+
+        self.generate_quads()?;
+        log!(self, "generated quads: {:?}", t.elapsed());
+
+        if self.deferred_winding {
+            // Like Mesh::make_winding_consistent(), but also reversing the triangles and keeping
+            // the face normals, that come from the gradient and already face outwards.
+            let flips = self.mesh.winding_flips();
+            self.reverse_faces(|i| flips[i]);
+            let num_flipped = flips.iter().filter(|&&flip| flip).count();
+            log!(self, "flipped {} faces: {:?}", num_flipped, t.elapsed());
+        }
+        self.convert_coordinates();
+
+        log!(
+            self,
+            "computed mesh with {:?} faces.",
+            self.mesh.faces.len()
+        );
+        Ok(())
+    }
+
+    // Sample the cube at idx and pos with edge length size, whose corner at pos has the value
+    // val. Cubes are refined level by level, so that the corners of all cubes of a level are
+    // evaluated in one batch. Unless the function is an exact distance, all cubes are refined.
+    fn sample_value_grid(
+        &mut self,
+        idx: Index,
+        pos: na::Point3<S>,
+        size: usize,
+        val: S,
+    ) -> Option<DualContouringError> {
+        debug_assert!(size > 1);
+        let exact = self.function.is_exact_distance();
+        let mut cubes = vec![(idx, pos, val)];
+        let mut size = size;
+        while !cubes.is_empty() {
+            size /= 2;
+            let size_s: S = From::from(size as f32);
+            let offset = na::Vector3::new(self.res, self.res, self.res) * size_s;
+            let cell_size = size_s * self.res;
+            let sub_cube_diagonal = cell_size * Float::sqrt(From::from(3f32)) * self.descent_margin;
+            let clip =
+                matches!(self.min_cell_size, Some(min_cell_size) if cell_size <= min_cell_size);
+            let corner = |pos: na::Point3<S>, i: usize| {
+                na::Point3::new(
+                    if i & 1 == 0 { pos.x } else { pos.x + offset.x },
+                    if i & 2 == 0 { pos.y } else { pos.y + offset.y },
+                    if i & 4 == 0 { pos.z } else { pos.z + offset.z },
+                )
+            };
+            // For exact distances, first evaluate the centers of the cubes, which are the last
+            // corners of their sub-cubes. If the center is farther from the surface than the
+            // corners of the cube plus two cells, neither the cube nor the cells next to it, which
+            // share its grid points, contain any surface. It is not refined and only its first
+            // corner is stored. thin_feature_detection distrusts the distance, so it disables
+            // this.
+            let skip_empty = exact && !self.thin_feature_detection;
+            let center_distance = (cell_size + self.res * From::from(2f32))
+                * Float::sqrt(From::from(3f32))
+                * self.descent_margin;
+            let mut center_values = Vec::new();
+            if skip_empty {
+                let centers: Vec<_> = cubes.iter().map(|&(_, pos, _)| corner(pos, 7)).collect();
+                center_values = vec![S::zero(); centers.len()];
+                if let Err(e) = self.values(&centers, &mut center_values) {
+                    return Some(e);
+                }
+                let mut kept = 0;
+                for c in 0..cubes.len() {
+                    let (idx, _, val) = cubes[c];
+                    if Float::abs(center_values[c]) > center_distance {
+                        self.value_grid.insert(idx, val);
+                    } else {
+                        cubes[kept] = cubes[c];
+                        center_values[kept] = center_values[c];
+                        kept += 1;
+                    }
+                }
+                cubes.truncate(kept);
+                center_values.truncate(kept);
+            }
+            // All corners but the first and, if evaluated, the center, which are already known.
+            let num_points = if skip_empty { 6 } else { 7 };
+            let points: Vec<_> = cubes
+                .iter()
+                .flat_map(|&(_, pos, _)| (1..=num_points).map(move |i| corner(pos, i)))
+                .collect();
+            let mut values = vec![S::zero(); points.len()];
+            if let Err(e) = self
+                .values(&points, &mut values)
+                .and_then(|_| self.check_timeout())
+            {
+                return Some(e);
+            }
+
+            let mut next_cubes = Vec::new();
+            // Cubes, that are only refined, if thin_feature_detection finds a sign change.
+            let mut unrefined_cubes = Vec::new();
+            for (c, &(idx, pos, val)) in cubes.iter().enumerate() {
+                for i in 0..8 {
+                    let midx = [
+                        idx[0] + (i & 1) * size,
+                        idx[1] + ((i >> 1) & 1) * size,
+                        idx[2] + ((i >> 2) & 1) * size,
+                    ];
+                    let (mpos, value) = if i == 0 {
+                        (pos, val)
+                    } else if i == 7 && skip_empty {
+                        (corner(pos, 7), center_values[c])
+                    } else {
+                        (
+                            points[c * num_points + i - 1],
+                            values[c * num_points + i - 1],
+                        )
+                    };
+
+                    if value == From::from(0f32) {
+                        return Some(DualContouringError::HitZero(format!("{}", mpos)));
+                    }
+
+                    let refine = !exact || Float::abs(value) <= sub_cube_diagonal;
+                    if size > 1 && refine && !clip {
+                        next_cubes.push((midx, mpos, value));
+                    } else if size > 1 && !clip && self.thin_feature_detection {
+                        unrefined_cubes.push((midx, mpos, value));
+                    } else {
+                        if size > 1 && refine {
+                            self.num_clipped_cubes += 1;
+                        }
+                        self.value_grid.insert(midx, value);
+                    }
+                }
+            }
+
+            // Refine cubes, whose center has a different sign than their corner.
+            let half: S = From::from(0.5f32);
+            let centers: Vec<_> = unrefined_cubes
+                .iter()
+                .map(|&(_, pos, _)| pos + offset * half)
+                .collect();
+            let mut center_values = vec![S::zero(); centers.len()];
+            if !centers.is_empty() {
+                if let Err(e) = self.values(&centers, &mut center_values) {
+                    return Some(e);
+                }
+            }
+            for (&(midx, mpos, value), &center_value) in
+                unrefined_cubes.iter().zip(center_values.iter())
+            {
+                if Float::signum(center_value) != Float::signum(value) {
+                    next_cubes.push((midx, mpos, value));
+                } else {
+                    self.value_grid.insert(midx, value);
+                }
+            }
+            cubes = next_cubes;
+        }
+        None
+    }
+
+    // Delete all values from value grid that do not have a value of opposing signum in any
+    // neighboring index.
+    // This might reduces memory usage by ~10x.
+    fn compact_value_grid(&mut self) {
+        // Collect all indexes to remove.
+        let value_grid = &mut self.value_grid;
+        let keys_to_remove: Vec<_> = install(&self.thread_pool, || {
+            value_grid
+                .par_iter()
+                .filter(|&(idx, &v)| {
+                    if idx[0] == 0 || idx[1] == 0 || idx[2] == 0 {
+                        // This grid cell does not have neighbors in some directions. Ignore.
+                        return false;
+                    }
+                    for z in 0..3 {
+                        for y in 0..3 {
+                            for x in 0..3 {
+                                let adjacent_idx = [idx[0] + x - 1, idx[1] + y - 1, idx[2] + z - 1];
+                                if let Some(&adjacent_value) = value_grid.get(&adjacent_idx) {
+                                    if Float::signum(v) != Float::signum(adjacent_value) {
+                                        // Don't collect indexes with
+                                        // opposing signum.
+                                        return false;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    true
+                })
+                .map(|(k, _)| *k)
+                .collect()
+        });
+        for k in keys_to_remove {
+            value_grid.remove(&k);
+        }
+        value_grid.shrink_to_fit();
+    }
+
+    // Store crossing positions of edges in edge_grid
+    fn generate_edge_grid(&mut self) -> Result<(), DualContouringError> {
+        let mut edge_grid = self.edge_grid.borrow_mut();
+        for (&point_idx, &point_value) in &self.value_grid {
+            for &edge in &[Edge::A, Edge::B, Edge::C] {
+                let mut adjacent_idx = point_idx;
+                adjacent_idx[edge as usize] += 1;
+                if let Some(&adjacent_value) = self.value_grid.get(&adjacent_idx) {
+                    let (point_pos, adjacent_pos) = self.edge_index_to_world(EdgeIndex {
+                        edge,
+                        index: point_idx,
+                    });
+                    if let Some(plane) =
+                        self.find_zero(point_pos, point_value, adjacent_pos, adjacent_value)?
+                    {
+                        edge_grid.insert(
+                            EdgeIndex {
+                                edge,
+                                index: point_idx,
+                            },
+                            plane,
+                        );
+                    }
+                }
+            }
+        }
+        // Without sign change there are no crossing edges, which would silently yield an empty
+        // mesh. This also covers value_grid being empty after compaction. If min_cell_size
+        // stopped refinement, the surface may exist but was not sampled finely enough.
+        if edge_grid.is_empty() && self.num_clipped_cubes == 0 {
+            return Err(DualContouringError::NoSurfaceInBounds);
+        }
+        drop(edge_grid);
+        if self.num_clipped_cubes > 0 {
+            self.prune_incomplete_edges();
+        }
+        Ok(())
+    }
+
+    // If sampling did not refine all cubes close to the surface, some cells adjacent to edges in
+    // edge_grid miss corners in value_grid, or miss some of their crossing edges in edge_grid.
+    // Remove edges adjacent to such cells, until all remaining edges are adjacent to complete
+    // cells only.
+    fn prune_incomplete_edges(&mut self) {
+        let mut edge_grid = self.edge_grid.borrow_mut();
+        loop {
+            let incomplete: Vec<EdgeIndex> = edge_grid
+                .keys()
+                .filter(|edge_index| {
+                    !edge_index.quad_cells().iter().all(|cell| {
+                        matches!(*cell, Some(cell) if self.is_complete_cell(cell, &edge_grid))
+                    })
+                })
+                .cloned()
+                .collect();
+            if incomplete.is_empty() {
+                break;
+            }
+            for edge_index in &incomplete {
+                edge_grid.remove(edge_index);
+            }
+        }
+    }
+
+    // Returns true, if all corners of the cell are in value_grid and all its crossing edges are
+    // in edge_grid.
+    fn is_complete_cell(&self, idx: Index, edge_grid: &HashMap<EdgeIndex, Plane<S>>) -> bool {
+        for z in 0..2 {
+            for y in 0..2 {
+                for x in 0..2 {
+                    if !self
+                        .value_grid
+                        .contains_key(&[idx[0] + x, idx[1] + y, idx[2] + z])
+                    {
+                        return false;
+                    }
+                }
+            }
+        }
+        let mut crossing_edges = CELL_CONFIGS[self.bitset_for_cell(idx).as_u32() as usize]
+            .iter()
+            .fold(BitSet::zero(), |sum, x| sum.merge(*x));
+        crossing_edges.all(|edge| {
+            edge_grid.contains_key(
+                &EdgeIndex {
+                    edge: Edge::from_usize(edge),
+                    index: idx,
+                }
+                .base(),
+            )
+        })
+    }
+
+    // Solves QEFs in vertex stack, starting at the highest level, down all layers until the qef
+    // error is below threshold.
+    // Returns the number of solved QEFs.
+    fn solve_qefs(&mut self) -> Result<usize, DualContouringError> {
+        self.num_qef_fallbacks = 0;
+        self.error_thresholds.clear();
+        if self.error_field.is_some() {
+            self.error_thresholds = self
+                .vertex_octtree
+                .iter()
+                .map(|layer| vec![S::nan(); layer.len()])
+                .collect();
+        }
+        if self.method == Method::SurfaceNets {
+            // Vertices are placed at their mass points, see add_mesh_vertex().
+            return Ok(0);
+        }
+        let mut num_solved = 0;
+        for i in 0..self
+            .vertex_octtree
+            .last()
+            .map_or(0, |top_layer| top_layer.len())
+        {
+            num_solved += self.recursively_solve_qefs(self.vertex_octtree.len() - 1, i)?;
+        }
+        Ok(num_solved)
+    }
+
+    fn recursively_solve_qefs(
+        &mut self,
+        layer: usize,
+        index_in_layer: usize,
+    ) -> Result<usize, DualContouringError> {
+        let regularization = self.qef_regularization;
+        let checks = self.invariant_checks;
+        let vertex = &mut self.vertex_octtree[layer][index_in_layer];
+        assert!(vertex.children.is_empty() || layer > 0);
+        // Make sure we never solve a qef twice.
+        check_invariant!(
+            checks,
+            vertex.qef.error.is_nan(),
+            "found solved qef layer {:?} index {:?} {:?} parent: {:?}",
+            layer,
+            index_in_layer,
+            vertex.index,
+            vertex.parent
+        );
+        if layer > 0 && vertex.is_unmergeable() {
+            // Never collapsed into, so skip its qef and solve the children.
+            let mut num_solved = 0;
+            for i in 0..self.vertex_octtree[layer][index_in_layer].children.len() {
+                let child_index = self.vertex_octtree[layer][index_in_layer].children[i];
+                num_solved += self.recursively_solve_qefs(layer - 1, child_index)?;
+            }
+            return Ok(num_solved);
+        }
+        // Solve qef and store error.
+        if vertex.qef.solve(regularization) {
+            self.num_qef_fallbacks += 1;
+        }
+        let error = vertex.qef.error;
+        let threshold = match self.error_field {
+            Some(ref f) => {
+                let s = vertex.qef.solution;
+                let threshold = self.res * f([s.x, s.y, s.z]);
+                self.error_thresholds[layer][index_in_layer] = threshold;
+                threshold
+            }
+            None => self.error,
+        };
+        let mut num_solved = 1;
+        // If error exceed threshold, recurse into subvertices.
+        if Float::abs(error) > threshold {
+            for i in 0..self.vertex_octtree[layer][index_in_layer].children.len() {
+                let child_index = self.vertex_octtree[layer][index_in_layer].children[i];
+                num_solved += self.recursively_solve_qefs(layer - 1, child_index)?;
+            }
+        }
+        Ok(num_solved)
+    }
+
+    // Generates leaf vertices along with a map that points VertexIndices to the index in the leaf
+    // vertex vec.
+    #[allow(clippy::type_complexity)]
+    fn generate_leaf_vertices(
+        &self,
+    ) -> Result<(Vec<Vertex<S>>, HashMap<VertexIndex, usize>), DualContouringError> {
+        let mut index_map = HashMap::new();
+        let mut vertices = Vec::new();
+        let boundary_edges = self.boundary_edges();
+        for edge_index in self.edge_grid.borrow().keys() {
+            self.add_vertices_for_minimal_egde(
+                edge_index,
+                &boundary_edges,
+                &mut vertices,
+                &mut index_map,
+            );
+        }
+        for vertex in &mut vertices {
+            for neighbor_vec in &mut vertex.neighbors {
+                for neighbor in neighbor_vec.iter_mut() {
+                    match *neighbor {
+                        VarIndex::VertexIndex(vi) => *neighbor = VarIndex::Index(index_map[&vi]),
+                        VarIndex::Index(_) => panic!("unexpected Index in fresh leaf map."),
+                    }
+                }
+            }
+        }
+        check_neighbor_symmetry(&vertices, self.invariant_checks)?;
+        Ok((vertices, index_map))
+    }
+    // The edges in edge_grid, that leave the grid, see edge_leaves_grid().
+    fn boundary_edges(&self) -> HashSet<EdgeIndex> {
+        let edge_grid = self.edge_grid.borrow();
+        let (edge_grid, value_grid): (&HashMap<EdgeIndex, Plane<S>>, _) =
+            (&edge_grid, &self.value_grid);
+        install(&self.thread_pool, || {
+            edge_grid
+                .par_iter()
+                .map(|(&edge_index, _)| edge_index)
+                .filter(|&edge_index| edge_leaves_grid(value_grid, edge_index))
+                .collect()
+        })
+    }
+
+    fn add_vertices_for_minimal_egde(
+        &self,
+        edge_index: &EdgeIndex,
+        boundary_edges: &HashSet<EdgeIndex>,
+        vertices: &mut Vec<Vertex<S>>,
+        index_map: &mut HashMap<VertexIndex, usize>,
+    ) {
+        debug_assert!((edge_index.edge as usize) < 4);
+        let cell_size = na::Vector3::new(self.res, self.res, self.res);
+        for (&quad_egde, &idx) in QUADS[edge_index.edge as usize]
+            .iter()
+            .zip(&edge_index.quad_cells())
+        {
+            // Cells outside of the grid have no vertex. All cells around edges, that do not leave
+            // the grid, are inside of it.
+            let idx = match idx {
+                Some(idx)
+                    if !boundary_edges.contains(edge_index)
+                        || is_cell_in_grid(&self.value_grid, idx) =>
+                {
+                    idx
+                }
+                _ => continue,
+            };
+
+            let edge_set = get_connected_edges(quad_egde, self.bitset_for_cell(idx));
+            let vertex_index = VertexIndex {
+                edges: edge_set,
+                index: idx,
+            };
+            index_map.entry(vertex_index).or_insert_with(|| {
+                let mut neighbors = [
+                    Vec::new(),
+                    Vec::new(),
+                    Vec::new(),
+                    Vec::new(),
+                    Vec::new(),
+                    Vec::new(),
+                ];
+                for (i, neighbor) in neighbors.iter_mut().enumerate().take(6) {
+                    if let Some(mut neighbor_index) = vertex_index.neighbor(i).filter(|n| {
+                        boundary_edges.is_empty() || is_cell_in_grid(&self.value_grid, n.index)
+                    }) {
+                        for edges in get_connected_edges_from_edge_set(
+                            neighbor_index.edges,
+                            self.bitset_for_cell(neighbor_index.index),
+                        ) {
+                            neighbor_index.edges = edges;
+                            let idx = VarIndex::VertexIndex(neighbor_index);
+                            if !neighbor.contains(&idx) {
+                                neighbor.push(idx);
+                            }
+                        }
+                    }
+                }
+                let mut intersections = [0u32; 12];
+                for edge in edge_set {
+                    intersections[edge] = 1;
+                }
+                let tangent_planes: Vec<_> = edge_set
+                    .map(|edge| {
+                        self.get_edge_tangent_plane(&EdgeIndex {
+                            edge: Edge::from_usize(edge),
+                            index: idx,
+                        })
+                    })
+                    .collect();
+                let on_grid_boundary = !boundary_edges.is_empty()
+                    && edge_set.clone().any(|edge| {
+                        boundary_edges.contains(
+                            &EdgeIndex {
+                                edge: Edge::from_usize(edge),
+                                index: idx,
+                            }
+                            .base(),
+                        )
+                    });
+                let cell_origin = self.index_to_world(idx);
+                vertices.push(Vertex {
+                    index: idx,
+                    qef: qef::Qef::new(
+                        &tangent_planes,
+                        BoundingBox::new(&cell_origin, &(cell_origin + cell_size)),
+                    ),
+                    neighbors,
+                    parent: None,
+                    children: Vec::new(),
+                    edge_intersections: intersections,
+                    euler_characteristic: if on_grid_boundary {
+                        UNMERGEABLE_EULER_CHARACTERISTIC
+                    } else {
+                        1
+                    },
+                });
+                vertices.len() - 1
+            });
+        }
+    }
+
+    fn get_edge_tangent_plane(&self, edge_index: &EdgeIndex) -> Plane<S> {
+        if let Some(ref plane) = self.edge_grid.borrow().get(&edge_index.base()) {
+            return **plane;
+        }
+        panic!(
+            "could not find edge_point: {:?} -> {:?}",
+            edge_index,
+            edge_index.base()
+        );
+    }
+
+    fn bitset_for_cell(&self, idx: Index) -> BitSet {
+        bitset_for_cell(&self.value_grid, idx, self.inside_sign)
+    }
+
+    // Compute the quad of each crossing edge in parallel batches and call f with the normal at
+    // the crossing and the (layer, index) in the octtree of each vertex of the quad. Fails, if a
+    // vertex of a quad is missing in the octtree.
+    fn for_each_quad<F>(
+        &mut self,
+        deferred_winding: bool,
+        mut f: F,
+    ) -> Result<(), DualContouringError>
+    where
+        F: FnMut(&mut Self, &na::Vector3<S>, &[(usize, usize)]),
+    {
+        // The edges along with the normal where the surface crosses them.
+        let edges: Vec<(EdgeIndex, na::Vector3<S>)> = self
+            .edge_grid
+            .borrow()
+            .iter()
+            .map(|(&edge_index, plane)| (edge_index, plane.n))
+            .collect();
+        let boundary_edges = self.boundary_edges();
+        self.num_boundary_edges = 0;
+        self.num_degenerate_quads = 0;
+        for batch in edges.chunks(QUAD_BATCH_SIZE) {
+            let quads: Vec<_> = {
+                let generator = QuadGenerator {
+                    value_grid: &self.value_grid,
+                    vertex_octtree: &self.vertex_octtree,
+                    vertex_index_map: &self.vertex_index_map,
+                    error: self.error,
+                    error_thresholds: &self.error_thresholds,
+                    deferred_winding,
+                    inside_sign: self.inside_sign,
+                };
+                // None for edges, that leave the grid, Some(Ok(None)) for degenerate quads.
+                install(&self.thread_pool, || {
+                    batch
+                        .par_iter()
+                        .map(|&(edge_index, _)| {
+                            if boundary_edges.contains(&edge_index) {
+                                None
+                            } else {
+                                Some(generator.compute_quad(edge_index))
+                            }
+                        })
+                        .collect()
+                })
+            };
+            self.num_boundary_edges += quads.iter().filter(|quad| quad.is_none()).count();
+            self.num_degenerate_quads += quads
+                .iter()
+                .filter(|quad| matches!(quad, Some(Ok(None))))
+                .count();
+            for ((_, normal), quad) in batch
+                .iter()
+                .zip(quads)
+                .filter_map(|(edge, quad)| quad.map(|quad| (edge, quad)))
+            {
+                if let Some(quad) = quad? {
+                    f(self, normal, &quad);
+                }
+            }
+        }
+        if self.num_boundary_edges > 0 {
+            log!(
+                self,
+                "Warning: {} crossing edges are on the boundary of the grid and generated no \
+                 face. The mesh is open there.",
+                self.num_boundary_edges
+            );
+        }
+        Ok(())
+    }
+
+    // Generate the quads for all edges in edge_grid and add them to the mesh.
+    // The quads are computed in parallel. Afterwards the octtree vertices they reference are
+    // added to the mesh in the order of the edges, so the result does not depend on the
+    // scheduling of the parallel phase.
+    fn generate_quads(&mut self) -> Result<(), DualContouringError> {
+        // Map from (layer, index) in the octtree to the index in self.mesh.vertices.
+        let mut mesh_indices = HashMap::new();
+        let mut labels = Vec::new();
+        let mut face_normals = Vec::new();
+        self.for_each_quad(self.deferred_winding, |mdc, normal, quad| {
+            let face: SmallVec<[usize; 4]> = quad
+                .iter()
+                .map(|&(layer, index)| {
+                    *mesh_indices
+                        .entry((layer, index))
+                        .or_insert_with(|| mdc.add_mesh_vertex(layer, index))
+                })
+                .collect();
+            if let Some(ref labeler) = mdc.face_labeler {
+                let sum = face.iter().fold(na::Vector3::zeros(), |sum, &i| {
+                    sum + na::Vector3::from(mdc.mesh.vertices[i])
+                });
+                let num_vertices: S = From::from(face.len() as f32);
+                let centroid: na::Vector3<S> = sum / num_vertices;
+                labels.push(labeler([centroid.x, centroid.y, centroid.z]));
+            }
+            if let Some(ref mut triangles) = mdc.triangles {
+                triangles.extend(triangulate(&face));
+            }
+            if mdc.face_normals {
+                // The gradient points inwards, if the function is positive inside.
+                let n = match mdc.inside_sign {
+                    Sign::Negative => normal.normalize(),
+                    Sign::Positive => -normal.normalize(),
+                };
+                face_normals.push([n.x, n.y, n.z]);
+            }
+            mdc.mesh.faces.push(face);
+        })?;
+        if self.face_labeler.is_some() {
+            self.mesh.labels = Some(labels);
+        }
+        if self.face_normals {
+            self.mesh.face_normals = Some(face_normals);
+        }
+        if let Some(ref f) = self.vertex_color {
+            self.mesh.vertex_colors = Some(self.mesh.vertices.iter().map(|&v| f(v)).collect());
+        }
+        Ok(())
+    }
+
+    // Store the vertex at layer/index of the octtree in the mesh and return its index.
+    fn add_mesh_vertex(&mut self, layer: usize, index: usize) -> usize {
+        let p = self.mesh_vertex(layer, index);
+        self.mesh.vertices.push(p);
+        self.mesh.vertices.len() - 1
+    }
+
+    // The position of the vertex at layer/index of the octtree in the mesh.
+    fn mesh_vertex(&mut self, layer: usize, index: usize) -> [S; 3] {
+        let regularization = self.qef_regularization;
+        let vertex = &mut self.vertex_octtree[layer][index];
+        if self.method == Method::SurfaceNets {
+            let p = vertex.qef.mass_point();
+            return [p.x, p.y, p.z];
+        }
+        if vertex.qef.error.is_nan() {
+            // Maybe the qef was not solved, since the error in the layer above was below the
+            // threshold. But it seems, manifold criterion has catched and we need to solve it now.
+            if vertex.qef.solve(regularization) {
+                self.num_qef_fallbacks += 1;
+            }
+        }
+        let qef_solution = vertex.qef.solution;
+        [qef_solution.x, qef_solution.y, qef_solution.z]
+    }
+
+    // If a is inside the object and b outside - this method returns the point on the line between
+    // a and b where the object edge is. It also returns the normal on that point.
+    // av and bv represent the object values at a and b.
+    fn find_zero(
+        &self,
+        a: na::Point3<S>,
+        av: S,
+        b: na::Point3<S>,
+        bv: S,
+    ) -> Result<Option<Plane<S>>, DualContouringError> {
+        assert!(a != b);
+        if Float::signum(av) == Float::signum(bv) {
+            return Ok(None);
+        }
+        match self.root_finder {
+            RootFinder::Linear => self.find_zero_linear(a, av, b, bv),
+            RootFinder::Secant => self.find_zero_secant(a, av, b, bv),
+            RootFinder::Brent => self.find_zero_brent(a, av, b, bv),
+        }
+        .map(|p| {
+            Some(Plane {
+                p,
+                // We need a precise normal here.
+                n: self.normal(&p),
+            })
+        })
+    }
+
+    // The normal at p from normal_grid, if it covers p, otherwise from the function.
+    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+        self.normal_grid
+            .as_ref()
+            .and_then(|grid| grid.normal(p))
+            .unwrap_or_else(|| match self.normal_memo {
+                Some(ref memo) => {
+                    if let Some(n) = memo.borrow().get(p) {
+                        return n;
+                    }
+                    let n = self.function.normal(p);
+                    memo.borrow_mut().insert(*p, n);
+                    n
+                }
+                None => self.function.normal(p),
+            })
+    }
+
+    // Find the zero crossing between a and b by repeated linear interpolation.
+    fn find_zero_linear(
+        &self,
+        a: na::Point3<S>,
+        av: S,
+        b: na::Point3<S>,
+        bv: S,
+    ) -> Result<na::Point3<S>, DualContouringError> {
+        let d = a - b;
+        let mut distance = Float::max(
+            Float::max(Float::abs(d.x), Float::abs(d.y)),
+            Float::abs(d.z),
+        );
+        distance = Float::min(Float::min(distance, Float::abs(av)), Float::abs(bv));
+        let precision: S = From::from(PRECISION);
+        if distance < precision * self.res {
+            return Ok(if Float::abs(bv) < Float::abs(av) {
+                b
+            } else {
+                a
+            });
+        }
+        // Linear interpolation of the zero crossing.
+        let n = a + (b - a) * (Float::abs(av) / Float::abs(bv - av));
+        let nv = self.value(&n)?;
+
+        if Float::signum(av) != Float::signum(nv) {
+            self.find_zero_linear(a, av, n, nv)
+        } else {
+            self.find_zero_linear(n, nv, b, bv)
+        }
+    }
+
+    // Find the zero crossing between a and b with secant steps. Points are parametrized as
+    // a + (b - a) * t.
+    fn find_zero_secant(
+        &self,
+        a: na::Point3<S>,
+        av: S,
+        b: na::Point3<S>,
+        bv: S,
+    ) -> Result<na::Point3<S>, DualContouringError> {
+        let precision: S = From::from(PRECISION);
+        let tolerance = precision * self.res;
+        let length = max_abs_component(&(b - a));
+        let half: S = From::from(0.5f32);
+        // The bracket around the zero crossing.
+        let (mut lo, mut lo_v, mut hi, mut hi_v) = (S::zero(), av, S::one(), bv);
+        // The last two points evaluated.
+        let (mut t0, mut v0, mut t1, mut v1) = (S::zero(), av, S::one(), bv);
+        loop {
+            let distance = Float::min(
+                Float::min((hi - lo) * length, Float::abs(lo_v)),
+                Float::abs(hi_v),
+            );
+            if distance < tolerance {
+                let t = if Float::abs(hi_v) < Float::abs(lo_v) {
+                    hi
+                } else {
+                    lo
+                };
+                return Ok(a + (b - a) * t);
+            }
+            let mut t = t1 - v1 * (t1 - t0) / (v1 - v0);
+            if Float::is_nan(t) || t <= lo || t >= hi {
+                t = (lo + hi) * half;
+            }
+            let v = self.value(&(a + (b - a) * t))?;
+            t0 = t1;
+            v0 = v1;
+            t1 = t;
+            v1 = v;
+            if Float::signum(v) == Float::signum(lo_v) {
+                lo = t;
+                lo_v = v;
+            } else {
+                hi = t;
+                hi_v = v;
+            }
+        }
+    }
+
+    // Find the zero crossing between a and b with Brent's method, following Numerical Recipes,
+    // 9.3. Points are parametrized as a + (b - a) * t.
+    fn find_zero_brent(
+        &self,
+        a: na::Point3<S>,
+        av: S,
+        b: na::Point3<S>,
+        bv: S,
+    ) -> Result<na::Point3<S>, DualContouringError> {
+        let precision: S = From::from(PRECISION);
+        let tolerance = precision * self.res;
+        let half: S = From::from(0.5f32);
+        let two: S = From::from(2f32);
+        let three: S = From::from(3f32);
+        let tol1 = half * tolerance / max_abs_component(&(b - a));
+        let (mut ta, mut va) = (S::zero(), av);
+        let (mut tb, mut vb) = (S::one(), bv);
+        let (mut tc, mut vc) = (tb, vb);
+        let mut d = tb - ta;
+        let mut e = d;
+        loop {
+            if Float::signum(vb) == Float::signum(vc) {
+                tc = ta;
+                vc = va;
+                d = tb - ta;
+                e = d;
+            }
+            if Float::abs(vc) < Float::abs(vb) {
+                ta = tb;
+                tb = tc;
+                tc = ta;
+                va = vb;
+                vb = vc;
+                vc = va;
+            }
+            let xm = half * (tc - tb);
+            if Float::abs(xm) <= tol1 || Float::abs(vb) < tolerance {
+                return Ok(a + (b - a) * tb);
+            }
+            if Float::abs(e) >= tol1 && Float::abs(va) > Float::abs(vb) {
+                // Try inverse quadratic interpolation, or a secant step, if only two points are
+                // distinct.
+                let s = vb / va;
+                let (mut p, mut q) = if ta == tc {
+                    (two * xm * s, S::one() - s)
+                } else {
+                    let q = va / vc;
+                    let r = vb / vc;
+                    (
+                        s * (two * xm * q * (q - r) - (tb - ta) * (r - S::one())),
+                        (q - S::one()) * (r - S::one()) * (s - S::one()),
+                    )
+                };
+                if p > S::zero() {
+                    q = -q;
+                }
+                p = Float::abs(p);
+                let min1 = three * xm * q - Float::abs(tol1 * q);
+                let min2 = Float::abs(e * q);
+                if two * p < Float::min(min1, min2) {
+                    e = d;
+                    d = p / q;
+                } else {
+                    // Interpolation failed, bisect.
+                    d = xm;
+                    e = d;
+                }
+            } else {
+                // Bounds are decreasing too slowly, bisect.
+                d = xm;
+                e = d;
+            }
+            ta = tb;
+            va = vb;
+            tb += if Float::abs(d) > tol1 {
+                d
+            } else if xm > S::zero() {
+                tol1
+            } else {
+                -tol1
+            };
+            vb = self.value(&(a + (b - a) * tb))?;
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'a, S: From<f32> + RealField + Float + AsUSize + Serialize + DeserializeOwned>
+    ManifoldDualContouring<'a, S>
+{
+    /// Write the sampled grid and the octtree to writer, after
+    /// [`build_octree()`](Self::build_octree()) or [`tessellate()`](Self::tessellate()). Load it
+    /// via [`load_state()`](Self::load_state()), possibly on another machine, to skip sampling.
+    pub fn save_state<W: Write>(&self, writer: W) -> Result<(), Box<dyn error::Error>> {
+        bincode::serialize_into(
+            writer,
+            &(
+                &self.origin,
+                &self.res,
+                &self.dim,
+                &self.value_grid,
+                &*self.edge_grid.borrow(),
+                &self.vertex_octtree,
+                &self.vertex_index_map,
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// Replace the sampled grid and the octtree with the state written by
+    /// [`save_state()`](Self::save_state()), e.g. to continue with
+    /// [`tessellate_from_state()`](Self::tessellate_from_state()). Like
+    /// [`with_sign_field()`](Self::with_sign_field()), the resolution of the state replaces res.
+    pub fn load_state<R: Read>(&mut self, reader: R) -> Result<(), Box<dyn error::Error>> {
+        #[allow(clippy::type_complexity)]
+        let (origin, res, dim, value_grid, edge_grid, vertex_octtree, vertex_index_map): (
+            na::Point3<S>,
+            S,
+            [usize; 3],
+            HashMap<Index, S>,
+            HashMap<EdgeIndex, Plane<S>>,
+            Vec<Vec<Vertex<S>>>,
+            HashMap<VertexIndex, usize>,
+        ) = bincode::deserialize_from(reader)?;
+        self.error = self.error / self.res * res;
+        self.origin = origin;
+        self.res = res;
+        self.dim = dim;
+        self.value_grid = value_grid;
+        self.edge_grid = RefCell::new(edge_grid);
+        self.vertex_octtree = vertex_octtree;
+        self.vertex_index_map = vertex_index_map;
+        self.presampled = true;
+        Ok(())
+    }
+
+    /// Return the octtree as JSON for debugging and visualization, after
+    /// [`build_octree()`](Self::build_octree()) or [`tessellate()`](Self::tessellate()).
+    ///
+    /// The result is an array of layers, starting with the leafs. Each layer is an array of
+    /// vertices with their grid `index`, the indices of their `parent` in the next layer and of
+    /// their `children` in the previous one, their `euler_characteristic` and their `qef_error`.
+    /// The latter is null for QEFs, which were not solved, e.g. before tessellating or below a
+    /// vertex with a small enough error.
+    pub fn octtree_debug_json(&self) -> String {
+        let layers: Vec<Vec<DebugVertex<S>>> = self
+            .vertex_octtree
+            .iter()
+            .map(|layer| {
+                layer
+                    .iter()
+                    .map(|vertex| DebugVertex {
+                        index: vertex.index,
+                        parent: vertex.parent,
+                        children: &vertex.children,
+                        euler_characteristic: vertex.euler_characteristic,
+                        qef_error: vertex.qef.error,
+                    })
+                    .collect()
+            })
+            .collect();
+        // Serializing plain structs with string keys to a String cannot fail.
+        serde_json::to_string(&layers).unwrap()
+    }
+}
+
+// The parts of a Vertex written by ManifoldDualContouring::octtree_debug_json().
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct DebugVertex<'b, S: RealField> {
+    index: Index,
+    parent: Option<usize>,
+    children: &'b [usize],
+    euler_characteristic: i32,
+    qef_error: S,
+}
+
+// The layer and index in the octtree of each vertex of a quad.
+type Quad = SmallVec<[(usize, usize); 4]>;
+
+// Read-only view of the state needed to generate quads. Unlike ManifoldDualContouring this can
+// be shared between threads.
+struct QuadGenerator<'b, S: RealField> {
+    value_grid: &'b HashMap<Index, S>,
+    vertex_octtree: &'b [Vec<Vertex<S>>],
+    vertex_index_map: &'b HashMap<VertexIndex, usize>,
+    error: S,
+    // Replaces error for each solved vertex of the octtree, if not empty.
+    error_thresholds: &'b [Vec<S>],
+    // Whether to skip orienting the quads.
+    deferred_winding: bool,
+    inside_sign: Sign,
+}
+
+impl<'b, S: From<f32> + RealField + Float> QuadGenerator<'b, S> {
+    // Return the layer and index in the octtree of the vertex belonging to edge/idx.
+    // Qefs, that are solved when adding vertices to the mesh, do not influence the walk up the
+    // octtree. That way the result does not depend on the order of the lookups.
+    fn lookup_cell_vertex(
+        &self,
+        edge: Edge,
+        idx: Index,
+    ) -> Result<(usize, usize), DualContouringError> {
+        // Generate the proper vertex Index from a single edge and an Index.
+        let edge_set = get_connected_edges(
+            edge,
+            bitset_for_cell(self.value_grid, idx, self.inside_sign),
+        );
+        let vertex_index = VertexIndex {
+            edges: edge_set,
+            index: idx,
+        };
+
+        // Convert the vertex index to index and layer in the Octtree.
+        match self.vertex_index_map.get(&vertex_index) {
+            Some(&octtree_index) => self.collapse_leaf_vertex(octtree_index),
+            None => Err(DualContouringError::InternalState(format!(
+                "no vertex for {:?}",
+                vertex_index
+            ))),
+        }
+    }
+
+    // Return the layer and index in the octtree of the vertex, that represents the leaf vertex
+    // at octtree_index in the mesh.
+    fn collapse_leaf_vertex(
+        &self,
+        octtree_index: usize,
+    ) -> Result<(usize, usize), DualContouringError> {
+        let vertex = |layer: usize, index: usize| {
+            self.vertex_octtree
+                .get(layer)
+                .and_then(|vertices| vertices.get(index))
+                .ok_or_else(|| {
+                    DualContouringError::InternalState(format!(
+                        "no vertex {} in layer {} of the octtree",
+                        index, layer
+                    ))
+                })
+        };
+        let mut octtree_index = octtree_index;
+        let mut octtree_layer = 0;
+        if self.vertex_octtree.len() == 1 {
+            // There are no parents, e.g. with Method::SurfaceNets.
+            vertex(octtree_layer, octtree_index)?;
+            return Ok((octtree_layer, octtree_index));
+        }
+        // Walk up the chain of parents
+        loop {
+            let next_index = vertex(octtree_layer, octtree_index)?
+                .parent
+                .ok_or_else(|| {
+                    DualContouringError::InternalState(format!(
+                        "vertex {} in layer {} of the octtree has no parent",
+                        octtree_index, octtree_layer
+                    ))
+                })?;
+            let next_vertex = vertex(octtree_layer + 1, next_index)?;
+            let error = next_vertex.qef.error;
+            let threshold = match self.error_thresholds.get(octtree_layer + 1) {
+                Some(thresholds) => thresholds[next_index],
+                None => self.error,
+            };
+            if (!error.is_nan() && error > threshold)
+                || (octtree_layer == self.vertex_octtree.len() - 2)
+                || !next_vertex.is_2manifold()
+            {
+                // Stop, if either the error is too large or we will reach the top.
+                break;
+            }
+            octtree_layer += 1;
+            octtree_index = next_index;
+        }
+        Ok((octtree_layer, octtree_index))
+    }
+
+    // Compute a quad for the given edge. Return the layer and index in the octtree of its
+    // vertices.
+    // Quads of edges, that leave the grid (see edge_leaves_grid()), are not computed. Fails, if a
+    // vertex of the quad is missing in the octtree.
+    fn compute_quad(&self, edge_index: EdgeIndex) -> Result<Option<Quad>, DualContouringError> {
+        debug_assert!((edge_index.edge as usize) < 4);
+
+        let mut p = SmallVec::with_capacity(4);
+        for (&quad_egde, &cell) in QUADS[edge_index.edge as usize]
+            .iter()
+            .zip(&edge_index.quad_cells())
+        {
+            let cell = match cell {
+                Some(cell) => cell,
+                None => return Ok(None),
+            };
+            let vertex = self.lookup_cell_vertex(quad_egde, cell)?;
+            // Dedup points before insertion (two minimal vertices might end up in the same parent
+            // vertex).
+            if !p.contains(&vertex) {
+                p.push(vertex)
+            }
+        }
+        // Avoid generating degenerate faces. They are counted in num_degenerate_quads.
+        if p.len() < 3 {
+            return Ok(None);
+        }
+        if self.deferred_winding {
+            return Ok(Some(p));
+        }
+        // Reverse order, if the edge is reversed.
+        if let Some(&v) = self.value_grid.get(&edge_index.index) {
+            if is_inside(v, self.inside_sign) {
+                p.reverse();
+            }
+        }
+        Ok(Some(p))
+    }
+}
+
+/// Tessellate each of items, a function with its res and relative_error like
+/// [`ManifoldDualContouring::new()`], in parallel. Each function is tessellated by its own
+/// [`ManifoldDualContouring`], the results are returned in the order of items.
+///
+/// The items are distributed over rayon's global thread pool. To use a custom pool instead, call
+/// this from within [`ThreadPool::install()`](rayon::ThreadPool::install()).
+pub fn tessellate_batch<S: From<f32> + RealField + Float + AsUSize>(
+    items: &[(&(dyn ImplicitFunction<S> + Sync), S, S)],
+) -> Vec<Result<Mesh<S>, DualContouringError>> {
+    items
+        .par_iter()
+        .map(|&(f, res, relative_error)| {
+            ManifoldDualContouring::new(f, res, relative_error).tessellate()
+        })
+        .collect()
+}
+
+// Run op in pool, if there is one, otherwise in the current thread, i.e. rayon's global pool.
+fn install<R: Send>(pool: &Option<Arc<ThreadPool>>, op: impl FnOnce() -> R + Send) -> R {
+    match pool {
+        Some(pool) => pool.install(op),
+        None => op(),
+    }
+}
+
+// Returns true, if some of the cells around the minimal edge are outside of the sampled grid,
+// i.e. they have negative indices or miss corners in value_grid. This happens, where the surface
+// reaches the boundary of the grid, e.g. of a sign field.
+fn edge_leaves_grid<S>(value_grid: &HashMap<Index, S>, edge_index: EdgeIndex) -> bool {
+    !edge_index
+        .quad_cells()
+        .iter()
+        .all(|cell| matches!(*cell, Some(cell) if is_cell_in_grid(value_grid, cell)))
+}
+
+// Returns true, if all corners of the cell are in value_grid.
+fn is_cell_in_grid<S>(value_grid: &HashMap<Index, S>, idx: Index) -> bool {
+    (0..8).all(|corner| {
+        value_grid.contains_key(&[
+            idx[0] + (corner & 1),
+            idx[1] + (corner >> 1 & 1),
+            idx[2] + (corner >> 2),
+        ])
+    })
+}
+
+// The error for a function value at p, that is not finite.
+fn non_finite_value<S: RealField + Float>(p: &na::Point3<S>) -> DualContouringError {
+    DualContouringError::NonFiniteValue {
+        position: [p.x, p.y, p.z].map(|x| x.to_f64().unwrap_or(f64::NAN)),
+    }
+}
+
+// Whether a grid point with value v is inside the object. Values, that are not negative, count as
+// positive, so both signs agree on which edges cross the surface.
+pub(crate) fn is_inside<S: From<f32> + RealField>(v: S, inside_sign: Sign) -> bool {
+    (v < From::from(0f32)) == (inside_sign == Sign::Negative)
+}
+
+fn bitset_for_cell<S: From<f32> + RealField>(
+    value_grid: &HashMap<Index, S>,
+    idx: Index,
+    inside_sign: Sign,
+) -> BitSet {
+    let mut idx = idx;
+    let mut result = BitSet::zero();
+    for z in 0..2 {
+        for y in 0..2 {
+            for x in 0..2 {
+                if let Some(&v) = value_grid.get(&idx) {
+                    if is_inside(v, inside_sign) {
+                        result.set(z << 2 | y << 1 | x);
+                    }
+                } else {
+                    panic!("did not find value_grid[{:?}]", idx);
+                }
+                idx[0] += 1;
+            }
+            idx[0] -= 2;
+            idx[1] += 1;
+        }
+        idx[1] -= 2;
+        idx[2] += 1;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_neighbor_symmetry, get_connected_edges_from_edge_set, qef,
+        subsample_euler_characteristics, tessellate_batch, Axis, CoordinateSystem,
+        DualContouringError, ManifoldDualContouring, Memo, Method, RootFinder, Sign, SignCheck,
+        VarIndex, Vertex,
+    };
+    use crate::{
+        bitset::BitSet,
+        test_functions::{Probe, Sphere},
+        ImplicitFunction, SplitFunction, Winding,
+    };
+    use bbox::BoundingBox;
+    use nalgebra as na;
+
+    // The unit sphere with inverted sign, i.e. positive inside.
+    fn inverted(sphere: &Sphere<f64>) -> Probe<'_, f64> {
+        Probe::new(sphere)
+            .with_value(|_, value| -value)
+            .with_normal(|_, normal| -normal)
+            .with_exact_distance(false)
+    }
+
+    // The unit sphere, whose value overestimates the distance to the surface.
+    fn overestimating(sphere: &Sphere<f64>, exact: bool) -> Probe<'_, f64> {
+        Probe::new(sphere)
+            .with_value(|_, value| value * 20.)
+            .with_exact_distance(exact)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::get_connected_edges_from_edge_set;
-    use crate::bitset::BitSet;
     //  Corner indexes
     //
     //      6---------------7
@@ -1006,27 +3557,1617 @@ mod tests {
     //  |/              |/
     //  0---------------1
 
-    //  Edge indexes
-    //
-    //      +-------9-------+
-    //     /|              /|
-    //    7 |            10 |              ^
-    //   /  8            /  11            /
-    //  +-------6-------+   |     ^    higher indexes in y
-    //  |   |           |   |     |     /
-    //  |   +-------3---|---+     |    /
-    //  2  /            5  /  higher indexes
-    //  | 1             | 4      in z
-    //  |/              |/        |/
-    //  o-------0-------+         +-- higher indexes in x ---->
+    //  Edge indexes
+    //
+    //      +-------9-------+
+    //     /|              /|
+    //    7 |            10 |              ^
+    //   /  8            /  11            /
+    //  +-------6-------+   |     ^    higher indexes in y
+    //  |   |           |   |     |     /
+    //  |   +-------3---|---+     |    /
+    //  2  /            5  /  higher indexes
+    //  | 1             | 4      in z
+    //  |/              |/        |/
+    //  o-------0-------+         +-- higher indexes in x ---->
+
+    #[test]
+    fn connected_edges() {
+        let cell = BitSet::from_4bits(0, 6, 3, 5);
+        let edge_set = BitSet::from_4bits(4, 5, 10, 11);
+        let connected_edges = get_connected_edges_from_edge_set(edge_set, cell);
+        assert_eq!(connected_edges.len(), 2);
+        assert!(connected_edges.contains(&BitSet::from_4bits(5, 5, 6, 10)));
+        assert!(connected_edges.contains(&BitSet::from_4bits(3, 3, 4, 11)));
+    }
+
+    #[test]
+    fn new_with_bbox() {
+        let sphere = Sphere::unit();
+        let bbox = BoundingBox::new(&na::Point3::new(-1., -1., 0.), &na::Point3::new(1., 1., 1.));
+        let mut mdc = ManifoldDualContouring::new_with_bbox(&sphere, bbox, 0.2, 0.1).unwrap();
+        assert!(mdc.dim[2] < ManifoldDualContouring::new(&sphere, 0.2, 0.1).dim[2]);
+        assert!(!mdc.tessellate().unwrap().faces.is_empty());
+
+        let empty = BoundingBox::new(&na::Point3::new(0., 0., 0.), &na::Point3::new(1., 1., 0.));
+        assert!(matches!(
+            ManifoldDualContouring::new_with_bbox(&sphere, empty, 0.2, 0.1),
+            Err(DualContouringError::InvalidBoundingBox(_))
+        ));
+        assert!(matches!(
+            ManifoldDualContouring::new_with_bbox(&sphere, BoundingBox::infinity(), 0.2, 0.1),
+            Err(DualContouringError::InvalidBoundingBox(_))
+        ));
+    }
+
+    #[test]
+    fn with_region() {
+        let cylinder = SplitFunction::new(
+            BoundingBox::new(
+                &na::Point3::new(-0.5, -0.5, f64::NEG_INFINITY),
+                &na::Point3::new(0.5, 0.5, f64::INFINITY),
+            ),
+            |p| na::Vector2::new(p.x, p.y).norm() - 0.5,
+            |p| na::Vector3::new(p.x, p.y, 0.).normalize(),
+        );
+        let e = ManifoldDualContouring::new(&cylinder, 0.1, 0.1)
+            .tessellate()
+            .unwrap_err();
+        assert!(matches!(e, DualContouringError::InvalidBoundingBox(_)));
+        assert!(e.to_string().contains("with_region()"));
+
+        let region = BoundingBox::new(
+            &na::Point3::new(-10., -10., -1.),
+            &na::Point3::new(10., 10., 1.),
+        );
+        let mut mdc = ManifoldDualContouring::new(&cylinder, 0.1, 0.1)
+            .with_region(region)
+            .with_bbox_dilation(0.2);
+        // Only z is clipped.
+        assert_eq!(mdc.dim, [14, 14, 24]);
+        let mesh = mdc.tessellate().unwrap();
+        assert!(!mesh.faces.is_empty());
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|v| (na::Vector2::new(v[0], v[1]).norm() - 0.5).abs() < 0.05));
+        assert!(mesh.vertices.iter().any(|v| v[2] < -1.));
+        assert!(mesh.vertices.iter().any(|v| v[2] > 1.));
+        // Disjoint from the bounding box of the function.
+        let disjoint = BoundingBox::new(&na::Point3::new(2., 2., 0.), &na::Point3::new(3., 3., 1.));
+        assert!(matches!(
+            ManifoldDualContouring::new(&cylinder, 0.1, 0.1)
+                .with_region(disjoint)
+                .tessellate(),
+            Err(DualContouringError::InvalidBoundingBox(_))
+        ));
+    }
+
+    #[test]
+    fn pow2roundup() {
+        assert_eq!(super::pow2roundup(1), Some(1));
+        assert_eq!(super::pow2roundup(2), Some(2));
+        assert_eq!(super::pow2roundup(3), Some(4));
+        assert_eq!(super::pow2roundup(1025), Some(2048));
+        assert_eq!(
+            super::pow2roundup(usize::MAX / 2 + 1),
+            Some(usize::MAX / 2 + 1)
+        );
+        assert_eq!(super::pow2roundup(usize::MAX / 2 + 2), None);
+    }
+
+    #[test]
+    fn resolution_too_fine() {
+        let sphere = Sphere::unit();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 1e-7, 0.1).with_bbox_dilation(0.);
+        let e = mdc.tessellate().unwrap_err();
+        assert!(matches!(e, DualContouringError::ResolutionTooFine { .. }));
+        assert!(e.to_string().contains("larger res"));
+    }
+
+    #[test]
+    fn resolution_too_coarse() {
+        let sphere = Sphere::unit();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 3., 0.1).with_bbox_dilation(0.);
+        assert_eq!(mdc.dim, [1, 1, 1]);
+        let e = mdc.tessellate().unwrap_err();
+        assert!(matches!(
+            e,
+            DualContouringError::ResolutionTooCoarse { dim: [1, 1, 1] }
+        ));
+        assert!(e.to_string().contains("smaller res"));
+        assert!(matches!(
+            mdc.sample_sign_field(),
+            Err(DualContouringError::ResolutionTooCoarse { .. })
+        ));
+    }
+
+    #[test]
+    fn sphere_is_closed() {
+        let sphere = Sphere::unit();
+        let mesh = ManifoldDualContouring::new(&sphere, 0.2, 0.1)
+            .tessellate()
+            .unwrap();
+        // Every directed edge must have a reversed twin.
+        let mut edges = std::collections::HashSet::new();
+        for face in &mesh.faces {
+            for i in 0..face.len() {
+                edges.insert((face[i], face[(i + 1) % face.len()]));
+            }
+        }
+        for &(a, b) in &edges {
+            assert!(edges.contains(&(b, a)), "edge {:?} has no twin", (a, b));
+        }
+        assert!(mesh
+            .faces
+            .iter()
+            .flatten()
+            .all(|&i| i < mesh.vertices.len()));
+    }
+
+    #[test]
+    fn sign_check() {
+        let unit = Sphere::unit();
+        let inverted = inverted(&unit);
+        assert!(matches!(
+            ManifoldDualContouring::new(&inverted, 0.2, 0.1)
+                .with_sign_check(SignCheck::Error)
+                .tessellate(),
+            Err(DualContouringError::InvertedSign)
+        ));
+        let mesh = ManifoldDualContouring::new(&inverted, 0.2, 0.1)
+            .with_sign_check(SignCheck::Flip)
+            .tessellate()
+            .unwrap();
+        // Faces must point away from the origin. Allow for some non-planar quads.
+        let outward = mesh
+            .faces
+            .iter()
+            .filter(|face| {
+                let v: Vec<_> = face
+                    .iter()
+                    .map(|&i| na::Vector3::from(mesh.vertices[i]))
+                    .collect();
+                (v[1] - v[0]).cross(&(v[2] - v[0])).dot(&v[0]) > 0.
+            })
+            .count();
+        assert!(outward > mesh.faces.len() * 9 / 10);
+        let sphere = Sphere::unit();
+        assert!(ManifoldDualContouring::new(&sphere, 0.2, 0.1)
+            .with_sign_check(SignCheck::Error)
+            .tessellate()
+            .is_ok());
+    }
+
+    #[test]
+    fn coordinate_system() {
+        let sphere = Sphere::unit();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.2, 0.1).with_face_normals(true);
+        let mesh = mdc.tessellate().unwrap();
+        for (system, convert, reversed) in [
+            (
+                CoordinateSystem::RightHandedYUp,
+                (|[x, y, z]: [f64; 3]| [x, z, -y]) as fn([f64; 3]) -> [f64; 3],
+                false,
+            ),
+            (CoordinateSystem::LeftHandedYUp, |[x, y, z]| [x, z, y], true),
+            (
+                CoordinateSystem::LeftHandedZUp,
+                |[x, y, z]| [x, -y, z],
+                true,
+            ),
+        ] {
+            let mut expected = mesh.clone();
+            for v in &mut expected.vertices {
+                *v = convert(*v);
+            }
+            for n in expected.face_normals.as_mut().unwrap() {
+                *n = convert(*n);
+            }
+            if reversed {
+                for face in &mut expected.faces {
+                    face.reverse();
+                }
+            }
+            let mut mdc = ManifoldDualContouring::new(&sphere, 0.2, 0.1)
+                .with_face_normals(true)
+                .with_coordinate_system(system);
+            let converted = mdc.tessellate().unwrap();
+            assert!(converted.approx_eq(&expected, 1e-9));
+            // The winding agrees with the face normals.
+            let agreeing = converted
+                .iter_face_vertices()
+                .zip(converted.face_normals.as_ref().unwrap())
+                .filter(|(face, n)| {
+                    let [a, b, c] = [0, 1, 2].map(|i| na::Vector3::from(face[i]));
+                    (b - a).cross(&(c - a)).dot(&na::Vector3::from(**n)) > 0.
+                })
+                .count();
+            assert!(agreeing > converted.faces.len() * 9 / 10);
+
+            let mut outward = 0;
+            let num_faces = mdc
+                .tessellate_streaming(|face, _| {
+                    let [a, b, c] = [0, 1, 2].map(|i| na::Vector3::from(face[i]));
+                    if (b - a).cross(&(c - a)).dot(&a) > 0. {
+                        outward += 1;
+                    }
+                })
+                .unwrap();
+            assert_eq!(num_faces, converted.faces.len());
+            assert!(outward > num_faces * 9 / 10);
+        }
+    }
+
+    #[test]
+    fn inside_sign() {
+        let sphere = Sphere::unit();
+        let expected = ManifoldDualContouring::new(&sphere, 0.2, 0.1)
+            .tessellate()
+            .unwrap();
+        let unit = Sphere::unit();
+        let inverted = inverted(&unit);
+        let mut mdc = ManifoldDualContouring::new(&inverted, 0.2, 0.1)
+            .with_inside_sign(Sign::Positive)
+            .with_sign_check(SignCheck::Error)
+            .with_face_normals(true);
+        assert_eq!(mdc.recommended_winding(), Winding::Keep);
+        let mesh = mdc.tessellate().unwrap();
+        assert_eq!(mesh.vertices.len(), expected.vertices.len());
+        assert_eq!(mesh.faces.len(), expected.faces.len());
+        assert_eq!(mesh.euler_characteristic(), expected.euler_characteristic());
+        // The faces point outwards. Allow for some non-planar quads.
+        let outward = mesh
+            .faces
+            .iter()
+            .filter(|face| {
+                let [a, b, c] = [0, 1, 2].map(|i| na::Vector3::from(mesh.vertices[face[i]]));
+                (b - a).cross(&(c - a)).dot(&a) > 0.
+            })
+            .count();
+        assert!(outward > mesh.faces.len() * 9 / 10);
+        // So do the face normals.
+        for (face, n) in mesh
+            .iter_face_vertices()
+            .zip(mesh.face_normals.as_ref().unwrap())
+        {
+            assert!(na::Vector3::from(*n).dot(&na::Vector3::from(face[0])) > 0.);
+        }
+        // The sampled grid points and the sign field agree on the inside.
+        for (index, inside) in mdc.occupied_cells() {
+            assert_eq!(inside, unit.value(&mdc.index_to_world(index)) < 0.);
+        }
+        let field = ManifoldDualContouring::new(&inverted, 0.2, 0.1)
+            .with_inside_sign(Sign::Positive)
+            .sample_sign_field()
+            .unwrap();
+        for (index, _) in field.iter() {
+            assert_eq!(
+                field.is_inside(index),
+                Some(unit.value(&field.position(index)) < 0.)
+            );
+        }
+        // A function, that is negative inside, is inverted now.
+        assert!(matches!(
+            ManifoldDualContouring::new(&sphere, 0.2, 0.1)
+                .with_inside_sign(Sign::Positive)
+                .with_sign_check(SignCheck::Error)
+                .tessellate(),
+            Err(DualContouringError::InvertedSign)
+        ));
+    }
+
+    #[test]
+    fn recommended_winding() {
+        let sphere = Sphere::unit();
+        assert_eq!(
+            ManifoldDualContouring::new(&sphere, 0.2, 0.1).recommended_winding(),
+            Winding::Keep
+        );
+        let unit = Sphere::unit();
+        let inverted = inverted(&unit);
+        let mut mdc = ManifoldDualContouring::new(&inverted, 0.2, 0.1);
+        assert_eq!(mdc.recommended_winding(), Winding::Reverse);
+        // The faces point inwards. Allow for some non-planar quads.
+        let mesh = mdc.tessellate().unwrap();
+        let inward = mesh
+            .faces
+            .iter()
+            .filter(|face| {
+                let [a, b, c] = [0, 1, 2].map(|i| na::Vector3::from(mesh.vertices[face[i]]));
+                (b - a).cross(&(c - a)).dot(&a) < 0.
+            })
+            .count();
+        assert!(inward > mesh.faces.len() * 9 / 10);
+        assert_eq!(
+            ManifoldDualContouring::new(&inverted, 0.2, 0.1)
+                .with_sign_check(SignCheck::Flip)
+                .recommended_winding(),
+            Winding::Keep
+        );
+        assert_eq!(
+            ManifoldDualContouring::new(&inverted, 0.2, 0.1)
+                .with_deferred_winding(true)
+                .recommended_winding(),
+            Winding::Keep
+        );
+    }
+
+    #[test]
+    fn min_cell_size() {
+        let sphere = Sphere::unit();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.05, 0.1);
+        mdc.tessellate().unwrap();
+        assert_eq!(mdc.num_clipped_cubes, 0);
+        let num_cells = mdc.value_grid.len();
+
+        let mut clipped = ManifoldDualContouring::new(&sphere, 0.05, 0.1).with_min_cell_size(0.2);
+        let mesh = clipped.tessellate().unwrap();
+        assert!(clipped.num_clipped_cubes > 0);
+        assert!(clipped.value_grid.len() < num_cells);
+        assert!(mesh
+            .faces
+            .iter()
+            .flatten()
+            .all(|&i| i < mesh.vertices.len()));
+    }
+
+    #[test]
+    fn occupied_cells() {
+        let sphere = Sphere::unit();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.1);
+        let mesh = mdc.tessellate().unwrap();
+        let compacted = mdc.occupied_cells().count();
+        assert_eq!(compacted, mdc.value_grid.len());
+
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.1).with_compact_value_grid(false);
+        assert_eq!(mdc.tessellate().unwrap().faces.len(), mesh.faces.len());
+        assert!(mdc.occupied_cells().count() > compacted);
+        // The center of the sphere is sampled and inside.
+        let center = mdc
+            .occupied_cells()
+            .map(|(index, inside)| (mdc.index_to_world(index).coords.norm(), inside))
+            .fold((f64::INFINITY, false), |a, b| if b.0 < a.0 { b } else { a });
+        assert!(center.1);
+    }
+
+    #[test]
+    fn index_to_world() {
+        let sphere = Sphere::unit();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.1);
+        assert_eq!(mdc.resolution(), 0.1);
+        assert_eq!(mdc.index_to_world([0, 0, 0]), mdc.origin());
+        assert!(
+            (mdc.index_to_world([1, 2, 3]) - mdc.origin() - na::Vector3::new(0.1, 0.2, 0.3)).norm()
+                < 1e-12
+        );
+        mdc.tessellate().unwrap();
+        for (index, inside) in mdc.occupied_cells() {
+            assert_eq!(sphere.value(&mdc.index_to_world(index)) < 0., inside);
+        }
+        // Crossings lie on their edges.
+        for (&edge_index, plane) in mdc.edge_grid.borrow().iter() {
+            let (a, b) = mdc.edge_index_to_world(edge_index);
+            assert!(((a - plane.p).norm() + (b - plane.p).norm() - mdc.res).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn face_labeler() {
+        let sphere = Sphere::unit();
+        let mesh = ManifoldDualContouring::new(&sphere, 0.2, 0.1)
+            .tessellate()
+            .unwrap();
+        assert_eq!(mesh.labels, None);
+
+        let mesh = ManifoldDualContouring::new(&sphere, 0.2, 0.1)
+            .with_face_labeler(|p| if p[0] < 0. { 0 } else { 1 })
+            .tessellate()
+            .unwrap();
+        let labels = mesh.labels.as_ref().unwrap();
+        assert_eq!(labels.len(), mesh.faces.len());
+        for (face, &label) in mesh.iter_face_vertices().zip(labels) {
+            let x = face.iter().map(|v| v[0]).sum::<f64>() / face.len() as f64;
+            assert_eq!(label, if x < 0. { 0 } else { 1 });
+        }
+        assert!(labels.contains(&0) && labels.contains(&1));
+    }
+
+    #[test]
+    fn debug_cell_mesh() {
+        let sphere = Sphere::unit();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.5);
+        assert!(mdc.debug_cell_mesh().unwrap().faces.is_empty());
+        let mesh = mdc.tessellate().unwrap();
+        let cells = mdc.debug_cell_mesh().unwrap();
+        let labels = cells.labels.as_ref().unwrap();
+        assert_eq!(cells.faces.len(), 6 * cells.vertices.len() / 8);
+        assert_eq!(labels.len(), cells.faces.len());
+        // Every vertex in the mesh has its own cell.
+        assert_eq!(cells.vertices.len(), 8 * mesh.vertices.len());
+        // With a large error some cells are merged.
+        assert!(labels.iter().any(|&layer| layer > 0));
+        assert!(labels
+            .iter()
+            .all(|&layer| (layer as usize) < mdc.vertex_octtree.len()));
+    }
 
     #[test]
-    fn connected_edges() {
-        let cell = BitSet::from_4bits(0, 6, 3, 5);
-        let edge_set = BitSet::from_4bits(4, 5, 10, 11);
-        let connected_edges = get_connected_edges_from_edge_set(edge_set, cell);
-        assert_eq!(connected_edges.len(), 2);
-        assert!(connected_edges.contains(&BitSet::from_4bits(5, 5, 6, 10)));
-        assert!(connected_edges.contains(&BitSet::from_4bits(3, 3, 4, 11)));
+    fn nan_guard() {
+        // Not defined for x > 0.5.
+        let unit = Sphere::unit();
+        let partial = Probe::new(&unit)
+            .with_value(|p, value| if p.x > 0.5 { f64::NAN } else { value })
+            .with_exact_distance(false);
+        match ManifoldDualContouring::new(&partial, 0.2, 0.1)
+            .with_nan_guard(true)
+            .tessellate()
+        {
+            Err(DualContouringError::NonFiniteValue { position }) => {
+                assert!(position[0] > 0.5);
+                assert!(position.iter().all(|x| x.is_finite()));
+            }
+            r => panic!(
+                "expected NonFiniteValue, got {:?}",
+                r.map(|m| m.faces.len())
+            ),
+        }
+        let sphere = Sphere::unit();
+        assert!(ManifoldDualContouring::new(&sphere, 0.2, 0.1)
+            .with_nan_guard(true)
+            .tessellate()
+            .is_ok());
+    }
+
+    #[test]
+    fn vertex_color() {
+        let sphere = Sphere::unit();
+        let mesh = ManifoldDualContouring::new(&sphere, 0.2, 0.1)
+            .tessellate()
+            .unwrap();
+        assert_eq!(mesh.vertex_colors, None);
+        let color = |p: [f64; 3]| [(p[0] * 0.5 + 0.5) as f32, 0., 1.];
+        let mesh = ManifoldDualContouring::new(&sphere, 0.2, 0.1)
+            .with_vertex_color(color)
+            .tessellate()
+            .unwrap();
+        let colors = mesh.vertex_colors.as_ref().unwrap();
+        assert_eq!(colors.len(), mesh.vertices.len());
+        for (&v, &c) in mesh.vertices.iter().zip(colors) {
+            assert_eq!(c, color(v));
+        }
+    }
+
+    #[test]
+    fn sample_attribute() {
+        let sphere = Sphere::unit();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.2, 0.1);
+        let mesh = mdc.tessellate().unwrap();
+        let attributes = mdc.sample_attribute(&mesh, |p| (p[0], [p[1] * 2., p[2]]));
+        assert_eq!(attributes.len(), mesh.vertices.len());
+        for (v, a) in mesh.vertices.iter().zip(attributes) {
+            assert_eq!(a, (v[0], [v[1] * 2., v[2]]));
+        }
+    }
+
+    #[test]
+    fn face_normals() {
+        let sphere = Sphere::unit();
+        let mesh = ManifoldDualContouring::new(&sphere, 0.2, 0.1)
+            .with_face_normals(true)
+            .tessellate()
+            .unwrap();
+        let face_normals = mesh.face_normals.as_ref().unwrap();
+        assert_eq!(face_normals.len(), mesh.faces.len());
+        for (face, n) in mesh.iter_face_vertices().zip(face_normals) {
+            let n = na::Vector3::from(*n);
+            assert!((n.norm() - 1.).abs() < 1e-9);
+            // Normals of the unit sphere point away from the center.
+            let c = face
+                .iter()
+                .fold(na::Vector3::zeros(), |c, v| c + na::Vector3::from(*v));
+            assert!(n.dot(&c.normalize()) > 0.9);
+        }
+
+        let unit = Sphere::unit();
+        let inverted = inverted(&unit);
+        let mesh = ManifoldDualContouring::new(&inverted, 0.2, 0.1)
+            .with_sign_check(SignCheck::Flip)
+            .with_face_normals(true)
+            .tessellate()
+            .unwrap();
+        for (face, n) in mesh
+            .iter_face_vertices()
+            .zip(mesh.face_normals.as_ref().unwrap())
+        {
+            assert!(na::Vector3::from(*n).dot(&na::Vector3::from(face[0])) > 0.);
+        }
+    }
+
+    #[test]
+    fn memoization() {
+        let evaluations = |epsilon: Option<f64>| {
+            // Returns zero on the 2000th evaluation, to force a retry.
+            let unit = Sphere::unit();
+            let count = std::cell::Cell::new(0);
+            let sphere = Probe::new(&unit).with_value(|_, value| {
+                count.set(count.get() + 1);
+                if count.get() == 2000 {
+                    0.
+                } else {
+                    value
+                }
+            });
+            let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.1).with_memoization(epsilon);
+            let origin = mdc.origin;
+            let mesh = mdc.tessellate().unwrap();
+            // Retried from a moved origin.
+            assert!(mdc.origin != origin);
+            assert!(mesh
+                .vertices
+                .iter()
+                .all(|v| (na::Vector3::from(*v).norm() - 1.).abs() < 0.1));
+            sphere.values.get()
+        };
+        // The retry reuses most of the evaluations before the zero.
+        let plain = evaluations(None);
+        let memoized = evaluations(Some(0.025));
+        assert!(memoized + 1500 < plain, "{} {}", memoized, plain);
+
+        // Without a retry nothing is reused, even for crossings closer than epsilon.
+        let sphere = Sphere::unit();
+        let expected = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .with_face_normals(true)
+            .tessellate()
+            .unwrap();
+        let mesh = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .with_face_normals(true)
+            .with_memoization(Some(0.05))
+            .tessellate()
+            .unwrap();
+        assert!(mesh.approx_eq(&expected, 1e-12));
+    }
+
+    #[test]
+    fn memo() {
+        let mut memo = Memo::new(0.1, 2);
+        memo.insert(na::Point3::new(0., 0., 0.), 1.);
+        // Only available after a retry.
+        assert_eq!(memo.get(&na::Point3::new(0.05, 0., 0.)), None);
+        memo.retry();
+        assert_eq!(memo.get(&na::Point3::new(0.05, 0., 0.)), Some(1.));
+        assert_eq!(memo.get(&na::Point3::new(0.15, 0., 0.)), None);
+        // Full after two results.
+        memo.insert(na::Point3::new(1., 0., 0.), 2.);
+        memo.insert(na::Point3::new(2., 0., 0.), 3.);
+        memo.retry();
+        assert_eq!(memo.get(&na::Point3::new(1., 0., 0.)), Some(2.));
+        assert_eq!(memo.get(&na::Point3::new(2., 0., 0.)), None);
+        memo.clear();
+        assert_eq!(memo.get(&na::Point3::new(0., 0., 0.)), None);
+    }
+
+    #[test]
+    fn surface_nets() {
+        let sphere = Sphere::unit();
+        let simplified = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        let mut mdc =
+            ManifoldDualContouring::new(&sphere, 0.1, 0.1).with_method(Method::SurfaceNets);
+        let mesh = mdc.tessellate().unwrap();
+        assert_eq!(mdc.vertex_octtree.len(), 1);
+        assert_eq!(mesh.vertices.len(), mdc.vertex_octtree[0].len());
+        assert!(mesh.faces.len() > simplified.faces.len());
+        assert!(mesh
+            .to_half_edge()
+            .half_edges
+            .iter()
+            .all(|e| e.twin.is_some()));
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|v| (na::Vector3::from(*v).norm() - 1.).abs() < 0.05));
+    }
+
+    #[test]
+    fn root_finder() {
+        let unit = Sphere::unit();
+        let sphere = Probe::new(&unit);
+        let res = 0.0001;
+        let tolerance = super::PRECISION as f64 * res;
+        // A long segment, where the linear interpolation converges slowly.
+        let a = na::Point3::new(0., 0.5, 0.);
+        let b = na::Point3::new(10., 0.5, 0.);
+        let (av, bv) = (sphere.value(&a), sphere.value(&b));
+        let mut evaluations = Vec::new();
+        for &root_finder in &[RootFinder::Linear, RootFinder::Secant, RootFinder::Brent] {
+            let mdc = ManifoldDualContouring::new(&sphere, res, 0.1).with_root_finder(root_finder);
+            sphere.values.set(0);
+            let plane = mdc.find_zero(a, av, b, bv).unwrap().unwrap();
+            assert!(
+                (plane.p.x - 0.75f64.sqrt()).abs() < 2. * tolerance,
+                "{:?} found {}",
+                root_finder,
+                plane.p
+            );
+            evaluations.push(sphere.values.get());
+        }
+        assert!(evaluations[1] < evaluations[0], "{:?}", evaluations);
+        assert!(evaluations[2] < evaluations[0], "{:?}", evaluations);
+
+        // All finders find the same crossings when tessellating.
+        let mut linear = ManifoldDualContouring::new(&sphere, 0.2, 0.1);
+        linear.tessellate().unwrap();
+        for &root_finder in &[RootFinder::Secant, RootFinder::Brent] {
+            let mut mdc =
+                ManifoldDualContouring::new(&sphere, 0.2, 0.1).with_root_finder(root_finder);
+            mdc.tessellate().unwrap();
+            let edge_grid = mdc.edge_grid.borrow();
+            assert_eq!(edge_grid.len(), linear.edge_grid.borrow().len());
+            for (edge_index, plane) in linear.edge_grid.borrow().iter() {
+                let distance = (edge_grid[edge_index].p - plane.p).norm();
+                assert!(distance < 2. * super::PRECISION as f64 * 0.2);
+            }
+        }
+    }
+
+    #[test]
+    fn uncompacted_value_grid() {
+        let sphere = Sphere::unit();
+        let compacted = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .sample_sign_field()
+            .unwrap();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.1).with_compact_value_grid(false);
+        let field = mdc.sample_sign_field().unwrap();
+        assert!(field.len() > compacted.len());
+        // Far inside the sphere only the uncompacted field knows the sign.
+        let center = field
+            .iter()
+            .map(|(index, _)| index)
+            .min_by(|a, b| {
+                let distance = |i: &[usize; 3]| field.position(*i).coords.norm();
+                distance(a).partial_cmp(&distance(b)).unwrap()
+            })
+            .unwrap();
+        assert!(field.position(center).coords.norm() < 0.5);
+        assert_eq!(field.is_inside(center), Some(true));
+        assert_eq!(compacted.is_inside(center), None);
+
+        let expected = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .with_sign_field(compacted)
+            .tessellate()
+            .unwrap();
+        let mesh = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .with_sign_field(field)
+            .tessellate()
+            .unwrap();
+        assert_eq!(mesh.faces.len(), expected.faces.len());
+        assert_eq!(mesh.vertices.len(), expected.vertices.len());
+    }
+
+    #[test]
+    fn slice() {
+        let sphere = Sphere::unit();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.05, 0.1);
+        assert!(mdc.slice(Axis::Z, 0.3).is_empty());
+        mdc.sample_sign_field().unwrap();
+        let z = 0.3;
+        let radius = (1f64 - z * z).sqrt();
+        let segments = mdc.slice(Axis::Z, z);
+        assert!(!segments.is_empty());
+        let mut length = 0.;
+        for segment in &segments {
+            for p in segment {
+                assert!((na::Vector2::new(p[0], p[1]).norm() - radius).abs() < 0.01);
+            }
+            length += (na::Vector2::from(segment[0]) - na::Vector2::from(segment[1])).norm();
+        }
+        let circumference = 2. * std::f64::consts::PI * radius;
+        assert!((length - circumference).abs() < 0.05 * circumference);
+        // Slicing along another axis gives the same circle in (x, z).
+        let segments = mdc.slice(Axis::Y, z);
+        for p in segments.iter().flatten() {
+            assert!((na::Vector2::new(p[0], p[1]).norm() - radius).abs() < 0.01);
+        }
+        assert!(mdc.slice(Axis::X, 2.).is_empty());
+    }
+
+    #[test]
+    fn quiet() {
+        let mut timer = super::Timer::new(false);
+        assert_eq!(timer.elapsed(), std::time::Duration::default());
+        let sphere = Sphere::unit();
+        let expected = ManifoldDualContouring::new(&sphere, 0.2, 0.1)
+            .tessellate()
+            .unwrap();
+        let mesh = ManifoldDualContouring::new(&sphere, 0.2, 0.1)
+            .with_quiet(true)
+            .tessellate()
+            .unwrap();
+        assert_eq!(mesh.faces.len(), expected.faces.len());
+    }
+
+    #[test]
+    fn tessellate_both() {
+        let sphere = Sphere::unit();
+        let (mesh, triangle_mesh) = ManifoldDualContouring::new(&sphere, 0.2, 0.1)
+            .tessellate_both()
+            .unwrap();
+        assert_eq!(triangle_mesh, mesh.to_triangle_mesh());
+        // The triangles follow the faces, when they are reversed afterwards.
+        let unit = Sphere::unit();
+        let inverted = inverted(&unit);
+        for mut mdc in [
+            ManifoldDualContouring::new(&inverted, 0.2, 0.1).with_sign_check(SignCheck::Flip),
+            ManifoldDualContouring::new(&sphere, 0.2, 0.1).with_deferred_winding(true),
+            ManifoldDualContouring::new(&sphere, 0.2, 0.1)
+                .with_coordinate_system(CoordinateSystem::LeftHandedYUp),
+        ] {
+            let (mesh, triangle_mesh) = mdc.tessellate_both().unwrap();
+            assert_eq!(triangle_mesh, mesh.to_triangle_mesh());
+        }
+    }
+
+    // Meshes from different runs differ in the order of faces and vertices, since the order of
+    // iteration over the HashMaps differs.
+    fn assert_same_mesh(a: &crate::Mesh<f64>, b: &crate::Mesh<f64>) {
+        assert_eq!(a.faces.len(), b.faces.len());
+        assert_eq!(a.vertices.len(), b.vertices.len());
+        // The vertices also differ in the last bits, so compare them rounded.
+        let sorted = |mesh: &crate::Mesh<f64>| {
+            let mut vertices: Vec<_> = mesh
+                .vertices
+                .iter()
+                .map(|v| [0, 1, 2].map(|i| (v[i] * 1e6).round() as i64))
+                .collect();
+            vertices.sort_unstable();
+            vertices
+        };
+        assert_eq!(sorted(a), sorted(b));
+    }
+
+    #[test]
+    fn tessellate_from_state() {
+        let sphere = Sphere::unit();
+        let expected = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.1);
+        mdc.build_octree().unwrap();
+        let mesh = mdc.tessellate_from_state().unwrap();
+        assert_same_mesh(&mesh, &expected);
+        // The state can be tessellated repeatedly.
+        assert_eq!(mdc.tessellate_from_state().unwrap(), mesh);
+        // Without octtree the mesh is empty.
+        let mesh = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .tessellate_from_state()
+            .unwrap();
+        assert!(mesh.faces.is_empty());
+    }
+
+    #[test]
+    fn tessellate_streaming() {
+        let sphere = Sphere::unit();
+        let expected = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        // Rebuild a mesh from the streamed faces, merging equal positions.
+        let mut indices = std::collections::HashMap::new();
+        let mut mesh = crate::Mesh {
+            vertices: Vec::new(),
+            faces: Vec::new(),
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        let num_faces = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .tessellate_streaming(|face, i| {
+                assert_eq!(i, mesh.faces.len());
+                let face = face
+                    .iter()
+                    .map(|v| {
+                        let key = [0, 1, 2].map(|i| (v[i] * 1e9).round() as i64);
+                        *indices.entry(key).or_insert_with(|| {
+                            mesh.vertices.push(*v);
+                            mesh.vertices.len() - 1
+                        })
+                    })
+                    .collect();
+                mesh.faces.push(face);
+            })
+            .unwrap();
+        assert_eq!(num_faces, mesh.faces.len());
+        assert_same_mesh(&mesh, &expected);
+        assert!(mesh
+            .to_half_edge()
+            .half_edges
+            .iter()
+            .all(|e| e.twin.is_some()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn save_and_load_state() {
+        let unit = Sphere::unit();
+        let sphere = Probe::new(&unit);
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.1);
+        mdc.build_octree().unwrap();
+        let mut state = Vec::new();
+        mdc.save_state(&mut state).unwrap();
+        let expected = mdc.tessellate_from_state().unwrap();
+
+        sphere.values.set(0);
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.2, 0.1);
+        mdc.load_state(state.as_slice()).unwrap();
+        let mesh = mdc.tessellate_from_state().unwrap();
+        assert_eq!(sphere.values.get(), 0);
+        assert_same_mesh(&mesh, &expected);
+        assert!(mdc.load_state(&state[..state.len() / 2]).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn octtree_debug_json() {
+        let sphere = Sphere::unit();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.1);
+        assert_eq!(mdc.octtree_debug_json(), "[]");
+        mdc.tessellate().unwrap();
+        let json: serde_json::Value = serde_json::from_str(&mdc.octtree_debug_json()).unwrap();
+        let layers = json.as_array().unwrap();
+        assert_eq!(layers.len(), mdc.vertex_octtree.len());
+        for (layer, vertices) in layers.iter().zip(&mdc.vertex_octtree) {
+            let layer = layer.as_array().unwrap();
+            assert_eq!(layer.len(), vertices.len());
+            for (json, vertex) in layer.iter().zip(vertices) {
+                assert_eq!(json["index"], serde_json::json!(vertex.index));
+                assert_eq!(json["parent"], serde_json::json!(vertex.parent));
+                assert_eq!(json["children"], serde_json::json!(vertex.children));
+                assert_eq!(json["euler_characteristic"], vertex.euler_characteristic);
+                assert!(json["qef_error"].is_number() || json["qef_error"].is_null());
+            }
+        }
+        assert!(layers
+            .iter()
+            .flat_map(|layer| layer.as_array().unwrap())
+            .any(|vertex| vertex["qef_error"].is_number()));
+    }
+
+    #[test]
+    fn tessellate_lods() {
+        let sphere = Sphere::unit();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.05, 0.1);
+        let lods = mdc.tessellate_lods(&[0.5, 0.1, 0.001]).unwrap();
+        assert_eq!(lods.len(), 3);
+        assert!(lods[0].faces.len() < lods[1].faces.len());
+        assert!(lods[1].faces.len() < lods[2].faces.len());
+        let expected = ManifoldDualContouring::new(&sphere, 0.05, 0.001)
+            .tessellate()
+            .unwrap();
+        assert_same_mesh(&lods[2], &expected);
+        // relative_error is restored.
+        assert_same_mesh(&mdc.tessellate_from_state().unwrap(), &lods[1]);
+    }
+
+    #[test]
+    fn verify_manifold_invariants() {
+        let sphere = Sphere::unit();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.1);
+        assert_eq!(mdc.verify_manifold_invariants(), Ok(()));
+        mdc.tessellate().unwrap();
+        assert_eq!(mdc.verify_manifold_invariants(), Ok(()));
+        // Break the surface in one cell and the neighborhood of the cells next to another one.
+        mdc.vertex_octtree[0][3].edge_intersections[0] += 1;
+        let neighbor = mdc.vertex_octtree[0]
+            .iter()
+            .find_map(|vertex| vertex.neighbors[0].first())
+            .cloned()
+            .unwrap();
+        let neighbor = match neighbor {
+            VarIndex::Index(i) => i,
+            VarIndex::VertexIndex(_) => unreachable!(),
+        };
+        mdc.vertex_octtree[0][neighbor].neighbors[1].clear();
+        let mut expected: Vec<_> = mdc.vertex_octtree[0]
+            .iter()
+            .filter(|vertex| vertex.neighbors[0].contains(&VarIndex::Index(neighbor)))
+            .map(|vertex| vertex.index)
+            .collect();
+        expected.push(mdc.vertex_octtree[0][3].index);
+        expected.sort_unstable();
+        expected.dedup();
+        assert_eq!(mdc.verify_manifold_invariants(), Err(expected));
+    }
+
+    #[test]
+    fn batch_values() {
+        let unit = Sphere::unit();
+        let sphere = Probe::new(&unit).with_exact_distance(false);
+        let mesh = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        // One batch per level of the sampling octtree.
+        assert!(sphere.batches.get() <= 6);
+        assert!(sphere.batch_points.get() > 10000);
+        let plain = Probe::new(&unit).with_exact_distance(false);
+        let expected = ManifoldDualContouring::new(&plain, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        assert_same_mesh(&mesh, &expected);
+    }
+
+    #[test]
+    fn bbox_dilation() {
+        let sphere = Sphere::unit();
+        let default = ManifoldDualContouring::new(&sphere, 0.1, 0.1);
+        let same = ManifoldDualContouring::new(&sphere, 0.1, 0.1).with_bbox_dilation(1.11);
+        assert_eq!(same.dim, default.dim);
+        assert!((same.origin - default.origin).norm() < 1e-6);
+        let mut tight = ManifoldDualContouring::new(&sphere, 0.1, 0.1).with_bbox_dilation(0.2);
+        assert_eq!(tight.dim, [24, 24, 24]);
+        assert!((tight.origin - na::Point3::new(-1.2, -1.2, -1.2)).norm() < 1e-12);
+        let mesh = tight.tessellate().unwrap();
+        assert!(!mesh.faces.is_empty());
+        assert!(mesh
+            .to_half_edge()
+            .half_edges
+            .iter()
+            .all(|e| e.twin.is_some()));
+    }
+
+    #[test]
+    fn edge_crossings_as_lines() {
+        let sphere = Sphere::unit();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.1);
+        assert!(mdc.edge_crossings_as_lines().is_empty());
+        mdc.build_octree().unwrap();
+        let lines = mdc.edge_crossings_as_lines();
+        assert!(!lines.is_empty());
+        // Each crossing is on the surface and shared by the four faces around its edge.
+        let mut uses = std::collections::HashMap::new();
+        for (a, b) in &lines {
+            for p in &[a, b] {
+                assert!(sphere.value(&na::Point3::from(**p)).abs() < 0.01);
+                *uses
+                    .entry([p[0].to_bits(), p[1].to_bits(), p[2].to_bits()])
+                    .or_insert(0) += 1;
+            }
+        }
+        // Crossings close to grid points may coincide.
+        assert_eq!(lines.len(), mdc.edge_grid.borrow().len() * 2);
+        assert!(uses.values().all(|&n| n % 4 == 0));
+    }
+
+    #[test]
+    fn no_surface_in_bounds() {
+        let sphere = Sphere::unit();
+        // The bbox is completely inside the sphere.
+        let bbox = BoundingBox::new(
+            &na::Point3::new(-0.2, -0.2, -0.2),
+            &na::Point3::new(0.2, 0.2, 0.2),
+        );
+        let result = ManifoldDualContouring::new_with_bbox(&sphere, bbox, 0.02, 0.1)
+            .unwrap()
+            .with_bbox_dilation(0.05)
+            .tessellate();
+        assert!(matches!(
+            result,
+            Err(DualContouringError::NoSurfaceInBounds)
+        ));
+        let bbox = BoundingBox::new(&na::Point3::new(2., 2., 2.), &na::Point3::new(3., 3., 3.));
+        let result = ManifoldDualContouring::new_with_bbox(&sphere, bbox, 0.1, 0.1)
+            .unwrap()
+            .with_compact_value_grid(false)
+            .tessellate();
+        assert!(matches!(
+            result,
+            Err(DualContouringError::NoSurfaceInBounds)
+        ));
+    }
+
+    #[test]
+    fn quads() {
+        let sphere = Sphere::unit();
+        let mesh = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        // Faces are quads, unless two cells collapsed into the same vertex.
+        assert!(mesh.faces.iter().all(|f| f.len() == 3 || f.len() == 4));
+        let num_quads = mesh.faces.iter().filter(|f| f.len() == 4).count();
+        assert!(num_quads > mesh.faces.len() / 2);
+        // Only the triangulation splits them.
+        assert_eq!(
+            mesh.to_triangle_mesh().faces.len(),
+            mesh.faces.len() + num_quads
+        );
+    }
+
+    #[test]
+    fn is_exact_distance() {
+        let unit = Sphere::unit();
+        let steep = overestimating(&unit, false);
+        let mesh = ManifoldDualContouring::new(&steep, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        assert!(!mesh.faces.is_empty());
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|v| (na::Vector3::from(*v).norm() - 1.).abs() < 0.1));
+        // Claiming to be exact skips the cubes containing the surface.
+        let steep = overestimating(&unit, true);
+        assert!(matches!(
+            ManifoldDualContouring::new(&steep, 0.1, 0.1).tessellate(),
+            Err(DualContouringError::NoSurfaceInBounds)
+        ));
+    }
+
+    #[test]
+    fn descent_margin() {
+        let unit = Sphere::unit();
+        let sphere = Probe::new(&unit);
+        let expected = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        let default_evaluations = sphere.values.replace(0);
+        let mesh = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .with_descent_margin(2.)
+            .tessellate()
+            .unwrap();
+        assert!(sphere.values.get() > default_evaluations);
+        assert!(mesh.approx_eq(&expected, 1e-9));
+        // A margin covering the overestimation finds the surface, see is_exact_distance().
+        let steep = overestimating(&unit, true);
+        let mesh = ManifoldDualContouring::new(&steep, 0.1, 0.1)
+            .with_descent_margin(20.)
+            .tessellate()
+            .unwrap();
+        assert!(!mesh.faces.is_empty());
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|v| (na::Vector3::from(*v).norm() - 1.).abs() < 0.1));
+    }
+
+    #[test]
+    fn descent_margin_threshold() {
+        // An exact distance of 1 everywhere, so the center alone decides on skipping the cube.
+        let constant = SplitFunction::new(
+            BoundingBox::new(
+                &na::Point3::new(-1., -1., -1.),
+                &na::Point3::new(1., 1., 1.),
+            ),
+            |_| 1.,
+            |_| na::Vector3::z(),
+        )
+        .with_exact_distance(true);
+        let res = 0.1;
+        let dim = ManifoldDualContouring::new(&constant, res, 0.1).dim;
+        let size = super::pow2roundup(*dim.iter().max().unwrap()).unwrap();
+        // The diagonal of a sub-cube of the whole grid grown by res on each side.
+        let center_distance = (size as f64 / 2. * res + 2. * res) * 3f64.sqrt();
+        for (margin, skipped) in [
+            (1. / center_distance * (1. - 1e-6), true),
+            (1. / center_distance * (1. + 1e-6), false),
+        ] {
+            let probe = Probe::new(&constant);
+            let mut mdc = ManifoldDualContouring::new(&probe, res, 0.1).with_descent_margin(margin);
+            let origin = mdc.origin;
+            assert!(mdc.sample_value_grid([0, 0, 0], origin, size, 1.).is_none());
+            // Only the center is evaluated and the first corner stored for a skipped cube.
+            assert_eq!(probe.values.get() == 1, skipped, "margin {}", margin);
+            assert_eq!(mdc.value_grid.len() == 1, skipped, "margin {}", margin);
+        }
+    }
+
+    #[cfg(feature = "shapes")]
+    #[test]
+    fn manifold_only_merges() {
+        let torus = crate::Torus::new(0.6, 0.25);
+        let non_manifold_parents = |mdc: &ManifoldDualContouring<f64>| {
+            mdc.vertex_octtree[1..]
+                .iter()
+                .flatten()
+                .filter(|v| !v.is_2manifold() && !v.is_unmergeable())
+                .count()
+        };
+        let mut default = ManifoldDualContouring::new(&torus, 0.05, 0.1);
+        default.build_octree().unwrap();
+        assert!(non_manifold_parents(&default) > 0);
+        let mut mdc =
+            ManifoldDualContouring::new(&torus, 0.05, 0.1).with_manifold_only_merges(true);
+        mdc.build_octree().unwrap();
+        assert_eq!(non_manifold_parents(&mdc), 0);
+        assert_eq!(mdc.verify_manifold_invariants(), Ok(()));
+        let mesh = mdc.tessellate().unwrap();
+        // The torus is closed and has genus 1, i.e. Euler characteristic 0.
+        let half_edge = mesh.to_half_edge();
+        assert!(half_edge.half_edges.iter().all(|e| e.twin.is_some()));
+        let num_vertices = half_edge
+            .vertex_half_edges
+            .iter()
+            .filter(|e| e.is_some())
+            .count() as i64;
+        let num_edges = half_edge.half_edges.len() as i64 / 2;
+        assert_eq!(num_vertices - num_edges + mesh.faces.len() as i64, 0);
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|v| torus.value(&na::Point3::from(*v)).abs() < 0.1));
+    }
+
+    #[cfg(feature = "shapes")]
+    #[test]
+    fn qef_regularization() {
+        let cube = crate::BoxSDF::new(na::Vector3::new(0.47, 0.47, 0.47));
+        // The largest distance between a corner of the cube and its closest vertex.
+        let corner_distance = |regularization: f64| {
+            let mesh = ManifoldDualContouring::new(&cube, 0.1, 0.1)
+                .with_qef_regularization(regularization)
+                .tessellate()
+                .unwrap();
+            (0..8)
+                .map(|corner| {
+                    let c = na::Vector3::new(
+                        if corner & 1 == 0 { -0.47 } else { 0.47 },
+                        if corner & 2 == 0 { -0.47 } else { 0.47 },
+                        if corner & 4 == 0 { -0.47 } else { 0.47 },
+                    );
+                    mesh.vertices
+                        .iter()
+                        .map(|v| (na::Vector3::from(*v) - c).norm())
+                        .fold(f64::INFINITY, f64::min)
+                })
+                .fold(0., f64::max)
+        };
+        let sharp = corner_distance(0.);
+        let round = corner_distance(1.);
+        assert!(sharp < 0.01, "{}", sharp);
+        assert!(round > 2. * sharp && round > 0.02, "{} {}", round, sharp);
+    }
+
+    #[test]
+    fn timeout() {
+        let sphere = Sphere::unit();
+        assert!(matches!(
+            ManifoldDualContouring::new(&sphere, 0.02, 0.1)
+                .with_timeout(std::time::Duration::from_nanos(1))
+                .tessellate(),
+            Err(DualContouringError::Timeout)
+        ));
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .with_timeout(std::time::Duration::from_secs(3600));
+        assert!(!mdc.tessellate().unwrap().faces.is_empty());
+    }
+
+    #[test]
+    fn isovalue() {
+        let sphere = Sphere::unit();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.05, 0.1).with_isovalue(-0.5);
+        let mesh = mdc.tessellate().unwrap();
+        assert!(!mesh.faces.is_empty());
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|v| (na::Vector3::from(*v).norm() - 0.5).abs() < 0.01));
+        assert!(mesh
+            .to_half_edge()
+            .half_edges
+            .iter()
+            .all(|e| e.twin.is_some()));
+        let segments = mdc.slice(Axis::Z, 0.);
+        assert!(!segments.is_empty());
+        assert!(segments
+            .iter()
+            .flatten()
+            .all(|p| (na::Vector2::new(p[0], p[1]).norm() - 0.5).abs() < 0.01));
+    }
+
+    #[test]
+    fn skip_empty_cubes() {
+        let small = Sphere::new(na::Point3::origin(), 0.5).with_bbox(BoundingBox::new(
+            &na::Point3::new(-3., -3., -3.),
+            &na::Point3::new(3., 3., 3.),
+        ));
+        let (exact, inexact) = (
+            Probe::new(&small),
+            Probe::new(&small).with_exact_distance(false),
+        );
+        let mesh = ManifoldDualContouring::new(&exact, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        let expected = ManifoldDualContouring::new(&inexact, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        assert_same_mesh(&mesh, &expected);
+        assert!(mesh
+            .to_half_edge()
+            .half_edges
+            .iter()
+            .all(|e| e.twin.is_some()));
+        assert!(exact.values.get() * 10 < inexact.values.get());
+    }
+
+    #[test]
+    fn thin_feature_detection() {
+        // Claims to be exact, so without checking the centers the surface is missed, see
+        // is_exact_distance.
+        let unit = Sphere::unit();
+        let steep = overestimating(&unit, true);
+        let mesh = ManifoldDualContouring::new(&steep, 0.1, 0.1)
+            .with_thin_feature_detection(true)
+            .tessellate()
+            .unwrap();
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|v| (na::Vector3::from(*v).norm() - 1.).abs() < 0.1));
+        // The whole surface is found, not just the part inside the refined cubes.
+        assert_eq!(mesh.split_connected_components().len(), 1);
+        assert!(mesh
+            .to_half_edge()
+            .half_edges
+            .iter()
+            .all(|e| e.twin.is_some()));
+    }
+
+    #[test]
+    fn reset_for() {
+        let sphere = Sphere::unit();
+        let offset = crate::Offset::new(Sphere::unit(), 0.5);
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.1).with_face_normals(true);
+        mdc.tessellate().unwrap();
+        mdc.reset_for(&offset);
+        let mesh = mdc.tessellate().unwrap();
+        let expected = ManifoldDualContouring::new(&offset, 0.1, 0.1)
+            .with_face_normals(true)
+            .tessellate()
+            .unwrap();
+        assert_same_mesh(&mesh, &expected);
+        assert_eq!(
+            mesh.face_normals.unwrap().len(),
+            expected.face_normals.unwrap().len()
+        );
+    }
+
+    #[test]
+    fn invariant_checks() {
+        let sphere = Sphere::unit();
+        let expected = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        let mesh = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .with_invariant_checks(true)
+            .tessellate()
+            .unwrap();
+        assert_same_mesh(&mesh, &expected);
+
+        // Edges inside the parent are shared by four of its cells, not crossed by a single one.
+        let vertex = Vertex::<f64> {
+            index: [0, 0, 0],
+            qef: qef::Qef::new(&[], BoundingBox::neg_infinity()),
+            neighbors: Default::default(),
+            parent: None,
+            children: Vec::new(),
+            edge_intersections: [1; 12],
+            euler_characteristic: 1,
+        };
+        let children = [0].iter().cloned().collect();
+        match subsample_euler_characteristics(&children, std::slice::from_ref(&vertex), true) {
+            Err(DualContouringError::InternalInvariantViolated(message)) => {
+                assert!(message.contains("not divisible by 4"))
+            }
+            r => panic!("unexpected {:?}", r),
+        }
+
+        // The second vertex does not list the first one as its neighbor.
+        let mut vertices = vec![vertex.clone(), vertex];
+        vertices[1].index = [1, 0, 0];
+        vertices[0].neighbors[1].push(VarIndex::Index(1));
+        assert!(matches!(
+            check_neighbor_symmetry(&vertices, true),
+            Err(DualContouringError::AsymmetricNeighbors {
+                vertex: [0, 0, 0],
+                neighbor: [1, 0, 0],
+            })
+        ));
+        vertices[1].neighbors[0].push(VarIndex::Index(0));
+        assert!(check_neighbor_symmetry(&vertices, true).is_ok());
+    }
+
+    #[test]
+    fn tessellate_into() {
+        let sphere = Sphere::unit();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.1);
+        let mut mesh = crate::Mesh {
+            vertices: vec![[1., 2., 3.]],
+            faces: Vec::new(),
+            labels: Some(vec![7]),
+            face_normals: None,
+            vertex_colors: None,
+        };
+        mdc.tessellate_into(&mut mesh).unwrap();
+        let expected = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        assert_same_mesh(&mesh, &expected);
+        assert_eq!(mesh.labels, None);
+
+        // The next frame reuses the allocations.
+        let (vertices, faces) = (mesh.vertices.as_ptr(), mesh.faces.as_ptr());
+        mdc.reset_for(&sphere);
+        mdc.tessellate_into(&mut mesh).unwrap();
+        assert_same_mesh(&mesh, &expected);
+        assert_eq!(mesh.vertices.as_ptr(), vertices);
+        assert_eq!(mesh.faces.as_ptr(), faces);
+
+        let mut disjoint = ManifoldDualContouring::new(&sphere, 0.1, 0.1).with_region(
+            BoundingBox::new(&na::Point3::new(2., 2., 2.), &na::Point3::new(3., 3., 3.)),
+        );
+        mesh.labels = Some(vec![7]);
+        mesh.face_normals = Some(vec![[0., 0., 1.]]);
+        mesh.vertex_colors = Some(vec![[1., 0., 0.]]);
+        assert!(disjoint.tessellate_into(&mut mesh).is_err());
+        assert!(mesh.vertices.is_empty() && mesh.faces.is_empty());
+        assert_eq!(mesh.labels, None);
+        assert_eq!(mesh.face_normals, None);
+        assert_eq!(mesh.vertex_colors, None);
+    }
+
+    #[test]
+    fn qef_fallbacks() {
+        let sphere = Sphere::unit();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.1);
+        mdc.tessellate().unwrap();
+        assert_eq!(mdc.stats().num_qef_fallbacks, 0);
+
+        // Without normals in the upper half.
+        let unit = Sphere::unit();
+        let sphere = Probe::new(&unit).with_normal(|p, normal| {
+            if p.z > 0. {
+                na::Vector3::new(f64::NAN, f64::NAN, f64::NAN)
+            } else {
+                normal
+            }
+        });
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.1);
+        let mesh = mdc.tessellate().unwrap();
+        assert!(mdc.stats().num_qef_fallbacks > 0);
+        assert!(mesh.vertices.iter().flatten().all(|c| c.is_finite()));
+    }
+
+    #[test]
+    fn normal_grid() {
+        let unit = Sphere::unit();
+        let sphere = Probe::new(&unit);
+        // Covers the surface, but not the upper cap.
+        let mut grid = crate::NormalGrid::new(na::Point3::new(-1.5, -1.5, -1.5), 0.05);
+        for z in 0..50 {
+            for y in 0..61 {
+                for x in 0..61 {
+                    let p = grid.position([x, y, z]);
+                    grid.insert([x, y, z], unit.normal(&p));
+                }
+            }
+        }
+        let mesh = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .with_normal_grid(grid)
+            .tessellate()
+            .unwrap();
+        let with_grid = sphere.normals.get();
+        sphere.normals.set(0);
+        ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        assert!(with_grid > 0 && with_grid < sphere.normals.get() / 4);
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|v| (na::Vector3::from(*v).norm() - 1.).abs() < 0.05));
+        assert!(mesh
+            .to_half_edge()
+            .half_edges
+            .iter()
+            .all(|e| e.twin.is_some()));
+    }
+
+    #[test]
+    fn deferred_winding() {
+        let sphere = Sphere::unit();
+        let expected = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        let mesh = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .with_deferred_winding(true)
+            .tessellate()
+            .unwrap();
+        assert!(mesh.approx_eq(&expected, 1e-6));
+        // Positive inside. The faces still face out of the sphere, like with SignCheck::Flip.
+        let unit = Sphere::unit();
+        let inverted = inverted(&unit);
+        let mesh = ManifoldDualContouring::new(&inverted, 0.1, 0.1)
+            .with_sign_check(SignCheck::Flip)
+            .with_deferred_winding(true)
+            .tessellate()
+            .unwrap();
+        assert!(mesh.approx_eq(&expected, 1e-6));
+    }
+
+    #[test]
+    fn f32_sphere() {
+        let sphere = Sphere::new(na::Point3::origin(), 1f32);
+        let mesh = ManifoldDualContouring::<f32>::new(&sphere, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        assert!(!mesh.faces.is_empty());
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|v| (na::Vector3::from(*v).norm() - 1.).abs() < 0.05));
+        assert!(mesh
+            .to_half_edge()
+            .half_edges
+            .iter()
+            .all(|e| e.twin.is_some()));
+    }
+
+    #[test]
+    fn surface_reaching_grid_boundary() {
+        // The plane z = 0.01 crosses all sides of the grid.
+        let plane = SplitFunction::new(
+            BoundingBox::new(
+                &na::Point3::new(-1., -1., -1.),
+                &na::Point3::new(1., 1., 1.),
+            ),
+            |p: &na::Point3<f64>| p.z - 0.01,
+            |_| na::Vector3::z(),
+        );
+        let mut mdc = ManifoldDualContouring::new(&plane, 0.1, 0.1);
+        let mesh = mdc.tessellate().unwrap();
+        assert!(mdc.stats().num_boundary_edges > 0);
+        assert!(mdc.verify_manifold_invariants().is_ok());
+        assert!(mesh.vertices.iter().all(|v| (v[2] - 0.01).abs() < 0.01));
+        // The mesh covers the bbox and is only open at the boundary of the grid.
+        let (min, max) = mesh
+            .vertices
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+                (min.min(v[0]), max.max(v[0]))
+            });
+        assert!(min < -1. && max > 1.);
+        let half_edge = mesh.to_half_edge();
+        assert!(half_edge
+            .half_edges
+            .iter()
+            .filter(|e| e.twin.is_none())
+            .all(|e| {
+                let v = mesh.vertices[e.vertex];
+                v[0] < -1. || v[0] > 1. || v[1] < -1. || v[1] > 1.
+            }));
+
+        let sphere = Sphere::unit();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.1);
+        mdc.tessellate().unwrap();
+        assert_eq!(mdc.stats().num_boundary_edges, 0);
+    }
+
+    #[test]
+    fn corrupt_octtree() {
+        let sphere = Sphere::unit();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.2, 0.1);
+        mdc.build_octree().unwrap();
+        mdc.vertex_octtree[0][0].parent = None;
+        assert!(matches!(
+            mdc.tessellate_from_state(),
+            Err(DualContouringError::InternalState(message)) if message.contains("parent")
+        ));
+        assert!(matches!(
+            mdc.debug_cell_mesh(),
+            Err(DualContouringError::InternalState(message)) if message.contains("parent")
+        ));
+        mdc.vertex_index_map.clear();
+        assert!(matches!(
+            mdc.tessellate_from_state(),
+            Err(DualContouringError::InternalState(message)) if message.contains("no vertex")
+        ));
+    }
+
+    #[test]
+    fn degenerate_quads() {
+        let sphere = Sphere::unit();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.);
+        mdc.tessellate().unwrap();
+        assert_eq!(mdc.stats().num_degenerate_quads, 0);
+        // Simplification merges vertices, which collapses the quads between them without opening
+        // the mesh.
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.5);
+        let mesh = mdc.tessellate().unwrap();
+        assert!(mdc.stats().num_degenerate_quads > 0);
+        assert!(mesh.is_closed_surface());
+    }
+
+    #[test]
+    fn thread_pool() {
+        let sphere = Sphere::unit();
+        let expected = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        let pool = std::sync::Arc::new(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(1)
+                .build()
+                .unwrap(),
+        );
+        for _ in 0..2 {
+            let mesh = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+                .with_thread_pool(pool.clone())
+                .tessellate()
+                .unwrap();
+            assert!(mesh.approx_eq(&expected, 1e-9));
+        }
+    }
+
+    #[test]
+    fn batch() {
+        let sphere = Sphere::unit();
+        let results = tessellate_batch(&[
+            (&sphere, 0.1, 0.1),
+            (&sphere, 1e-7, 0.1),
+            (&sphere, 0.2, 0.1),
+        ]);
+        assert_eq!(results.len(), 3);
+        for (i, res) in [(0, 0.1), (2, 0.2)] {
+            let expected = ManifoldDualContouring::new(&sphere, res, 0.1)
+                .tessellate()
+                .unwrap();
+            assert!(results[i].as_ref().unwrap().approx_eq(&expected, 1e-9));
+        }
+        assert!(matches!(
+            results[1],
+            Err(DualContouringError::ResolutionTooFine { .. })
+        ));
+        assert!(tessellate_batch::<f64>(&[]).is_empty());
+    }
+
+    #[test]
+    fn estimated_memory_bytes() {
+        let sphere = Sphere::unit();
+        let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.1);
+        assert_eq!(mdc.estimated_memory_bytes(), 0);
+        mdc.build_octree().unwrap();
+        let bytes = mdc.estimated_memory_bytes();
+        assert!(
+            bytes
+                > mdc.value_grid.len() * std::mem::size_of::<([usize; 3], f64)>()
+                    + mdc.vertex_octtree[0].len() * std::mem::size_of::<super::Vertex<f64>>()
+        );
+        // Finer sampling needs more memory.
+        let mut fine = ManifoldDualContouring::new(&sphere, 0.05, 0.1);
+        fine.build_octree().unwrap();
+        assert!(fine.estimated_memory_bytes() > 2 * bytes);
+    }
+
+    #[test]
+    fn error_field() {
+        let sphere = Sphere::unit();
+        let count = |mesh: &crate::Mesh<f64>| {
+            let positive = mesh.vertices.iter().filter(|v| v[0] > 0.).count();
+            (positive, mesh.vertices.len() - positive)
+        };
+        let fine = ManifoldDualContouring::new(&sphere, 0.05, 0.001)
+            .tessellate()
+            .unwrap();
+        let coarse = ManifoldDualContouring::new(&sphere, 0.05, 1.)
+            .tessellate()
+            .unwrap();
+        // Fine for x > 0, coarse elsewhere.
+        let graded = ManifoldDualContouring::new(&sphere, 0.05, 0.1)
+            .with_error_field(|p| if p[0] > 0. { 0.001 } else { 1. })
+            .tessellate()
+            .unwrap();
+        let (fine, coarse, graded_counts) = (count(&fine), count(&coarse), count(&graded));
+        assert!(graded_counts.0 > fine.0 * 9 / 10);
+        assert!(graded_counts.1 < fine.1 / 2);
+        assert!(graded_counts.1 > coarse.1);
     }
 }
@@ -1,14 +1,221 @@
-use alga::general::RealField;
+use crate::{
+    bvh::{closest_point_on_triangle, Bvh},
+    half_edge::HalfEdgeMesh,
+    plane::Plane,
+    Axis, ImplicitFunction, RealField,
+};
+use bbox::BoundingBox;
 use nalgebra as na;
-#[cfg(any(feature = "obj", feature = "polyhedron-ops"))]
+#[cfg(any(
+    feature = "obj",
+    feature = "ply",
+    feature = "stl",
+    feature = "polyhedron-ops",
+    feature = "gpu"
+))]
 use num_traits::AsPrimitive;
+use num_traits::Float;
 #[cfg(feature = "polyhedron-ops")]
 use polyhedron_ops as p_ops;
 use rayon::prelude::*;
 use smallvec::SmallVec;
-use std::fmt::Debug;
 #[cfg(feature = "obj")]
-use std::{error::Error, fs::File, io::Write, path::Path};
+use std::collections::BTreeMap;
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    error,
+    fmt::{self, Debug},
+    num::TryFromIntError,
+};
+#[cfg(any(feature = "obj", feature = "ply", feature = "stl"))]
+use std::{error::Error, io::Write};
+#[cfg(feature = "obj")]
+use std::{fs::File, io::BufWriter, path::Path};
+
+/// How [`Mesh::generate_uvs()`] projects vertices to texture coordinates.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UvMode {
+    /// Project all faces along the given axis, keeping the other two
+    /// coordinates in ascending order, e.g. (x, z) for [`Axis::Y`].
+    Planar(Axis),
+    /// Project each face along the axis its normal is most aligned with.
+    Triplanar,
+}
+
+/// The up axis of the coordinate system to export to.
+///
+/// Meshes are assumed to be Z-up. Converting rotates the coordinates and
+/// keeps the handedness, so it is independent of reversing the face
+/// winding.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UpAxis {
+    /// Y points up, e.g. for Maya or Unity. `(x, y, z)` is written as
+    /// `(x, z, -y)`.
+    YUp,
+    /// Z points up, e.g. for Blender. Coordinates are written unchanged.
+    ZUp,
+}
+
+// How the OBJ writer splits the faces into objects or groups.
+#[cfg(feature = "obj")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ObjGrouping {
+    // One object containing all faces.
+    Single,
+    // One object per connected component.
+    ByComponent,
+    // One object with a group per face label.
+    ByLabel,
+}
+
+impl UpAxis {
+    #[cfg(any(feature = "obj", feature = "ply", feature = "stl"))]
+    fn convert(self, [x, y, z]: [f32; 3]) -> [f32; 3] {
+        match self {
+            // Subtract instead of negating, so zeros are not written as -0.
+            UpAxis::YUp => [x, z, 0. - y],
+            UpAxis::ZUp => [x, y, z],
+        }
+    }
+}
+
+/// How exporters like [`Mesh::to_obj()`] wind the faces of the mesh.
+///
+/// Converts from `bool`, with `true` reversing the winding.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Winding {
+    /// Keep the winding of the mesh.
+    Keep,
+    /// Reverse the winding of the mesh, e.g. for a left-handed target.
+    Reverse,
+    /// Reverse the winding, if the faces of the mesh point inwards, i.e.
+    /// the volume enclosed by them is negative. The result is arbitrary
+    /// for open meshes. See also
+    /// [`ManifoldDualContouring::recommended_winding()`](crate::ManifoldDualContouring::recommended_winding()).
+    Auto,
+}
+
+impl Winding {
+    // Whether to reverse the faces of the mesh with vertices.
+    #[cfg(any(feature = "obj", feature = "ply", feature = "stl"))]
+    fn reverses<'f, S: AsPrimitive<f32>>(
+        self,
+        vertices: &[[S; 3]],
+        faces: impl Iterator<Item = &'f [usize]>,
+    ) -> bool {
+        match self {
+            Winding::Keep => false,
+            Winding::Reverse => true,
+            Winding::Auto => signed_volume_f32(vertices, faces) < 0.,
+        }
+    }
+}
+
+impl From<bool> for Winding {
+    fn from(reverse: bool) -> Self {
+        if reverse {
+            Winding::Reverse
+        } else {
+            Winding::Keep
+        }
+    }
+}
+
+/// How [`TriangleMesh::vertex_normals()`] weights the normals of the
+/// faces around a vertex.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NormalWeighting {
+    /// All faces count the same.
+    Uniform,
+    /// Faces count proportional to their area.
+    ByArea,
+    /// Faces count proportional to their angle at the vertex. This does
+    /// not depend on how the surface is split into triangles.
+    ByAngle,
+}
+
+/// Which passes [`Mesh::repair()`] runs.
+///
+/// The default runs all passes except welding, which needs a distance.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RepairOptions<S> {
+    /// Merge vertices via [`Mesh::weld()`] with this epsilon, if set.
+    pub weld_epsilon: Option<S>,
+    /// Run [`Mesh::remove_degenerate_faces()`].
+    pub remove_degenerate_faces: bool,
+    /// Run [`Mesh::remove_duplicate_faces()`].
+    pub remove_duplicate_faces: bool,
+    /// Run [`Mesh::make_winding_consistent()`].
+    pub make_winding_consistent: bool,
+    /// Run [`Mesh::split_nonmanifold_vertices()`].
+    pub split_nonmanifold_vertices: bool,
+}
+
+impl<S> Default for RepairOptions<S> {
+    fn default() -> Self {
+        RepairOptions {
+            weld_epsilon: None,
+            remove_degenerate_faces: true,
+            remove_duplicate_faces: true,
+            make_winding_consistent: true,
+            split_nonmanifold_vertices: true,
+        }
+    }
+}
+
+/// Returned from [`Mesh::repair()`] with what each pass changed. Passes,
+/// that did not run, report zero.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct RepairReport {
+    /// Number of vertices removed by welding.
+    pub num_welded_vertices: usize,
+    /// Number of degenerate faces removed.
+    pub num_degenerate_faces: usize,
+    /// Number of duplicate faces removed.
+    pub num_duplicate_faces: usize,
+    /// Number of faces reversed.
+    pub num_reversed_faces: usize,
+    /// Number of non-manifold vertices split.
+    pub num_split_vertices: usize,
+}
+
+/// Returned from [`TriangleMesh::flat_topology_u32()`] and
+/// [`TriangleMesh::flat_topology_u16()`], if an index does not fit into
+/// the index type.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TooManyVertices {
+    /// An index, that does not fit.
+    pub index: usize,
+}
+
+impl error::Error for TooManyVertices {}
+
+impl fmt::Display for TooManyVertices {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Vertex index {} does not fit into the index type",
+            self.index
+        )
+    }
+}
+
+/// A vertex of the buffer returned from
+/// [`TriangleMesh::to_interleaved_bytes()`].
+///
+/// Its layout is `#[repr(C)]`, i.e. 24 bytes with the position at offset
+/// 0 and the normal at offset 12, e.g. two `Float32x3` attributes of a
+/// wgpu vertex buffer.
+#[cfg(feature = "gpu")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct GpuVertex {
+    /// The position of the vertex.
+    pub pos: [f32; 3],
+    /// The normal of the vertex.
+    pub normal: [f32; 3],
+}
 
 /// A polygon mesh consiting of (mostly) quads and triangles.
 ///
@@ -22,26 +229,288 @@ pub struct Mesh<S: Clone> {
     pub vertices: Vec<[S; 3]>,
     /// The list of faces as indexes into vertices.
     pub faces: Vec<SmallVec<[usize; 4]>>,
+    /// Optional per-face labels, parallel to faces.
+    ///
+    /// Set by
+    /// [`ManifoldDualContouring::with_face_labeler()`](crate::ManifoldDualContouring::with_face_labeler()).
+    pub labels: Option<Vec<u32>>,
+    /// Optional per-face normals, parallel to faces.
+    ///
+    /// Set by
+    /// [`ManifoldDualContouring::with_face_normals()`](crate::ManifoldDualContouring::with_face_normals()).
+    pub face_normals: Option<Vec<[S; 3]>>,
+    /// Optional per-vertex RGB colors, parallel to vertices.
+    ///
+    /// Set by
+    /// [`ManifoldDualContouring::with_vertex_color()`](crate::ManifoldDualContouring::with_vertex_color()).
+    pub vertex_colors: Option<Vec<[f32; 3]>>,
 }
 
 impl<S: Clone> Mesh<S> {
     /// Tessellates the mesh into triangles and yields a
     /// [`TriangleMesh`].
+    ///
+    /// Quads are split along the diagonal between their first and third
+    /// vertex. Larger polygons, e.g. from
+    /// [`merge_coplanar_faces()`](Self::merge_coplanar_faces()), are split
+    /// into a fan around their first vertex.
     pub fn to_triangle_mesh(&self) -> TriangleMesh<S> {
         TriangleMesh {
             vertices: self.vertices.clone(),
-            faces: self
-                .faces
-                .par_iter()
-                .flat_map(|face| {
-                    if 4 == face.len() {
-                        vec![[face[0], face[1], face[2]], [face[2], face[3], face[0]]]
-                    } else {
-                        vec![[face[0], face[1], face[2]]]
-                    }
+            faces: self.triangulated_faces(),
+        }
+    }
+
+    /// Like [`to_triangle_mesh()`](Self::to_triangle_mesh()), but moves
+    /// the vertices instead of cloning them.
+    ///
+    /// Use this, if the mesh is not needed afterwards, e.g. right after
+    /// tessellating, to avoid holding two copies of the vertices.
+    pub fn into_triangle_mesh(self) -> TriangleMesh<S> {
+        let faces = self.triangulated_faces();
+        TriangleMesh {
+            vertices: self.vertices,
+            faces,
+        }
+    }
+
+    fn triangulated_faces(&self) -> Vec<[usize; 3]> {
+        self.faces
+            .par_iter()
+            .flat_map_iter(|face| triangulate(face))
+            .collect()
+    }
+
+    /// Iterate over the faces, yielding the positions of each face’s
+    /// vertices.
+    pub fn iter_face_vertices(&self) -> impl Iterator<Item = SmallVec<[[S; 3]; 4]>> + '_ {
+        self.faces.iter().map(move |face| {
+            face.iter()
+                .map(|&index| self.vertices[index].clone())
+                .collect()
+        })
+    }
+
+    /// Builds a [`HalfEdgeMesh`] from the mesh.
+    ///
+    /// Edges on the boundary of the mesh are represented by half-edges
+    /// without a twin.
+    pub fn to_half_edge(&self) -> HalfEdgeMesh<S> {
+        HalfEdgeMesh::new(self.vertices.clone(), &self.faces)
+    }
+
+    /// Splits the mesh into its connected components.
+    ///
+    /// Faces are connected if they share a vertex. Each component gets
+    /// its own, compacted list of vertices and keeps the faces’ `labels`
+    /// and `face_normals` and the vertices’ `vertex_colors`. Vertices not
+    /// referenced by any face are dropped. Components are ordered by
+    /// their first face.
+    pub fn split_connected_components(&self) -> Vec<Mesh<S>> {
+        let mut components: Vec<Mesh<S>> = Vec::new();
+        // Map from vertex index to index in its component.
+        let mut local_indices = vec![None; self.vertices.len()];
+        for (face_index, (face, component)) in
+            self.faces.iter().zip(self.face_components()).enumerate()
+        {
+            if component == components.len() {
+                components.push(Mesh {
+                    vertices: Vec::new(),
+                    faces: Vec::new(),
+                    labels: self.labels.as_ref().map(|_| Vec::new()),
+                    face_normals: self.face_normals.as_ref().map(|_| Vec::new()),
+                    vertex_colors: self.vertex_colors.as_ref().map(|_| Vec::new()),
+                });
+            }
+            let mesh = &mut components[component];
+            let local_face = face
+                .iter()
+                .map(|&i| {
+                    *local_indices[i].get_or_insert_with(|| {
+                        mesh.vertices.push(self.vertices[i].clone());
+                        if let (Some(colors), Some(component_colors)) =
+                            (&self.vertex_colors, &mut mesh.vertex_colors)
+                        {
+                            component_colors.push(colors[i]);
+                        }
+                        mesh.vertices.len() - 1
+                    })
+                })
+                .collect();
+            mesh.faces.push(local_face);
+            if let (Some(labels), Some(component_labels)) = (&self.labels, &mut mesh.labels) {
+                component_labels.push(labels[face_index]);
+            }
+            if let (Some(normals), Some(component_normals)) =
+                (&self.face_normals, &mut mesh.face_normals)
+            {
+                component_normals.push(normals[face_index].clone());
+            }
+        }
+        components
+    }
+
+    /// Duplicates vertices, where two or more sheets of the surface
+    /// touch, and returns the number of vertices split.
+    ///
+    /// The faces around such a *bowtie* vertex form more than one fan,
+    /// i.e. group of faces connected by the edges at the vertex, e.g.
+    /// after [`weld()`](Self::weld()). The first fan keeps the vertex,
+    /// each further fan gets a copy appended to `vertices` and
+    /// `vertex_colors`. Afterwards
+    /// the faces around each vertex form a single fan, as required by
+    /// e.g. subdivision.
+    pub fn split_nonmanifold_vertices(&mut self) -> usize {
+        // The corners of the faces at each vertex as face index and index
+        // in the face.
+        let mut corners = vec![Vec::new(); self.vertices.len()];
+        for (face_index, face) in self.faces.iter().enumerate() {
+            for (i, &vertex) in face.iter().enumerate() {
+                corners[vertex].push((face_index, i));
+            }
+        }
+
+        let mut num_split = 0;
+        for (vertex, corners) in corners.iter().enumerate() {
+            if corners.len() < 2 {
+                continue;
+            }
+            // Union-find over the corners, joining corners whose faces
+            // share an edge at vertex, i.e. its other vertex.
+            let mut parents: Vec<usize> = (0..corners.len()).collect();
+            let mut edges = HashMap::new();
+            for (corner, &(face_index, i)) in corners.iter().enumerate() {
+                let face = &self.faces[face_index];
+                let n = face.len();
+                for &other in &[face[(i + 1) % n], face[(i + n - 1) % n]] {
+                    let first = *edges.entry(other).or_insert(corner);
+                    let a = find_root(&mut parents, first);
+                    let b = find_root(&mut parents, corner);
+                    parents[a] = b;
+                }
+            }
+
+            // Map from root corner to fan.
+            let mut fans = HashMap::new();
+            let corner_fans: Vec<usize> = (0..corners.len())
+                .map(|corner| {
+                    let root = find_root(&mut parents, corner);
+                    let next = fans.len();
+                    *fans.entry(root).or_insert(next)
                 })
-                .collect::<Vec<[usize; 3]>>(),
+                .collect();
+            if fans.len() < 2 {
+                continue;
+            }
+            num_split += 1;
+            let first_copy = self.vertices.len();
+            for _ in 1..fans.len() {
+                self.vertices.push(self.vertices[vertex].clone());
+                if let Some(ref mut colors) = self.vertex_colors {
+                    colors.push(colors[vertex]);
+                }
+            }
+            for (&(face_index, i), &fan) in corners.iter().zip(&corner_fans) {
+                if fan > 0 {
+                    self.faces[face_index][i] = first_copy + fan - 1;
+                }
+            }
+        }
+        num_split
+    }
+
+    // The index of the connected component of each face, see
+    // split_connected_components(). Components are numbered in the order
+    // of their first face.
+    fn face_components(&self) -> Vec<usize> {
+        // Union-find over the vertex indices.
+        let mut parents: Vec<usize> = (0..self.vertices.len()).collect();
+        for face in &self.faces {
+            for &i in &face[1..] {
+                let a = find_root(&mut parents, face[0]);
+                let b = find_root(&mut parents, i);
+                parents[a] = b;
+            }
         }
+
+        // Map from root vertex to component.
+        let mut components = HashMap::new();
+        self.faces
+            .iter()
+            .map(|face| {
+                let root = find_root(&mut parents, face[0]);
+                let next = components.len();
+                *components.entry(root).or_insert(next)
+            })
+            .collect()
+    }
+
+    /// Removes faces with less than three distinct vertices and returns
+    /// the number of faces removed, together with their `labels` and
+    /// `face_normals`.
+    pub fn remove_degenerate_faces(&mut self) -> usize {
+        let keep: Vec<bool> = self
+            .faces
+            .iter()
+            .map(|face| {
+                let mut distinct: SmallVec<[usize; 4]> = SmallVec::new();
+                for &vertex in face {
+                    if !distinct.contains(&vertex) {
+                        distinct.push(vertex);
+                    }
+                }
+                distinct.len() >= 3
+            })
+            .collect();
+        self.retain_faces(&keep)
+    }
+
+    /// Removes faces equal to an earlier face up to a cyclic rotation of
+    /// their vertices, and returns the number of faces removed, together
+    /// with their `labels` and `face_normals`.
+    ///
+    /// Faces with opposite winding are duplicates, too, as they cover the
+    /// same polygon. Faces using the same vertices in a different cyclic
+    /// order, i.e. describing a different polygon, are kept.
+    pub fn remove_duplicate_faces(&mut self) -> usize {
+        let mut seen = HashSet::new();
+        let keep: Vec<bool> = self
+            .faces
+            .iter()
+            .map(|face| {
+                let mut reversed = face.clone();
+                reversed.reverse();
+                // The smallest rotation in either winding.
+                let key = (0..face.len())
+                    .flat_map(|i| {
+                        [face, &reversed].map(|face| {
+                            let mut rotated = face.clone();
+                            rotated.rotate_left(i);
+                            rotated
+                        })
+                    })
+                    .min();
+                seen.insert(key)
+            })
+            .collect();
+        self.retain_faces(&keep)
+    }
+
+    // Keeps the faces, for which keep is true, together with their labels
+    // and face normals, and returns the number of faces removed.
+    fn retain_faces(&mut self, keep: &[bool]) -> usize {
+        let num_faces = self.faces.len();
+        let mut keep_face = keep.iter();
+        self.faces.retain(|_| *keep_face.next().unwrap());
+        if let Some(ref mut labels) = self.labels {
+            let mut keep = keep.iter();
+            labels.retain(|_| *keep.next().unwrap());
+        }
+        if let Some(ref mut face_normals) = self.face_normals {
+            let mut keep = keep.iter();
+            face_normals.retain(|_| *keep.next().unwrap());
+        }
+        num_faces - self.faces.len()
     }
 
     /// Returns the mesh’s topology as two, flat buffers.
@@ -69,49 +538,325 @@ impl<S: Clone> Mesh<S> {
     /// file.
     ///
     /// Depending on the target coordinate system (left- or right
-    /// handed) the mesh’s winding order can be reversed with the
-    /// `reverse_face_winding` flag.
+    /// handed) the mesh’s winding order can be reversed with `winding`,
+    /// see [`Winding`]. `true` and `false` convert to
+    /// [`Winding::Reverse`] and [`Winding::Keep`].
+    /// `up_axis` selects the target’s up axis, see [`UpAxis`].
+    /// [`vertex_colors`](Self::vertex_colors) are appended to the
+    /// vertices as `v x y z r g b`, which most tools read.
+    #[cfg(feature = "obj")]
+    pub fn to_obj(
+        &self,
+        winding: impl Into<Winding>,
+        up_axis: UpAxis,
+    ) -> Result<Vec<u8>, Box<dyn Error>>
+    where
+        S: AsPrimitive<f32>,
+    {
+        let mut file = Vec::new();
+        self.write_obj(&mut file, winding, up_axis)?;
+        Ok(file)
+    }
+
+    /// Write the mesh as a
+    /// [Wavefront OBJ](https://en.wikipedia.org/wiki/Wavefront_.obj_file)
+    /// file to `writer`.
+    ///
+    /// Unlike [`to_obj()`](Self::to_obj()) this does not buffer the whole
+    /// file in memory. Wrap unbuffered writers like a [`File`] in a
+    /// [`BufWriter`](std::io::BufWriter).
+    ///
+    /// Depending on the target coordinate system (left- or right
+    /// handed) the mesh’s winding order can be reversed with `winding`,
+    /// see [`Winding`]. `true` and `false` convert to
+    /// [`Winding::Reverse`] and [`Winding::Keep`].
+    /// `up_axis` selects the target’s up axis, see [`UpAxis`].
+    #[cfg(feature = "obj")]
+    pub fn write_obj<W: Write>(
+        &self,
+        writer: &mut W,
+        winding: impl Into<Winding>,
+        up_axis: UpAxis,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        S: AsPrimitive<f32>,
+    {
+        self.write_obj_impl(
+            writer,
+            winding.into(),
+            up_axis,
+            None,
+            ObjGrouping::Single,
+            None,
+        )
+    }
+
+    /// Like [`to_obj()`](Self::to_obj()), but also writes a smoothing
+    /// group directive, `s 1` if `smooth` is `true`, else `s off`, so
+    /// importers shade the mesh smooth or faceted.
+    ///
+    /// If `by_label` is `true` and the mesh has
+    /// [`labels`](Self::labels), the faces are split into one group per
+    /// label, named `label_0`, `label_3` and so on in ascending order,
+    /// e.g. to assign a material to each.
+    #[cfg(feature = "obj")]
+    pub fn to_obj_with_groups(
+        &self,
+        winding: impl Into<Winding>,
+        up_axis: UpAxis,
+        smooth: bool,
+        by_label: bool,
+    ) -> Result<Vec<u8>, Box<dyn Error>>
+    where
+        S: AsPrimitive<f32>,
+    {
+        let mut file = Vec::new();
+        self.write_obj_with_groups(&mut file, winding, up_axis, smooth, by_label)?;
+        Ok(file)
+    }
+
+    /// Like [`write_obj()`](Self::write_obj()), but also writes a
+    /// smoothing group directive and groups by label, see
+    /// [`to_obj_with_groups()`](Self::to_obj_with_groups()).
+    #[cfg(feature = "obj")]
+    pub fn write_obj_with_groups<W: Write>(
+        &self,
+        writer: &mut W,
+        winding: impl Into<Winding>,
+        up_axis: UpAxis,
+        smooth: bool,
+        by_label: bool,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        S: AsPrimitive<f32>,
+    {
+        let grouping = if by_label {
+            ObjGrouping::ByLabel
+        } else {
+            ObjGrouping::Single
+        };
+        self.write_obj_impl(
+            writer,
+            winding.into(),
+            up_axis,
+            None,
+            grouping,
+            Some(smooth),
+        )
+    }
+
+    /// Like [`to_obj()`](Self::to_obj()), but writes each connected
+    /// component as a separate object, named `SDFMesh_0`, `SDFMesh_1`
+    /// and so on.
+    ///
+    /// All objects share one list of vertices. Components are ordered
+    /// like in
+    /// [`split_connected_components()`](Self::split_connected_components()).
+    #[cfg(feature = "obj")]
+    pub fn to_obj_by_component(
+        &self,
+        winding: impl Into<Winding>,
+        up_axis: UpAxis,
+    ) -> Result<Vec<u8>, Box<dyn Error>>
+    where
+        S: AsPrimitive<f32>,
+    {
+        let mut file = Vec::new();
+        self.write_obj_by_component(&mut file, winding, up_axis)?;
+        Ok(file)
+    }
+
+    /// Like [`write_obj()`](Self::write_obj()), but writes each connected
+    /// component as a separate object, see
+    /// [`to_obj_by_component()`](Self::to_obj_by_component()).
+    #[cfg(feature = "obj")]
+    pub fn write_obj_by_component<W: Write>(
+        &self,
+        writer: &mut W,
+        winding: impl Into<Winding>,
+        up_axis: UpAxis,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        S: AsPrimitive<f32>,
+    {
+        self.write_obj_impl(
+            writer,
+            winding.into(),
+            up_axis,
+            None,
+            ObjGrouping::ByComponent,
+            None,
+        )
+    }
+
+    /// Like [`to_obj()`](Self::to_obj()), but also writes texture
+    /// coordinates.
+    ///
+    /// `uvs` has one entry per face corner, as returned from
+    /// [`generate_uvs()`](Self::generate_uvs()).
     #[cfg(feature = "obj")]
-    pub fn to_obj(&self, reverse_face_winding: bool) -> Result<Vec<u8>, Box<dyn Error>>
+    pub fn to_obj_with_uvs(
+        &self,
+        winding: impl Into<Winding>,
+        up_axis: UpAxis,
+        uvs: &[[S; 2]],
+    ) -> Result<Vec<u8>, Box<dyn Error>>
     where
         S: AsPrimitive<f32>,
     {
         let mut file = Vec::new();
+        self.write_obj_with_uvs(&mut file, winding, up_axis, uvs)?;
+        Ok(file)
+    }
+
+    /// Like [`write_obj()`](Self::write_obj()), but also writes texture
+    /// coordinates.
+    ///
+    /// `uvs` has one entry per face corner, as returned from
+    /// [`generate_uvs()`](Self::generate_uvs()).
+    #[cfg(feature = "obj")]
+    pub fn write_obj_with_uvs<W: Write>(
+        &self,
+        writer: &mut W,
+        winding: impl Into<Winding>,
+        up_axis: UpAxis,
+        uvs: &[[S; 2]],
+    ) -> Result<(), Box<dyn Error>>
+    where
+        S: AsPrimitive<f32>,
+    {
+        self.write_obj_impl(
+            writer,
+            winding.into(),
+            up_axis,
+            Some(uvs),
+            ObjGrouping::Single,
+            None,
+        )
+    }
+
+    #[cfg(feature = "obj")]
+    fn write_obj_impl<W: Write>(
+        &self,
+        writer: &mut W,
+        winding: Winding,
+        up_axis: UpAxis,
+        uvs: Option<&[[S; 2]]>,
+        grouping: ObjGrouping,
+        smooth: Option<bool>,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        S: AsPrimitive<f32>,
+    {
+        if let Some(uvs) = uvs {
+            let num_corners: usize = self.faces.iter().map(|face| face.len()).sum();
+            if uvs.len() != num_corners {
+                return Err(
+                    format!("got {} uvs for {} face corners", uvs.len(), num_corners).into(),
+                );
+            }
+        }
 
-        writeln!(file, "o SDFMesh")?;
+        let reverse_face_winding =
+            winding.reverses(&self.vertices, self.faces.iter().map(|face| &face[..]));
 
-        for vertex in &self.vertices {
-            writeln!(
-                file,
-                "v {} {} {}",
-                vertex[0].as_(),
-                vertex[1].as_(),
-                vertex[2].as_(),
-            )?;
+        let write_smoothing = |writer: &mut W| match smooth {
+            Some(true) => writeln!(writer, "s 1"),
+            Some(false) => writeln!(writer, "s off"),
+            None => Ok(()),
+        };
+        if grouping != ObjGrouping::ByComponent {
+            writeln!(writer, "o SDFMesh")?;
+            write_smoothing(writer)?;
         }
 
-        match reverse_face_winding {
-            true => {
-                for face in &self.faces {
-                    write!(file, "f")?;
-                    for vertex_index in face.iter().rev() {
-                        write!(file, " {}", vertex_index + 1)?;
-                    }
-                    writeln!(file)?;
+        for (i, vertex) in self.vertices.iter().enumerate() {
+            let [x, y, z] = up_axis.convert([vertex[0].as_(), vertex[1].as_(), vertex[2].as_()]);
+            match self.vertex_colors {
+                Some(ref colors) => {
+                    let [r, g, b] = colors[i];
+                    writeln!(writer, "v {} {} {} {} {} {}", x, y, z, r, g, b)?
                 }
+                None => writeln!(writer, "v {} {} {}", x, y, z)?,
             }
-            false => {
-                for face in &self.faces {
-                    write!(file, "f")?;
-                    for vertex_index in face {
-                        write!(file, " {}", vertex_index + 1)?;
+        }
+
+        if let Some(uvs) = uvs {
+            for uv in uvs {
+                writeln!(writer, "vt {} {}", uv[0].as_(), uv[1].as_())?;
+            }
+        }
+
+        // The faces of each object or group, in order, along with its
+        // header.
+        let all_faces = || vec![(None, (0..self.faces.len()).collect())];
+        let objects: Vec<(Option<String>, Vec<usize>)> = match (grouping, &self.labels) {
+            (ObjGrouping::ByComponent, _) => {
+                let mut objects: Vec<Vec<usize>> = Vec::new();
+                for (face_index, component) in self.face_components().into_iter().enumerate() {
+                    if component == objects.len() {
+                        objects.push(Vec::new());
                     }
-                    writeln!(file)?;
+                    objects[component].push(face_index);
+                }
+                objects
+                    .into_iter()
+                    .enumerate()
+                    .map(|(object, faces)| (Some(format!("o SDFMesh_{}", object)), faces))
+                    .collect()
+            }
+            (ObjGrouping::ByLabel, Some(labels)) => {
+                let mut groups = BTreeMap::new();
+                for (face_index, &label) in labels.iter().enumerate() {
+                    groups
+                        .entry(label)
+                        .or_insert_with(Vec::new)
+                        .push(face_index);
                 }
+                groups
+                    .into_iter()
+                    .map(|(label, faces)| (Some(format!("g label_{}", label)), faces))
+                    .collect()
             }
+            _ => all_faces(),
         };
+        // Index of the first corner of each face in uvs.
+        let first_corners: Vec<usize> = self
+            .faces
+            .iter()
+            .scan(0, |first_corner, face| {
+                let corner = *first_corner;
+                *first_corner += face.len();
+                Some(corner)
+            })
+            .collect();
+        for (header, faces) in &objects {
+            if let Some(header) = header {
+                writeln!(writer, "{}", header)?;
+                if grouping == ObjGrouping::ByComponent {
+                    write_smoothing(writer)?;
+                }
+            }
+            for &face_index in faces {
+                let face = &self.faces[face_index];
+                let first_corner = first_corners[face_index];
+                write!(writer, "f")?;
+                for k in 0..face.len() {
+                    let k = if reverse_face_winding {
+                        face.len() - 1 - k
+                    } else {
+                        k
+                    };
+                    match uvs {
+                        Some(_) => write!(writer, " {}/{}", face[k] + 1, first_corner + k + 1)?,
+                        None => write!(writer, " {}", face[k] + 1)?,
+                    }
+                }
+                writeln!(writer)?;
+            }
+        }
 
-        Ok(file)
+        Ok(())
     }
 
     /// Export the mesh as a
@@ -119,46 +864,1273 @@ impl<S: Clone> Mesh<S> {
     /// file.
     ///
     /// Depending on the target coordinate system (left- or right
-    /// handed) the mesh’s winding order can be reversed with the
-    /// `reverse_face_winding` flag.
+    /// handed) the mesh’s winding order can be reversed with `winding`,
+    /// see [`Winding`]. `true` and `false` convert to
+    /// [`Winding::Reverse`] and [`Winding::Keep`].
+    /// `up_axis` selects the target’s up axis, see [`UpAxis`].
     #[cfg(feature = "obj")]
     pub fn write_to_obj(
         &self,
         destination: &Path,
-        reverse_face_winding: bool,
+        winding: impl Into<Winding>,
+        up_axis: UpAxis,
     ) -> Result<(), Box<dyn Error>>
     where
         S: AsPrimitive<f32>,
     {
-        let mut file = File::create(destination)?;
-        file.write_all(&self.to_obj(reverse_face_winding)?)?;
+        let mut file = BufWriter::new(File::create(destination)?);
+        self.write_obj(&mut file, winding, up_axis)?;
         file.flush()?;
 
         Ok(())
     }
-}
 
-#[cfg(feature = "polyhedron-ops")]
-impl<S: Clone + AsPrimitive<f32>> From<Mesh<S>> for p_ops::Polyhedron {
-    fn from(mesh: Mesh<S>) -> p_ops::Polyhedron {
-        p_ops::Polyhedron::from(
-            "SDFMesh",
-            mesh.vertices
-                .iter()
-                .map(|vertex| p_ops::Point::new(vertex[0].as_(), vertex[1].as_(), vertex[2].as_()))
-                .collect(),
-            mesh.faces
-                .iter()
-                .map(|face| face.iter().map(|index| *index as u32).collect())
-                .collect(),
-            None,
-        )
+    /// Describe the mesh as an [`Vec<u8>`] buffer containing an ASCII
+    /// [PLY](https://en.wikipedia.org/wiki/PLY_(file_format)) file.
+    ///
+    /// `winding` and `up_axis` work like for [`to_obj()`](Self::to_obj()).
+    /// [`vertex_colors`](Self::vertex_colors) are written as the `red`,
+    /// `green` and `blue` properties of the vertices, scaled from
+    /// `[0, 1]` to bytes.
+    #[cfg(feature = "ply")]
+    pub fn to_ply(
+        &self,
+        winding: impl Into<Winding>,
+        up_axis: UpAxis,
+    ) -> Result<Vec<u8>, Box<dyn Error>>
+    where
+        S: AsPrimitive<f32>,
+    {
+        let mut file = Vec::new();
+        self.write_ply(&mut file, winding, up_axis)?;
+        Ok(file)
     }
-}
 
-/// Triangle mesh that will be returned from
-/// [`TriangleMesh::from<Mesh>()`] or
-/// [`Mesh::to_triangle_mesh()`].
+    /// Write the mesh as an ASCII
+    /// [PLY](https://en.wikipedia.org/wiki/PLY_(file_format)) file to
+    /// `writer`, see [`to_ply()`](Self::to_ply()).
+    ///
+    /// Fails, if a face has more than 255 vertices.
+    #[cfg(feature = "ply")]
+    pub fn write_ply<W: Write>(
+        &self,
+        writer: &mut W,
+        winding: impl Into<Winding>,
+        up_axis: UpAxis,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        S: AsPrimitive<f32>,
+    {
+        if let Some(face) = self.faces.iter().find(|face| face.len() > 255) {
+            return Err(format!("got a face with {} vertices", face.len()).into());
+        }
+        let reverse_face_winding = winding
+            .into()
+            .reverses(&self.vertices, self.faces.iter().map(|face| &face[..]));
+
+        writeln!(writer, "ply")?;
+        writeln!(writer, "format ascii 1.0")?;
+        writeln!(writer, "element vertex {}", self.vertices.len())?;
+        for coordinate in &["x", "y", "z"] {
+            writeln!(writer, "property float {}", coordinate)?;
+        }
+        if self.vertex_colors.is_some() {
+            for channel in &["red", "green", "blue"] {
+                writeln!(writer, "property uchar {}", channel)?;
+            }
+        }
+        writeln!(writer, "element face {}", self.faces.len())?;
+        writeln!(writer, "property list uchar uint vertex_indices")?;
+        writeln!(writer, "end_header")?;
+
+        for (i, vertex) in self.vertices.iter().enumerate() {
+            let [x, y, z] = up_axis.convert([vertex[0].as_(), vertex[1].as_(), vertex[2].as_()]);
+            match self.vertex_colors {
+                Some(ref colors) => {
+                    let [r, g, b] = colors[i].map(|c| (c.clamp(0., 1.) * 255.).round() as u8);
+                    writeln!(writer, "{} {} {} {} {} {}", x, y, z, r, g, b)?
+                }
+                None => writeln!(writer, "{} {} {}", x, y, z)?,
+            }
+        }
+
+        for face in &self.faces {
+            write!(writer, "{}", face.len())?;
+            for k in 0..face.len() {
+                let k = if reverse_face_winding {
+                    face.len() - 1 - k
+                } else {
+                    k
+                };
+                write!(writer, " {}", face[k])?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: RealField + Float + Debug> Mesh<S> {
+    /// Returns the maximum and the root mean square of `|f.value()|` at
+    /// the vertices, e.g. to measure how far a tessellation of `f`
+    /// deviates from its surface.
+    ///
+    /// Since `f` is zero on its surface, this is the error introduced by
+    /// the resolution and simplification. For an exact distance function
+    /// it is the distance to the surface. Returns zeros for a mesh
+    /// without vertices.
+    pub fn surface_error(&self, f: &dyn ImplicitFunction<S>) -> (S, S) {
+        if self.vertices.is_empty() {
+            return (S::zero(), S::zero());
+        }
+        let points: Vec<na::Point3<S>> =
+            self.vertices.iter().map(|&v| na::Point3::from(v)).collect();
+        let mut values = vec![S::zero(); points.len()];
+        f.values(&points, &mut values);
+        let (max, sum_squared) = values
+            .iter()
+            .fold((S::zero(), S::zero()), |(max, sum), &value| {
+                (Float::max(max, Float::abs(value)), sum + value * value)
+            });
+        let count = na::convert::<f64, S>(values.len() as f64);
+        (max, Float::sqrt(sum_squared / count))
+    }
+
+    /// Returns the point on the mesh closest to `p` and the index of the
+    /// face it lies on.
+    ///
+    /// Returns `None` if the mesh has no faces.
+    pub fn closest_point(&self, p: [S; 3]) -> Option<([S; 3], usize)> {
+        let p = na::Point3::from(p);
+        let mut result: Option<(na::Point3<S>, usize)> = None;
+        let mut best_distance_squared = <S as Float>::infinity();
+        for (face_index, face) in self.faces.iter().enumerate() {
+            let points: SmallVec<[na::Point3<S>; 4]> = face
+                .iter()
+                .map(|&i| na::Point3::from(self.vertices[i]))
+                .collect();
+            // Skip faces whose bounding box is further away than the best
+            // point found so far.
+            let mut outside = na::Vector3::zeros();
+            for dim in 0..3 {
+                let min = points
+                    .iter()
+                    .fold(<S as Float>::infinity(), |m, v| Float::min(m, v[dim]));
+                let max = points
+                    .iter()
+                    .fold(<S as Float>::neg_infinity(), |m, v| Float::max(m, v[dim]));
+                outside[dim] = Float::max(Float::max(min - p[dim], p[dim] - max), S::zero());
+            }
+            if outside.norm_squared() >= best_distance_squared {
+                continue;
+            }
+            for i in 1..points.len() - 1 {
+                let candidate =
+                    closest_point_on_triangle(&p, &points[0], &points[i], &points[i + 1]);
+                let distance_squared = (candidate - p).norm_squared();
+                if distance_squared < best_distance_squared {
+                    best_distance_squared = distance_squared;
+                    result = Some((candidate, face_index));
+                }
+            }
+        }
+        result.map(|(point, face_index)| ([point.x, point.y, point.z], face_index))
+    }
+
+    /// Merges adjacent faces into larger polygons, where this keeps the
+    /// result planar and convex.
+    ///
+    /// Two faces are merged if they share at least one edge, their
+    /// normals differ by at most `angle_tolerance` (in radians) and the
+    /// merged polygon is a single loop, whose corners all turn the same
+    /// way and whose local normals stay within `angle_tolerance` of its
+    /// normal. Corners on a straight line are kept, so no T-junctions
+    /// are introduced. Faces with different `labels` are not merged;
+    /// `face_normals` of merged faces are averaged. Vertices, that end up
+    /// inside a merged polygon, stay in `vertices`.
+    pub fn merge_coplanar_faces(&mut self, angle_tolerance: S) {
+        let cos_tolerance = Float::cos(angle_tolerance);
+        let sin_tolerance = Float::sin(angle_tolerance);
+        let mut faces: Vec<Option<SmallVec<[usize; 4]>>> = self.faces.drain(..).map(Some).collect();
+        let mut normals: Vec<Option<na::Vector3<S>>> = faces
+            .iter()
+            .map(|face| self.polygon_normal(face.as_ref().unwrap()))
+            .collect();
+        loop {
+            // Map from directed edge to the face it belongs to.
+            let mut edges = HashMap::new();
+            for (face_index, face) in faces.iter().enumerate() {
+                if let Some(face) = face {
+                    for (i, &a) in face.iter().enumerate() {
+                        edges.insert((a, face[(i + 1) % face.len()]), face_index);
+                    }
+                }
+            }
+            let mut touched = vec![false; faces.len()];
+            let mut merged_any = false;
+            for f in 0..faces.len() {
+                if touched[f] || faces[f].is_none() {
+                    continue;
+                }
+                let face = faces[f].clone().unwrap();
+                for (i, &a) in face.iter().enumerate() {
+                    let b = face[(i + 1) % face.len()];
+                    let g = match edges.get(&(b, a)) {
+                        Some(&g) if g != f && !touched[g] && faces[g].is_some() => g,
+                        _ => continue,
+                    };
+                    let same_label = match self.labels {
+                        Some(ref labels) => labels[f] == labels[g],
+                        None => true,
+                    };
+                    let similar = match (normals[f], normals[g]) {
+                        (Some(nf), Some(ng)) => nf.dot(&ng) >= cos_tolerance,
+                        _ => false,
+                    };
+                    if !same_label || !similar {
+                        continue;
+                    }
+                    if let Some((merged, normal)) = self.merge_faces(
+                        &face,
+                        faces[g].as_ref().unwrap(),
+                        cos_tolerance,
+                        sin_tolerance,
+                    ) {
+                        faces[f] = Some(merged);
+                        faces[g] = None;
+                        normals[f] = Some(normal);
+                        if let Some(ref mut face_normals) = self.face_normals {
+                            let n = (na::Vector3::from(face_normals[f])
+                                + na::Vector3::from(face_normals[g]))
+                            .normalize();
+                            face_normals[f] = [n.x, n.y, n.z];
+                        }
+                        touched[f] = true;
+                        touched[g] = true;
+                        merged_any = true;
+                        break;
+                    }
+                }
+            }
+            if !merged_any {
+                break;
+            }
+        }
+        let keep: Vec<bool> = faces.iter().map(|face| face.is_some()).collect();
+        self.faces = faces.into_iter().flatten().collect();
+        if let Some(ref mut labels) = self.labels {
+            let mut keep = keep.iter();
+            labels.retain(|_| *keep.next().unwrap());
+        }
+        if let Some(ref mut face_normals) = self.face_normals {
+            let mut keep = keep.iter();
+            face_normals.retain(|_| *keep.next().unwrap());
+        }
+    }
+
+    /// Reverses faces, so that neighboring faces wind consistently and
+    /// each connected component faces outwards, and returns the number
+    /// of faces reversed.
+    ///
+    /// Faces are neighbors, if they share an edge. Outwards means the
+    /// signed volume of the component is positive, which is only
+    /// meaningful for closed components. For non-orientable components,
+    /// the first winding found for each face is kept. The `face_normals`
+    /// of reversed faces are negated.
+    pub fn make_winding_consistent(&mut self) -> usize {
+        let flips = self.winding_flips();
+        for (face, _) in self.faces.iter_mut().zip(&flips).filter(|(_, &flip)| flip) {
+            face.reverse();
+        }
+        if let Some(ref mut face_normals) = self.face_normals {
+            for (normal, _) in face_normals
+                .iter_mut()
+                .zip(&flips)
+                .filter(|(_, &flip)| flip)
+            {
+                for c in normal.iter_mut() {
+                    *c = -*c;
+                }
+            }
+        }
+        flips.iter().filter(|&&flip| flip).count()
+    }
+
+    // Whether make_winding_consistent() reverses each face.
+    pub(crate) fn winding_flips(&self) -> Vec<bool> {
+        // Map from undirected edge to the faces using it and whether
+        // they use it from its lower to its higher vertex.
+        let mut edges: HashMap<(usize, usize), Vec<(usize, bool)>> = HashMap::new();
+        for (face_index, face) in self.faces.iter().enumerate() {
+            for (i, &a) in face.iter().enumerate() {
+                let b = face[(i + 1) % face.len()];
+                edges
+                    .entry((a.min(b), a.max(b)))
+                    .or_default()
+                    .push((face_index, a < b));
+            }
+        }
+
+        let mut flip: Vec<Option<bool>> = vec![None; self.faces.len()];
+        let mut reverse = vec![false; self.faces.len()];
+        for start in 0..self.faces.len() {
+            if flip[start].is_some() {
+                continue;
+            }
+            // Walk the component, deciding each face's flip relative to
+            // the neighbor it was reached from.
+            flip[start] = Some(false);
+            let mut component = vec![start];
+            let mut stack = vec![start];
+            while let Some(face_index) = stack.pop() {
+                let face = &self.faces[face_index];
+                for (i, &a) in face.iter().enumerate() {
+                    let b = face[(i + 1) % face.len()];
+                    for &(neighbor, forward) in &edges[&(a.min(b), a.max(b))] {
+                        if flip[neighbor].is_none() {
+                            // Using the edge in the same direction means
+                            // opposite winding.
+                            flip[neighbor] = Some(flip[face_index].unwrap() ^ (forward == (a < b)));
+                            component.push(neighbor);
+                            stack.push(neighbor);
+                        }
+                    }
+                }
+            }
+
+            let mut volume = S::zero();
+            for &face_index in &component {
+                let face = &self.faces[face_index];
+                let v = |i: usize| na::Vector3::from(self.vertices[face[i]]);
+                for i in 1..face.len() - 1 {
+                    let signed = v(0).dot(&v(i).cross(&v(i + 1)));
+                    volume += if flip[face_index].unwrap() {
+                        -signed
+                    } else {
+                        signed
+                    };
+                }
+            }
+            let inward = volume < S::zero();
+            for &face_index in &component {
+                reverse[face_index] = flip[face_index].unwrap() != inward;
+            }
+        }
+        reverse
+    }
+
+    /// Returns the edges on the boundary of the mesh, i.e. the edges used
+    /// by exactly one face, as pairs of vertex indices.
+    ///
+    /// Each edge is oriented like in its face. The edges are sorted. A
+    /// closed mesh has none.
+    pub fn boundary_edges(&self) -> Vec<(usize, usize)> {
+        // Map from undirected edge to the number of faces using it and
+        // the edge as used by the last one.
+        let mut edges: HashMap<(usize, usize), (usize, (usize, usize))> = HashMap::new();
+        for face in &self.faces {
+            for (i, &a) in face.iter().enumerate() {
+                let b = face[(i + 1) % face.len()];
+                let entry = edges.entry((a.min(b), a.max(b))).or_insert((0, (a, b)));
+                entry.0 += 1;
+                entry.1 = (a, b);
+            }
+        }
+        let mut boundary: Vec<(usize, usize)> = edges
+            .values()
+            .filter(|&&(count, _)| count == 1)
+            .map(|&(_, edge)| edge)
+            .collect();
+        boundary.sort_unstable();
+        boundary
+    }
+
+    /// Returns the Euler characteristic `V - E + F` of the mesh.
+    ///
+    /// Only vertices used by faces are counted and each edge is counted
+    /// once, regardless of how many faces share it. A closed surface of
+    /// genus `g`, e.g. a sphere for `0` or a torus for `1`, has `2 - 2g`.
+    pub fn euler_characteristic(&self) -> i32 {
+        self.euler_characteristics(&vec![0; self.faces.len()], 1)
+            .first()
+            .cloned()
+            .unwrap_or(0)
+    }
+
+    /// Returns true, if the mesh is a closed, orientable surface.
+    ///
+    /// Every edge must be used by exactly two faces in opposite
+    /// directions, so there are no boundary edges, and each connected
+    /// component must have the Euler characteristic `2 - 2g` of a
+    /// surface of some genus `g`. The latter fails e.g. for components
+    /// touching at a vertex. An empty mesh is not a closed surface.
+    pub fn is_closed_surface(&self) -> bool {
+        let mut directed_edges = HashSet::new();
+        for face in &self.faces {
+            for (i, &a) in face.iter().enumerate() {
+                if !directed_edges.insert((a, face[(i + 1) % face.len()])) {
+                    return false;
+                }
+            }
+        }
+        if self.faces.is_empty()
+            || directed_edges
+                .iter()
+                .any(|&(a, b)| !directed_edges.contains(&(b, a)))
+        {
+            return false;
+        }
+        let components = self.face_components();
+        let num_components = components.iter().max().map_or(0, |&c| c + 1);
+        self.euler_characteristics(&components, num_components)
+            .iter()
+            .all(|&euler| euler <= 2 && euler % 2 == 0)
+    }
+
+    // The Euler characteristic of each component, given the component of
+    // each face.
+    fn euler_characteristics(&self, components: &[usize], num_components: usize) -> Vec<i32> {
+        let mut euler = vec![0; num_components];
+        let mut vertices = HashSet::new();
+        let mut edges = HashSet::new();
+        for (face, &component) in self.faces.iter().zip(components) {
+            euler[component] += 1;
+            for (i, &a) in face.iter().enumerate() {
+                if vertices.insert(a) {
+                    euler[component] += 1;
+                }
+                let b = face[(i + 1) % face.len()];
+                if edges.insert((a.min(b), a.max(b))) {
+                    euler[component] -= 1;
+                }
+            }
+        }
+        euler
+    }
+
+    /// Chains the [`boundary_edges()`](Self::boundary_edges()) into
+    /// loops and returns their vertex indices in order.
+    ///
+    /// Loops follow the orientation of the faces next to them. Where
+    /// several loops touch at a vertex, their edges are chained there
+    /// arbitrarily. Edges, that do not form a closed loop, e.g. next to
+    /// inconsistently oriented faces, are left out.
+    pub fn boundary_loops(&self) -> Vec<Vec<usize>> {
+        // Map from start to end vertices.
+        let mut ends: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (a, b) in self.boundary_edges() {
+            ends.entry(a).or_default().push(b);
+        }
+        let mut starts: Vec<usize> = ends.keys().cloned().collect();
+        starts.sort_unstable();
+        let mut loops = Vec::new();
+        for start in starts {
+            while let Some(mut next) = ends.get_mut(&start).and_then(|ends| ends.pop()) {
+                let mut boundary_loop = vec![start];
+                while next != start {
+                    match ends.get_mut(&next).and_then(|ends| ends.pop()) {
+                        Some(end) => {
+                            boundary_loop.push(next);
+                            next = end;
+                        }
+                        None => break,
+                    }
+                }
+                if next == start {
+                    loops.push(boundary_loop);
+                }
+            }
+        }
+        loops
+    }
+
+    /// Closes holes in the mesh, whose boundary loop has at most
+    /// `max_edges` edges, and returns the number of holes filled.
+    ///
+    /// Boundary edges are edges used by exactly one face. A hole with
+    /// three edges is closed with a single triangle. A hole with more
+    /// edges gets a new vertex at the centroid of the loop and is closed
+    /// with a fan of triangles around it. The new faces get the `labels`
+    /// of the faces across the hole’s edges and the normal of the loop as
+    /// their `face_normals`. Holes with more than `max_edges` edges are
+    /// left open.
+    pub fn fill_holes(&mut self, max_edges: usize) -> usize {
+        let mut directed_edges = HashMap::new();
+        for (face_index, face) in self.faces.iter().enumerate() {
+            for (i, &a) in face.iter().enumerate() {
+                directed_edges.insert((a, face[(i + 1) % face.len()]), face_index);
+            }
+        }
+        // The edges of the holes, winding opposite to the faces next to
+        // them. Map from start vertex to end vertex and neighboring face.
+        let mut hole_edges: HashMap<usize, Vec<(usize, usize)>> = HashMap::new();
+        for (&(a, b), &face_index) in &directed_edges {
+            if !directed_edges.contains_key(&(b, a)) {
+                hole_edges.entry(b).or_default().push((a, face_index));
+            }
+        }
+
+        let mut starts: Vec<usize> = hole_edges.keys().cloned().collect();
+        starts.sort_unstable();
+        let mut num_filled = 0;
+        for start in starts {
+            while let Some((mut next, face_index)) =
+                hole_edges.get_mut(&start).and_then(|edges| edges.pop())
+            {
+                // Walk along the loop. The vertices and the faces across
+                // the edges from each vertex to the next.
+                let mut hole = vec![(start, face_index)];
+                while next != start {
+                    match hole_edges.get_mut(&next).and_then(|edges| edges.pop()) {
+                        Some((end, face_index)) => {
+                            hole.push((next, face_index));
+                            next = end;
+                        }
+                        // Not a closed loop. Can only happen for
+                        // inconsistently oriented faces.
+                        None => break,
+                    }
+                }
+                if next != start || hole.len() < 3 || hole.len() > max_edges {
+                    continue;
+                }
+                self.fill_hole(&hole);
+                num_filled += 1;
+            }
+        }
+        num_filled
+    }
+
+    // Closes the hole given as loop of vertices and the faces across the
+    // edges from each vertex to the next.
+    fn fill_hole(&mut self, hole: &[(usize, usize)]) {
+        let vertices: Vec<usize> = hole.iter().map(|&(vertex, _)| vertex).collect();
+        let normal = self
+            .polygon_normal(&vertices)
+            .unwrap_or_else(na::Vector3::zeros);
+        let add_face = |mesh: &mut Self, face: SmallVec<[usize; 4]>, neighbor: usize| {
+            mesh.faces.push(face);
+            if let Some(ref mut labels) = mesh.labels {
+                labels.push(labels[neighbor]);
+            }
+            if let Some(ref mut face_normals) = mesh.face_normals {
+                face_normals.push([normal.x, normal.y, normal.z]);
+            }
+        };
+        if hole.len() == 3 {
+            add_face(self, SmallVec::from_slice(&vertices), hole[0].1);
+            return;
+        }
+        let sum = vertices.iter().fold(na::Vector3::zeros(), |sum, &i| {
+            sum + na::Vector3::from(self.vertices[i])
+        });
+        let centroid: na::Vector3<S> = sum / na::convert::<f64, S>(vertices.len() as f64);
+        self.vertices.push([centroid.x, centroid.y, centroid.z]);
+        if let Some(ref mut colors) = self.vertex_colors {
+            let mut color = [0f32; 3];
+            for &i in &vertices {
+                for (c, &v) in color.iter_mut().zip(&colors[i]) {
+                    *c += v / vertices.len() as f32;
+                }
+            }
+            colors.push(color);
+        }
+        let center = self.vertices.len() - 1;
+        for (i, &(vertex, neighbor)) in hole.iter().enumerate() {
+            let next = hole[(i + 1) % hole.len()].0;
+            add_face(
+                self,
+                SmallVec::from_slice(&[vertex, next, center]),
+                neighbor,
+            );
+        }
+    }
+
+    // Returns the normalized normal of the polygon, computed with Newell’s
+    // method, or None if the polygon is degenerate.
+    fn polygon_normal(&self, face: &[usize]) -> Option<na::Vector3<S>> {
+        let mut n = na::Vector3::zeros();
+        for (i, &a) in face.iter().enumerate() {
+            let p = na::Vector3::from(self.vertices[a]);
+            let q = na::Vector3::from(self.vertices[face[(i + 1) % face.len()]]);
+            n += p.cross(&q);
+        }
+        n.try_normalize(S::zero())
+    }
+
+    // Returns the boundary of the union of the faces f and g and its
+    // normal, if it is a single, convex and planar polygon.
+    fn merge_faces(
+        &self,
+        f: &[usize],
+        g: &[usize],
+        cos_tolerance: S,
+        sin_tolerance: S,
+    ) -> Option<(SmallVec<[usize; 4]>, na::Vector3<S>)> {
+        let directed_edges = |face: &[usize]| {
+            (0..face.len())
+                .map(|i| (face[i], face[(i + 1) % face.len()]))
+                .collect::<Vec<_>>()
+        };
+        let f_edges = directed_edges(f);
+        let g_edges = directed_edges(g);
+        // Chain the edges, that are not shared, into a loop.
+        let mut next = HashMap::new();
+        for &(a, b) in f_edges.iter().chain(g_edges.iter()) {
+            let shared = f_edges.contains(&(b, a)) || g_edges.contains(&(b, a));
+            if !shared && next.insert(a, b).is_some() {
+                return None;
+            }
+        }
+        let start = *next.keys().next()?;
+        let mut merged = SmallVec::new();
+        let mut current = start;
+        loop {
+            merged.push(current);
+            current = *next.get(&current)?;
+            if current == start {
+                break;
+            }
+            if merged.len() > next.len() {
+                return None;
+            }
+        }
+        if merged.len() != next.len() {
+            return None;
+        }
+
+        let normal = self.polygon_normal(&merged)?;
+        for i in 0..merged.len() {
+            let p = na::Vector3::from(self.vertices[merged[i]]);
+            let previous =
+                p - na::Vector3::from(self.vertices[merged[(i + merged.len() - 1) % merged.len()]]);
+            let following = na::Vector3::from(self.vertices[merged[(i + 1) % merged.len()]]) - p;
+            let corner = previous.cross(&following);
+            let scale = previous.norm() * following.norm();
+            if corner.norm() <= sin_tolerance * scale {
+                // (Almost) straight.
+                continue;
+            }
+            if corner.dot(&normal) < cos_tolerance * corner.norm() {
+                return None;
+            }
+        }
+        Some((merged, normal))
+    }
+
+    /// Generates texture coordinates by projecting the vertices onto a
+    /// plane.
+    ///
+    /// Returns one entry per face corner, in the order of the faces and
+    /// their vertices (see [`flat_topology()`](Self::flat_topology())).
+    /// The coordinates are in the units of the mesh. For
+    /// [`UvMode::Triplanar`] the face normals are taken from
+    /// [`face_normals`](Self::face_normals) or computed, if absent.
+    pub fn generate_uvs(&self, mode: UvMode) -> Vec<[S; 2]> {
+        let mut uvs = Vec::with_capacity(self.faces.iter().map(|face| face.len()).sum());
+        for (face_index, face) in self.faces.iter().enumerate() {
+            let axis = match mode {
+                UvMode::Planar(axis) => axis as usize,
+                UvMode::Triplanar => {
+                    let n = match self.face_normals {
+                        Some(ref face_normals) => na::Vector3::from(face_normals[face_index]),
+                        None => self.polygon_normal(face).unwrap_or_else(na::Vector3::z),
+                    };
+                    n.iamax()
+                }
+            };
+            let (u, v) = match axis {
+                0 => (1, 2),
+                1 => (0, 2),
+                _ => (0, 1),
+            };
+            uvs.extend(
+                face.iter()
+                    .map(|&i| [self.vertices[i][u], self.vertices[i][v]]),
+            );
+        }
+        uvs
+    }
+
+    /// Returns true, if other is the same mesh as self up to the order
+    /// of vertices and faces, the rotation of each face and a difference
+    /// of at most `position_eps` per vertex coordinate.
+    ///
+    /// Each vertex of other is matched to the nearest unmatched vertex
+    /// of self within `position_eps`. Vertices closer together than
+    /// `position_eps` are only paired up wrongly, if they are offset
+    /// by more than their distance. `labels` and `face_normals` are
+    /// ignored.
+    pub fn approx_eq(&self, other: &Mesh<S>, position_eps: S) -> bool {
+        if self.vertices.len() != other.vertices.len() || self.faces.len() != other.faces.len() {
+            return false;
+        }
+        // Look up the vertices of self in a grid with spacing position_eps,
+        // so matches are in the 27 cells around a vertex of other.
+        let spacing = if position_eps > S::zero() {
+            position_eps
+        } else {
+            S::one()
+        };
+        let cell = |v: &[S; 3]| grid_cell(v, spacing);
+        let mut cells: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+        for (index, v) in self.vertices.iter().enumerate() {
+            cells.entry(cell(v)).or_default().push(index);
+        }
+        let mut matched = vec![false; self.vertices.len()];
+        // Map from the indices of other to the indices of self.
+        let mut mapping = vec![0; other.vertices.len()];
+        for (b, v) in other.vertices.iter().enumerate() {
+            // The nearest unmatched vertex and its squared distance.
+            let mut found: Option<(usize, S)> = None;
+            for neighbor in neighbor_cells(cell(v)) {
+                for &a in cells.get(&neighbor).into_iter().flatten() {
+                    let u = &self.vertices[a];
+                    if matched[a] || (0..3).any(|i| Float::abs(u[i] - v[i]) > position_eps) {
+                        continue;
+                    }
+                    let distance =
+                        (0..3).fold(S::zero(), |sum, i| sum + (u[i] - v[i]) * (u[i] - v[i]));
+                    let is_nearer = match found {
+                        Some((_, nearest)) => distance < nearest,
+                        None => true,
+                    };
+                    if is_nearer {
+                        found = Some((a, distance));
+                    }
+                }
+            }
+            match found {
+                Some((a, _)) => {
+                    matched[a] = true;
+                    mapping[b] = a;
+                }
+                None => return false,
+            }
+        }
+        // Rotate each face, so it starts at its lowest index, and sort.
+        let canonical = |faces: &mut Vec<SmallVec<[usize; 4]>>| {
+            for face in faces.iter_mut() {
+                if let Some(first) = (0..face.len()).min_by_key(|&i| face[i]) {
+                    face.rotate_left(first);
+                }
+            }
+            faces.sort_unstable();
+        };
+        let mut our_faces = self.faces.clone();
+        let mut their_faces: Vec<SmallVec<[usize; 4]>> = other
+            .faces
+            .iter()
+            .map(|face| face.iter().map(|&i| mapping[i]).collect())
+            .collect();
+        canonical(&mut our_faces);
+        canonical(&mut their_faces);
+        our_faces == their_faces
+    }
+
+    /// Merges vertices, which fall into the same cell of a grid with
+    /// spacing `epsilon`, and returns the number of vertices removed.
+    ///
+    /// Each cell keeps its vertex with the lowest index, so the result
+    /// does not depend on the number of threads. Vertices closer than
+    /// `epsilon`, but in neighboring cells, are not merged. The order of
+    /// the remaining vertices is kept, together with their
+    /// `vertex_colors`. Faces collapsing to less than three vertices are
+    /// removed, together with their `labels` and `face_normals`. A
+    /// non-positive `epsilon` merges nothing.
+    pub fn weld(&mut self, epsilon: S) -> usize {
+        if epsilon <= S::zero() {
+            return 0;
+        }
+        let cell = |v: &[S; 3]| grid_cell(v, epsilon);
+        let representatives: HashMap<[i64; 3], usize> = self
+            .vertices
+            .par_iter()
+            .enumerate()
+            .fold(HashMap::new, |mut cells, (index, v)| {
+                // Indices only increase within a fold, keep the first.
+                cells.entry(cell(v)).or_insert(index);
+                cells
+            })
+            .reduce(HashMap::new, |mut a, b| {
+                for (cell, index) in b {
+                    let entry = a.entry(cell).or_insert(index);
+                    *entry = (*entry).min(index);
+                }
+                a
+            });
+
+        // Map each vertex to the new index of its representative.
+        let mut new_indices = vec![0; self.vertices.len()];
+        let mut num_kept = 0;
+        for index in 0..self.vertices.len() {
+            let representative = representatives[&cell(&self.vertices[index])];
+            if representative == index {
+                new_indices[index] = num_kept;
+                self.vertices[num_kept] = self.vertices[index];
+                if let Some(ref mut colors) = self.vertex_colors {
+                    colors[num_kept] = colors[index];
+                }
+                num_kept += 1;
+            } else {
+                new_indices[index] = new_indices[representative];
+            }
+        }
+        let num_removed = self.vertices.len() - num_kept;
+        self.vertices.truncate(num_kept);
+        if let Some(ref mut colors) = self.vertex_colors {
+            colors.truncate(num_kept);
+        }
+
+        self.faces.par_iter_mut().for_each(|face| {
+            for index in face.iter_mut() {
+                *index = new_indices[*index];
+            }
+            face.dedup();
+            while face.len() > 1 && face.first() == face.last() {
+                face.pop();
+            }
+        });
+        let keep: Vec<bool> = self.faces.iter().map(|face| face.len() >= 3).collect();
+        self.retain_faces(&keep);
+        num_removed
+    }
+
+    /// Runs the passes enabled in options, in the order weld, remove
+    /// degenerate faces, remove duplicate faces, make the winding
+    /// consistent and split non-manifold vertices, and reports what each
+    /// pass changed.
+    ///
+    /// Welding can create degenerate and duplicate faces, which would
+    /// confuse the orientation of their neighbors, so they are removed
+    /// before the winding is made consistent.
+    pub fn repair(&mut self, options: RepairOptions<S>) -> RepairReport {
+        let mut report = RepairReport::default();
+        if let Some(epsilon) = options.weld_epsilon {
+            report.num_welded_vertices = self.weld(epsilon);
+        }
+        if options.remove_degenerate_faces {
+            report.num_degenerate_faces = self.remove_degenerate_faces();
+        }
+        if options.remove_duplicate_faces {
+            report.num_duplicate_faces = self.remove_duplicate_faces();
+        }
+        if options.make_winding_consistent {
+            report.num_reversed_faces = self.make_winding_consistent();
+        }
+        if options.split_nonmanifold_vertices {
+            report.num_split_vertices = self.split_nonmanifold_vertices();
+        }
+        report
+    }
+
+    /// Stitches other onto self along the plane both were cut along and
+    /// returns the number of vertices merged.
+    ///
+    /// Each vertex of other within `eps` of the plane is merged with a
+    /// vertex of self within `eps` of it, that is also near the plane.
+    /// All other vertices of other and all its faces are appended to
+    /// self, leaving other empty. Vertices away from the plane are never
+    /// merged. `labels`, `face_normals` and `vertex_colors` are kept, if
+    /// both meshes have them.
+    pub fn weld_boundary(&mut self, other: &mut Mesh<S>, plane: Plane<S>, eps: S) -> usize {
+        let n = plane.n.normalize();
+        let near_plane = |v: &[S; 3]| Float::abs((na::Point3::from(*v) - plane.p).dot(&n)) <= eps;
+        let cell = |v: &[S; 3]| grid_cell(v, eps);
+        // Spatial hash of the vertices of self near the plane.
+        let mut cells: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+        for (index, v) in self.vertices.iter().enumerate() {
+            if near_plane(v) {
+                cells.entry(cell(v)).or_default().push(index);
+            }
+        }
+
+        let mut num_merged = 0;
+        let mut new_indices = Vec::with_capacity(other.vertices.len());
+        let mut vertex_colors = match (self.vertex_colors.take(), other.vertex_colors.take()) {
+            (Some(colors), Some(other_colors)) => Some((colors, other_colors)),
+            _ => None,
+        };
+        for (other_index, v) in other.vertices.drain(..).enumerate() {
+            let mut merged = None;
+            if near_plane(&v) {
+                let mut best = eps;
+                for neighbor in neighbor_cells(cell(&v)) {
+                    for &index in cells.get(&neighbor).into_iter().flatten() {
+                        let distance =
+                            (na::Point3::from(self.vertices[index]) - na::Point3::from(v)).norm();
+                        if distance <= best {
+                            best = distance;
+                            merged = Some(index);
+                        }
+                    }
+                }
+            }
+            new_indices.push(match merged {
+                Some(index) => {
+                    num_merged += 1;
+                    index
+                }
+                None => {
+                    self.vertices.push(v);
+                    if let Some((ref mut colors, ref other_colors)) = vertex_colors {
+                        colors.push(other_colors[other_index]);
+                    }
+                    self.vertices.len() - 1
+                }
+            });
+        }
+        self.vertex_colors = vertex_colors.map(|(colors, _)| colors);
+
+        self.labels = match (self.labels.take(), other.labels.take()) {
+            (Some(mut labels), Some(other_labels)) => {
+                labels.extend(other_labels);
+                Some(labels)
+            }
+            _ => None,
+        };
+        self.face_normals = match (self.face_normals.take(), other.face_normals.take()) {
+            (Some(mut face_normals), Some(other_face_normals)) => {
+                face_normals.extend(other_face_normals);
+                Some(face_normals)
+            }
+            _ => None,
+        };
+        self.faces.extend(
+            other
+                .faces
+                .drain(..)
+                .map(|face| face.iter().map(|&i| new_indices[i]).collect()),
+        );
+        num_merged
+    }
+
+    /// Cuts the mesh along `plane`, e.g. for a cross-section, and returns
+    /// the number of caps added.
+    ///
+    /// Faces on the side `plane.n` points to are removed, faces on the
+    /// other side are kept. Faces crossing the plane are cut, with new
+    /// vertices where their edges cross it, which neighboring faces share.
+    /// Cut faces keep their `labels` and `face_normals`, new vertices get
+    /// `vertex_colors` interpolated along their edge. All vertices are
+    /// kept, even unused ones.
+    ///
+    /// If `cap` is true, each closed loop of boundary edges on the plane
+    /// is closed like in [`fill_holes()`](Self::fill_holes()). This is only
+    /// valid for convex, or at least star-shaped, cross-sections.
+    pub fn clip(&mut self, plane: Plane<S>, cap: bool) -> usize {
+        let n = plane.n.normalize();
+        let distances: Vec<S> = self
+            .vertices
+            .iter()
+            .map(|v| (na::Point3::from(*v) - plane.p).dot(&n))
+            .collect();
+        // The vertices on the plane, including the new ones.
+        let mut on_plane: HashSet<usize> = (0..self.vertices.len())
+            .filter(|&i| distances[i] == S::zero())
+            .collect();
+        // Map from undirected edge to the vertex where it crosses the plane.
+        let mut crossings: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut keep = Vec::with_capacity(self.faces.len());
+        for face_index in 0..self.faces.len() {
+            let face = &self.faces[face_index];
+            if face.iter().all(|&i| distances[i] <= S::zero()) {
+                keep.push(true);
+                continue;
+            }
+            if face.iter().all(|&i| distances[i] >= S::zero()) {
+                keep.push(false);
+                continue;
+            }
+            let face = face.clone();
+            let mut clipped = SmallVec::new();
+            for (i, &a) in face.iter().enumerate() {
+                let b = face[(i + 1) % face.len()];
+                let (da, db) = (distances[a], distances[b]);
+                if da <= S::zero() {
+                    clipped.push(a);
+                }
+                if (da < S::zero() && db > S::zero()) || (da > S::zero() && db < S::zero()) {
+                    let vertices = &mut self.vertices;
+                    let colors = &mut self.vertex_colors;
+                    let crossing = *crossings.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                        let t = da / (da - db);
+                        let p = na::Vector3::from(vertices[a]) * (S::one() - t)
+                            + na::Vector3::from(vertices[b]) * t;
+                        vertices.push([p.x, p.y, p.z]);
+                        if let Some(ref mut colors) = colors {
+                            let t = t.to_f32().unwrap_or(0.5);
+                            let color =
+                                [0, 1, 2].map(|c| colors[a][c] * (1. - t) + colors[b][c] * t);
+                            colors.push(color);
+                        }
+                        vertices.len() - 1
+                    });
+                    on_plane.insert(crossing);
+                    clipped.push(crossing);
+                }
+            }
+            self.faces[face_index] = clipped;
+            keep.push(true);
+        }
+        self.retain_faces(&keep);
+        if !cap {
+            return 0;
+        }
+
+        let mut directed_edges = HashMap::new();
+        for (face_index, face) in self.faces.iter().enumerate() {
+            for (i, &a) in face.iter().enumerate() {
+                directed_edges.insert((a, face[(i + 1) % face.len()]), face_index);
+            }
+        }
+        let mut num_caps = 0;
+        for boundary_loop in self.boundary_loops() {
+            if !boundary_loop.iter().all(|i| on_plane.contains(i)) {
+                continue;
+            }
+            // Wind the cap opposite to the faces next to it.
+            let hole: Vec<(usize, usize)> = (0..boundary_loop.len())
+                .rev()
+                .map(|i| {
+                    let a = boundary_loop[i];
+                    let b = boundary_loop[(i + 1) % boundary_loop.len()];
+                    (b, directed_edges[&(a, b)])
+                })
+                .collect();
+            self.fill_hole(&hole);
+            num_caps += 1;
+        }
+        num_caps
+    }
+
+    /// Applies the affine transformation `m` to all vertices.
+    ///
+    /// If `m` mirrors the mesh, the winding of all faces is reversed, so
+    /// they keep facing outwards. [`face_normals`](Self::face_normals) are
+    /// transformed accordingly.
+    ///
+    /// # Panics
+    ///
+    /// If the mesh has `face_normals` and `m` is not invertible.
+    pub fn transform(&mut self, m: &na::Matrix4<S>) {
+        self.vertices.par_iter_mut().for_each(|v| {
+            let p = m.transform_point(&na::Point3::from(*v));
+            *v = [p.x, p.y, p.z];
+        });
+        self.transform_attributes(m);
+    }
+
+    /// Like [`transform()`](Self::transform()), but processes the vertices
+    /// in parallel chunks and skips the perspective division for affine
+    /// `m`, i.e. if its last row is `[0, 0, 0, 1]`.
+    ///
+    /// This amortizes scheduling the vertices over the threads. Run the
+    /// `transform` benchmarks to compare both for the mesh sizes and
+    /// the machine at hand.
+    ///
+    /// # Panics
+    ///
+    /// If the mesh has `face_normals` and `m` is not invertible.
+    pub fn transform_parallel(&mut self, m: &na::Matrix4<S>) {
+        const CHUNK_SIZE: usize = 4096;
+        let last_row = m.fixed_slice::<na::U1, na::U4>(3, 0);
+        if last_row != na::RowVector4::new(S::zero(), S::zero(), S::zero(), S::one()) {
+            self.transform(m);
+            return;
+        }
+        let linear = m.fixed_slice::<na::U3, na::U3>(0, 0).into_owned();
+        let translation = m.fixed_slice::<na::U3, na::U1>(0, 3).into_owned();
+        self.vertices.par_chunks_mut(CHUNK_SIZE).for_each(|chunk| {
+            for v in chunk {
+                let p = linear * na::Vector3::from(*v) + translation;
+                *v = [p.x, p.y, p.z];
+            }
+        });
+        self.transform_attributes(m);
+    }
+
+    // Transforms face_normals and fixes the winding after transforming the
+    // vertices by m.
+    fn transform_attributes(&mut self, m: &na::Matrix4<S>) {
+        if let Some(ref mut face_normals) = self.face_normals {
+            Self::transform_normals(m, face_normals);
+        }
+        if m.fixed_slice::<na::U3, na::U3>(0, 0).determinant() < S::zero() {
+            self.faces.par_iter_mut().for_each(|face| face.reverse());
+        }
+    }
+
+    /// Moves all vertices by `t`.
+    pub fn translate(&mut self, t: &na::Vector3<S>) {
+        self.transform(&na::Matrix4::new_translation(t));
+    }
+
+    /// Scales all vertices by `s` along the respective axes.
+    pub fn scale(&mut self, s: &na::Vector3<S>) {
+        self.transform(&na::Matrix4::new_nonuniform_scaling(s));
+    }
+
+    /// Rotates all vertices by `r` around the origin.
+    pub fn rotate(&mut self, r: &na::UnitQuaternion<S>) {
+        self.transform(&r.to_homogeneous());
+    }
+
+    /// Moves and uniformly scales the mesh, so the center of its bounds
+    /// is at the origin and its largest extent is 1.
+    ///
+    /// Returns the original center and the scale factor applied. Scaling
+    /// by its reciprocal and translating by the center restores the
+    /// mesh. Empty or degenerate meshes are only moved and the returned
+    /// scale is 1.
+    pub fn normalize(&mut self) -> (na::Point3<S>, S) {
+        let mut bbox = BoundingBox::neg_infinity();
+        for v in &self.vertices {
+            bbox.insert(&na::Point3::from(*v));
+        }
+        if self.vertices.is_empty() {
+            return (na::Point3::origin(), S::one());
+        }
+        let center = na::center(&bbox.min, &bbox.max);
+        let dim = bbox.dim();
+        let extent = Float::max(dim.x, Float::max(dim.y, dim.z));
+        let scale = if extent > S::zero() {
+            S::one() / extent
+        } else {
+            S::one()
+        };
+        self.transform(
+            &(na::Matrix4::new_scaling(scale) * na::Matrix4::new_translation(&-center.coords)),
+        );
+        (center, scale)
+    }
+
+    /// Moves each vertex by `lambda` times the offset to the mean of its
+    /// neighbors, `iterations` times.
+    ///
+    /// Neighbors are vertices sharing an edge of a face. This shrinks
+    /// the mesh, see [`smooth_taubin()`](Self::smooth_taubin()). Like all
+    /// smoothing it may pull vertices off the exact surface, so it is
+    /// never applied during tessellation.
+    /// [`face_normals`](Self::face_normals) are not updated.
+    pub fn smooth_laplacian(&mut self, lambda: S, iterations: usize) {
+        let neighbors = self.vertex_neighbors();
+        for _ in 0..iterations {
+            self.smooth_step(&neighbors, lambda);
+        }
+    }
+
+    /// Taubin λ|μ smoothing: each iteration does a step of
+    /// [`smooth_laplacian()`](Self::smooth_laplacian()) with `lambda`,
+    /// followed by one with `mu`.
+    ///
+    /// A negative `mu` with a slightly larger magnitude than `lambda`,
+    /// e.g. 0.5 and -0.53, undoes the shrinkage of the first step while
+    /// keeping the smoothing. Vertices may move off the exact surface and
+    /// [`face_normals`](Self::face_normals) are not updated.
+    pub fn smooth_taubin(&mut self, lambda: S, mu: S, iterations: usize) {
+        let neighbors = self.vertex_neighbors();
+        for _ in 0..iterations {
+            self.smooth_step(&neighbors, lambda);
+            self.smooth_step(&neighbors, mu);
+        }
+    }
+
+    // The sorted indices of the vertices sharing an edge with each vertex.
+    fn vertex_neighbors(&self) -> Vec<Vec<usize>> {
+        let mut neighbors = vec![Vec::new(); self.vertices.len()];
+        for face in &self.faces {
+            for (i, &a) in face.iter().enumerate() {
+                let b = face[(i + 1) % face.len()];
+                if a != b {
+                    neighbors[a].push(b);
+                    neighbors[b].push(a);
+                }
+            }
+        }
+        neighbors.par_iter_mut().for_each(|n| {
+            n.sort_unstable();
+            n.dedup();
+        });
+        neighbors
+    }
+
+    fn smooth_step(&mut self, neighbors: &[Vec<usize>], factor: S) {
+        let vertices = &self.vertices;
+        let smoothed: Vec<[S; 3]> = vertices
+            .par_iter()
+            .zip(neighbors)
+            .map(|(v, n)| {
+                if n.is_empty() {
+                    return *v;
+                }
+                let mut mean = na::Vector3::zeros();
+                for &i in n {
+                    mean += na::Vector3::from(vertices[i]);
+                }
+                let count = na::convert::<f64, S>(n.len() as f64);
+                let v = na::Vector3::from(*v);
+                (v + (mean / count - v) * factor).into()
+            })
+            .collect();
+        self.vertices = smoothed;
+    }
+
+    /// Transforms `normals` the way [`transform()`](Self::transform()) would
+    /// transform the surface they belong to, i.e. by the inverse transpose
+    /// of `m`. The results are normalized.
+    ///
+    /// # Panics
+    ///
+    /// If `m` is not invertible.
+    pub fn transform_normals(m: &na::Matrix4<S>, normals: &mut [[S; 3]]) {
+        let inverse_transpose = m
+            .fixed_slice::<na::U3, na::U3>(0, 0)
+            .into_owned()
+            .try_inverse()
+            .expect("transform is not invertible")
+            .transpose();
+        normals.par_iter_mut().for_each(|n| {
+            let n_t = (inverse_transpose * na::Vector3::from(*n)).normalize();
+            *n = [n_t.x, n_t.y, n_t.z];
+        });
+    }
+}
+
+#[cfg(feature = "polyhedron-ops")]
+impl<S: Clone + AsPrimitive<f32>> Mesh<S> {
+    /// Converts the mesh into a
+    /// [`Polyhedron`](polyhedron_ops::Polyhedron) with the given `name`.
+    ///
+    /// A `Polyhedron` does not store normals. Use
+    /// [`Polyhedron::normals()`](polyhedron_ops::Polyhedron::normals())
+    /// to compute them.
+    pub fn to_polyhedron(&self, name: &str) -> p_ops::Polyhedron {
+        p_ops::Polyhedron::from(
+            name,
+            self.vertices
+                .iter()
+                .map(|vertex| p_ops::Point::new(vertex[0].as_(), vertex[1].as_(), vertex[2].as_()))
+                .collect(),
+            self.faces
+                .iter()
+                .map(|face| face.iter().map(|index| *index as u32).collect())
+                .collect(),
+            None,
+        )
+    }
+}
+
+#[cfg(feature = "polyhedron-ops")]
+impl<S: Clone + AsPrimitive<f32>> From<Mesh<S>> for p_ops::Polyhedron {
+    fn from(mesh: Mesh<S>) -> p_ops::Polyhedron {
+        mesh.to_polyhedron("SDFMesh")
+    }
+}
+
+/// Triangle mesh that will be returned from
+/// [`TriangleMesh::from<Mesh>()`] or
+/// [`Mesh::to_triangle_mesh()`].
 #[derive(Clone, Debug, PartialEq)]
 pub struct TriangleMesh<S: Clone> {
     /// The list of vertices.
@@ -170,11 +2142,29 @@ pub struct TriangleMesh<S: Clone> {
 /// Converts
 impl<S: Clone> From<Mesh<S>> for TriangleMesh<S> {
     fn from(mesh: Mesh<S>) -> Self {
-        mesh.to_triangle_mesh()
+        mesh.into_triangle_mesh()
     }
 }
 
 impl<S: Clone> TriangleMesh<S> {
+    /// Iterate over the triangles, yielding the positions of each
+    /// triangle’s vertices.
+    pub fn iter_face_vertices(&self) -> impl Iterator<Item = [[S; 3]; 3]> + '_ {
+        self.faces.iter().map(move |face| {
+            [
+                self.vertices[face[0]].clone(),
+                self.vertices[face[1]].clone(),
+                self.vertices[face[2]].clone(),
+            ]
+        })
+    }
+
+    /// Returns the triangles as a soup without shared vertices, i.e. the
+    /// positions of each triangle’s vertices.
+    pub fn to_soup(&self) -> Vec<[[S; 3]; 3]> {
+        self.iter_face_vertices().collect()
+    }
+
     /// Returns the mesh’s topology as a flat buffer.
     ///
     /// Each triangle is represented by a group of three entries into
@@ -185,11 +2175,214 @@ impl<S: Clone> TriangleMesh<S> {
             .flat_map(|face| face.to_vec())
             .collect()
     }
+
+    /// Returns the mesh’s topology as a flat buffer of `u32`s, like
+    /// [`flat_topology()`](Self::flat_topology()).
+    ///
+    /// Fails, if there are more vertices than fit in a `u32`.
+    pub fn indices_u32(&self) -> Result<Vec<u32>, TryFromIntError> {
+        u32::try_from(self.vertices.len())?;
+        Ok(self
+            .faces
+            .par_iter()
+            .flat_map_iter(|face| face.iter().map(|&i| i as u32))
+            .collect())
+    }
+
+    /// Like [`indices_u32()`](Self::indices_u32()), but failing with a
+    /// [`TooManyVertices`] error like
+    /// [`flat_topology_u16()`](Self::flat_topology_u16()).
+    ///
+    /// Fails with the index of the last vertex, if there are more
+    /// vertices than fit in a `u32`.
+    pub fn flat_topology_u32(&self) -> Result<Vec<u32>, TooManyVertices> {
+        self.indices_u32().map_err(|_| TooManyVertices {
+            index: self.vertices.len() - 1,
+        })
+    }
+
+    /// Returns the mesh’s topology as a flat buffer of `u16`s, like
+    /// [`flat_topology()`](Self::flat_topology()), e.g. for a GPU index
+    /// buffer of a small mesh.
+    ///
+    /// Fails, if an index does not fit in a `u16`.
+    pub fn flat_topology_u16(&self) -> Result<Vec<u16>, TooManyVertices> {
+        self.flat_topology_as()
+    }
+
+    fn flat_topology_as<T: TryFrom<usize> + Send>(&self) -> Result<Vec<T>, TooManyVertices> {
+        self.faces
+            .par_iter()
+            .flat_map_iter(|face| face.iter())
+            .map(|&index| T::try_from(index).map_err(|_| TooManyVertices { index }))
+            .collect()
+    }
+
+    /// Export the mesh as an ASCII
+    /// [STL](https://en.wikipedia.org/wiki/STL_(file_format)) file, e.g.
+    /// for CAD or 3D printing. STL stores a soup of triangles, each with
+    /// its own vertices and its normal, see [`to_soup()`](Self::to_soup()).
+    ///
+    /// `winding` and `up_axis` work like for [`Mesh::to_obj()`].
+    #[cfg(feature = "stl")]
+    pub fn to_stl(
+        &self,
+        winding: impl Into<Winding>,
+        up_axis: UpAxis,
+    ) -> Result<Vec<u8>, Box<dyn Error>>
+    where
+        S: AsPrimitive<f32>,
+    {
+        let mut file = Vec::new();
+        self.write_stl(&mut file, winding, up_axis)?;
+        Ok(file)
+    }
+
+    /// Write the mesh as an ASCII
+    /// [STL](https://en.wikipedia.org/wiki/STL_(file_format)) file to
+    /// `writer`, see [`to_stl()`](Self::to_stl()).
+    #[cfg(feature = "stl")]
+    pub fn write_stl<W: Write>(
+        &self,
+        writer: &mut W,
+        winding: impl Into<Winding>,
+        up_axis: UpAxis,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        S: AsPrimitive<f32>,
+    {
+        writeln!(writer, "solid SDFMesh")?;
+        for [a, b, c] in self.soup_f32(winding.into(), up_axis) {
+            let [a, b, c] = [a, b, c].map(na::Vector3::from);
+            let n = (b - a)
+                .cross(&(c - a))
+                .try_normalize(0.)
+                .unwrap_or_else(na::Vector3::zeros)
+                // Add zero, so zeros are not written as -0.
+                .map(|x| x + 0.);
+            writeln!(writer, "facet normal {} {} {}", n.x, n.y, n.z)?;
+            writeln!(writer, "  outer loop")?;
+            for v in &[a, b, c] {
+                writeln!(writer, "    vertex {} {} {}", v.x, v.y, v.z)?;
+            }
+            writeln!(writer, "  endloop")?;
+            writeln!(writer, "endfacet")?;
+        }
+        writeln!(writer, "endsolid SDFMesh")?;
+        Ok(())
+    }
+
+    /// Export the mesh as a
+    /// [Wavefront OBJ](https://en.wikipedia.org/wiki/Wavefront_.obj_file)
+    /// file without shared vertices, i.e. with three vertices for each
+    /// triangle, see [`to_soup()`](Self::to_soup()).
+    ///
+    /// `winding` and `up_axis` work like for [`Mesh::to_obj()`].
+    #[cfg(feature = "obj")]
+    pub fn to_obj_soup(
+        &self,
+        winding: impl Into<Winding>,
+        up_axis: UpAxis,
+    ) -> Result<Vec<u8>, Box<dyn Error>>
+    where
+        S: AsPrimitive<f32>,
+    {
+        let mut file = Vec::new();
+        self.write_obj_soup(&mut file, winding, up_axis)?;
+        Ok(file)
+    }
+
+    /// Write the mesh as a
+    /// [Wavefront OBJ](https://en.wikipedia.org/wiki/Wavefront_.obj_file)
+    /// file without shared vertices to `writer`, see
+    /// [`to_obj_soup()`](Self::to_obj_soup()).
+    #[cfg(feature = "obj")]
+    pub fn write_obj_soup<W: Write>(
+        &self,
+        writer: &mut W,
+        winding: impl Into<Winding>,
+        up_axis: UpAxis,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        S: AsPrimitive<f32>,
+    {
+        writeln!(writer, "o SDFMesh")?;
+        let soup = self.soup_f32(winding.into(), up_axis);
+        for v in soup.iter().flatten() {
+            writeln!(writer, "v {} {} {}", v[0], v[1], v[2])?;
+        }
+        for i in 0..soup.len() {
+            writeln!(writer, "f {} {} {}", 3 * i + 1, 3 * i + 2, 3 * i + 3)?;
+        }
+        Ok(())
+    }
+
+    // The soup with reversed faces, if winding says so, and coordinates
+    // converted to up_axis.
+    #[cfg(any(feature = "obj", feature = "stl"))]
+    fn soup_f32(&self, winding: Winding, up_axis: UpAxis) -> Vec<[[f32; 3]; 3]>
+    where
+        S: AsPrimitive<f32>,
+    {
+        let reverse = winding.reverses(&self.vertices, self.faces.iter().map(|face| &face[..]));
+        self.faces
+            .iter()
+            .map(|face| {
+                let corner = |k: usize| {
+                    let v = &self.vertices[face[if reverse { 2 - k } else { k }]];
+                    up_axis.convert([v[0].as_(), v[1].as_(), v[2].as_()])
+                };
+                [corner(0), corner(1), corner(2)]
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "gpu")]
+impl<S: Clone + Sync + AsPrimitive<f32>> TriangleMesh<S> {
+    /// Returns the vertices with the given per-vertex `normals` packed
+    /// into a buffer of [`GpuVertex`]es, ready for uploading to a GPU
+    /// vertex buffer.
+    ///
+    /// The normals can be computed with
+    /// [`vertex_normals()`](Self::vertex_normals()). Panics, if there are
+    /// not as many normals as vertices.
+    pub fn to_interleaved_bytes(&self, normals: &[[S; 3]]) -> Vec<u8> {
+        assert_eq!(
+            normals.len(),
+            self.vertices.len(),
+            "got {} normals for {} vertices",
+            normals.len(),
+            self.vertices.len()
+        );
+        let vertices: Vec<GpuVertex> = self
+            .vertices
+            .par_iter()
+            .zip(normals.par_iter())
+            .map(|(v, n)| GpuVertex {
+                pos: [v[0].as_(), v[1].as_(), v[2].as_()],
+                normal: [n[0].as_(), n[1].as_(), n[2].as_()],
+            })
+            .collect();
+        bytemuck::cast_slice(&vertices).to_vec()
+    }
+
+    /// Returns the mesh’s topology as `u32`s packed into a buffer, ready
+    /// for uploading to a GPU index buffer matching
+    /// [`to_interleaved_bytes()`](Self::to_interleaved_bytes()).
+    ///
+    /// Fails, if an index does not fit in a `u32`.
+    pub fn index_bytes(&self) -> Result<Vec<u8>, TooManyVertices> {
+        Ok(bytemuck::cast_slice(&self.flat_topology_u32()?).to_vec())
+    }
 }
 
 impl<S: RealField + Debug> TriangleMesh<S> {
     /// Return the normal of the face at index `face` as triple of
     /// `f32`s.
+    ///
+    /// Panics, if `face` or one of its vertex indices is out of range,
+    /// see [`get_normal()`](Self::get_normal()).
     pub fn normal<T>(&self, face: usize) -> [T; 3]
     where
         S: Into<f32>,
@@ -209,18 +2402,392 @@ impl<S: RealField + Debug> TriangleMesh<S> {
         [r[0].into(), r[1].into(), r[2].into()]
     }
 
-    /// Return the vertics of the face at index `i` as triple of
-    /// `f32`s.
-    pub fn vertex<T>(&self, i: usize) -> [T; 3]
-    where
-        T: From<S>,
-    {
-        [
-            self.vertices[i][0].into(),
-            self.vertices[i][1].into(),
-            self.vertices[i][2].into(),
-        ]
+    /// Like [`normal()`](Self::normal()), but returns `None`, if `face`
+    /// or one of its vertex indices is out of range.
+    pub fn get_normal<T>(&self, face: usize) -> Option<[T; 3]>
+    where
+        S: Into<f32>,
+        T: From<f32>,
+    {
+        let face = self.faces.get(face)?;
+        if face.iter().any(|&i| i >= self.vertices.len()) {
+            return None;
+        }
+        let v = face.map(|i| {
+            na::Point3::<f32>::new(
+                self.vertices[i][0].into(),
+                self.vertices[i][1].into(),
+                self.vertices[i][2].into(),
+            )
+        });
+        let r = (v[1] - v[0]).cross(&(v[2] - v[0])).normalize();
+        Some([r[0].into(), r[1].into(), r[2].into()])
+    }
+
+    /// Returns the vertex positions as a tightly packed buffer of
+    /// `f32`s, i.e. `x, y, z` of each vertex in order, ready for
+    /// uploading to a GPU vertex buffer.
+    pub fn positions_f32(&self) -> Vec<f32>
+    where
+        S: Into<f32>,
+    {
+        self.vertices
+            .par_iter()
+            .flat_map_iter(|v| v.iter().map(|&c| c.into()))
+            .collect()
+    }
+
+    /// Return the vertics of the face at index `i` as triple of
+    /// `f32`s.
+    ///
+    /// Panics, if `i` is out of range, see
+    /// [`get_vertex()`](Self::get_vertex()).
+    pub fn vertex<T>(&self, i: usize) -> [T; 3]
+    where
+        T: From<S>,
+    {
+        [
+            self.vertices[i][0].into(),
+            self.vertices[i][1].into(),
+            self.vertices[i][2].into(),
+        ]
+    }
+
+    /// Like [`vertex()`](Self::vertex()), but returns `None`, if `i` is
+    /// out of range.
+    pub fn get_vertex<T>(&self, i: usize) -> Option<[T; 3]>
+    where
+        T: From<S>,
+    {
+        let v = self.vertices.get(i)?;
+        Some([v[0].into(), v[1].into(), v[2].into()])
+    }
+}
+
+impl<S: RealField + Float + Debug> TriangleMesh<S> {
+    /// Builds a bounding volume hierarchy over the triangles for fast
+    /// ray queries.
+    pub fn build_bvh(&self) -> Bvh<S> {
+        Bvh::new(self)
+    }
+
+    /// Returns the distance from `p` to the closest point on the mesh,
+    /// negative if `p` is inside.
+    ///
+    /// See [`DistanceQuery::signed_distance()`]. This builds the query
+    /// for a single point, use [`distance_query()`](Self::distance_query())
+    /// for more points.
+    pub fn signed_distance(&self, p: [S; 3]) -> S {
+        self.distance_query().signed_distance(p)
+    }
+
+    /// Builds a [`DistanceQuery`] for signed distances to the mesh.
+    pub fn distance_query(&self) -> DistanceQuery<S> {
+        DistanceQuery {
+            bvh: self.build_bvh(),
+            half_edges: HalfEdgeMesh::new(self.vertices.clone(), &self.faces),
+        }
+    }
+
+    /// Returns a normal for each vertex, computed from the normals of
+    /// the faces using it, weighted by `weighting`.
+    ///
+    /// This only needs the geometry, e.g. if
+    /// [`ImplicitFunction::normal()`](crate::ImplicitFunction::normal())
+    /// is unavailable or imprecise. Faces without area are ignored and
+    /// vertices without faces get a zero normal.
+    pub fn vertex_normals(&self, weighting: NormalWeighting) -> Vec<[S; 3]> {
+        let point = |i: usize| na::Point3::from(self.vertices[i]);
+        let zeros = || vec![na::Vector3::zeros(); self.vertices.len()];
+        let sums = self
+            .faces
+            .par_iter()
+            .fold(zeros, |mut sums, face| {
+                let [a, b, c] = [point(face[0]), point(face[1]), point(face[2])];
+                // Twice the area times the unit normal.
+                let cross = (b - a).cross(&(c - a));
+                let area = cross.norm();
+                if area > S::zero() {
+                    let normal = cross / area;
+                    for k in 0..3 {
+                        let weight = match weighting {
+                            NormalWeighting::Uniform => S::one(),
+                            NormalWeighting::ByArea => area,
+                            NormalWeighting::ByAngle => {
+                                let vertex = point(face[k]);
+                                (point(face[(k + 1) % 3]) - vertex)
+                                    .angle(&(point(face[(k + 2) % 3]) - vertex))
+                            }
+                        };
+                        sums[face[k]] += normal * weight;
+                    }
+                }
+                sums
+            })
+            .reduce(zeros, |mut a, b| {
+                for (a, b) in a.iter_mut().zip(b) {
+                    *a += b;
+                }
+                a
+            });
+        sums.into_par_iter()
+            .map(|sum| {
+                let norm = sum.norm();
+                if norm > S::zero() {
+                    (sum / norm).into()
+                } else {
+                    [S::zero(); 3]
+                }
+            })
+            .collect()
+    }
+
+    /// Estimates the mean and the Gaussian curvature at each vertex from
+    /// the triangles around it, e.g. for adaptive remeshing or to color
+    /// the surface.
+    ///
+    /// The mean curvature is half the length of the cotangent Laplacian
+    /// of the positions, positive where the surface is convex, i.e. bends
+    /// away from the direction the faces point to. The Gaussian
+    /// curvature is the angle defect, 2π minus the sum of the angles of
+    /// the triangles at the vertex. Both are divided by a third of the
+    /// area of the triangles around the vertex. For a sphere of radius r
+    /// they approach 1 / r and 1 / r². Vertices on the boundary of the
+    /// mesh or without triangles get NaN for both.
+    pub fn curvatures(&self) -> (Vec<S>, Vec<S>) {
+        let point = |i: usize| na::Point3::from(self.vertices[i]);
+        // Per vertex the cotangent weighted sum of the edges to its
+        // neighbors, the area weighted normal, the sum of the angles and
+        // a third of the area around it.
+        let zeros = || {
+            vec![
+                (
+                    na::Vector3::zeros(),
+                    na::Vector3::zeros(),
+                    S::zero(),
+                    S::zero()
+                );
+                self.vertices.len()
+            ]
+        };
+        let three = na::convert::<f64, S>(3.);
+        let two = na::convert::<f64, S>(2.);
+        let sums = self
+            .faces
+            .par_iter()
+            .fold(zeros, |mut sums, face| {
+                let p = [point(face[0]), point(face[1]), point(face[2])];
+                let cross = (p[1] - p[0]).cross(&(p[2] - p[0]));
+                let double_area = cross.norm();
+                if double_area > S::zero() {
+                    for k in 0..3 {
+                        let (i, j) = ((k + 1) % 3, (k + 2) % 3);
+                        let (u, v) = (p[i] - p[k], p[j] - p[k]);
+                        let cot = u.dot(&v) / double_area;
+                        let sum = &mut sums[face[i]];
+                        sum.0 += (p[j] - p[i]) * (cot / two);
+                        let sum = &mut sums[face[j]];
+                        sum.0 += (p[i] - p[j]) * (cot / two);
+                        let sum = &mut sums[face[k]];
+                        sum.1 += cross;
+                        sum.2 += u.angle(&v);
+                        sum.3 += double_area / two / three;
+                    }
+                }
+                sums
+            })
+            .reduce(zeros, |mut a, b| {
+                for (a, b) in a.iter_mut().zip(b) {
+                    a.0 += b.0;
+                    a.1 += b.1;
+                    a.2 += b.2;
+                    a.3 += b.3;
+                }
+                a
+            });
+
+        let mut boundary = vec![false; self.vertices.len()];
+        let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+        for face in &self.faces {
+            for k in 0..3 {
+                let (a, b) = (face[k], face[(k + 1) % 3]);
+                *edges.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+        }
+        for (&(a, b), &count) in &edges {
+            if count == 1 {
+                boundary[a] = true;
+                boundary[b] = true;
+            }
+        }
+
+        let two_pi = <S as na::RealField>::two_pi();
+        sums.into_iter()
+            .zip(boundary)
+            .map(|((laplacian, normal, angles, area), boundary)| {
+                if boundary || area <= S::zero() {
+                    return (S::nan(), S::nan());
+                }
+                let normal = normal
+                    .try_normalize(S::zero())
+                    .unwrap_or_else(na::Vector3::zeros);
+                (
+                    -laplacian.dot(&normal) / (two * area),
+                    (two_pi - angles) / area,
+                )
+            })
+            .unzip()
+    }
+}
+
+/// Signed distances to a [`TriangleMesh`], returned from
+/// [`TriangleMesh::distance_query()`].
+///
+/// It keeps a [`Bvh`] and the adjacency of the triangles, so a query
+/// only visits the triangles near the point. Like the BVH, it copies
+/// the mesh.
+#[derive(Clone, Debug)]
+pub struct DistanceQuery<S: RealField + Float + Debug> {
+    bvh: Bvh<S>,
+    half_edges: HalfEdgeMesh<S>,
+}
+
+impl<S: RealField + Float + Debug> DistanceQuery<S> {
+    /// Returns the distance from `p` to the closest point on the mesh,
+    /// negative if `p` is inside.
+    ///
+    /// The sign is taken from the angle-weighted pseudonormal of the
+    /// closest feature, i.e. of the face, edge or vertex the closest
+    /// point lies on, so it does not flip near edges and corners. This
+    /// requires a closed, consistently oriented mesh. Returns infinity if
+    /// the mesh has no faces.
+    pub fn signed_distance(&self, p: [S; 3]) -> S {
+        let p = na::Point3::from(p);
+        let (face_index, q) = match self.bvh.closest_point(&p) {
+            Some(closest) => closest,
+            None => return <S as Float>::infinity(),
+        };
+        let mesh = &self.half_edges;
+        let point = |i: usize| na::Point3::from(mesh.vertices[i]);
+        // The half-edges of a triangle start at its vertices in order.
+        let triangle = |face: usize| {
+            let first = mesh.face_half_edges[face];
+            [0, 1, 2].map(|i| mesh.half_edges[first + i].vertex)
+        };
+        let face_normal = |face: usize| {
+            let [a, b, c] = triangle(face).map(point);
+            (b - a).cross(&(c - a)).normalize()
+        };
+
+        // The corners of the closest face with a non-zero weight at q.
+        let face = triangle(face_index);
+        let [a, b, c] = face.map(point);
+        let area = (b - a).cross(&(c - a)).norm();
+        let tolerance = Float::sqrt(<S as Float>::epsilon()) * area;
+        let corners: SmallVec<[usize; 3]> = (0..3)
+            .filter(|&i| {
+                // Twice the area of the triangle q forms with the other
+                // two corners, proportional to the weight of corner i.
+                let (u, v) = (point(face[(i + 1) % 3]), point(face[(i + 2) % 3]));
+                (u - q).cross(&(v - q)).norm() > tolerance
+            })
+            .map(|i| face[i])
+            .collect();
+        let pseudonormal: na::Vector3<S> = match corners.len() {
+            // On a vertex: the normals of its faces, weighted by their
+            // angle at the vertex.
+            1 => mesh
+                .vertex_faces(corners[0])
+                .into_iter()
+                .map(|face| {
+                    let vertices = triangle(face);
+                    let k = vertices.iter().position(|&i| i == corners[0]).unwrap();
+                    let vertex = point(vertices[k]);
+                    let angle = (point(vertices[(k + 1) % 3]) - vertex)
+                        .angle(&(point(vertices[(k + 2) % 3]) - vertex));
+                    face_normal(face) * angle
+                })
+                .fold(na::Vector3::zeros(), |sum, n| sum + n),
+            // On an edge: the normals of both faces.
+            2 => {
+                mesh.face_half_edges(face_index)
+                    .find(|&half_edge| {
+                        let edge = [
+                            mesh.half_edges[half_edge].vertex,
+                            mesh.destination(half_edge),
+                        ];
+                        edge.contains(&corners[0]) && edge.contains(&corners[1])
+                    })
+                    .and_then(|half_edge| mesh.half_edges[half_edge].twin)
+                    .map_or(na::Vector3::zeros(), |twin| {
+                        face_normal(mesh.half_edges[twin].face)
+                    })
+                    + face_normal(face_index)
+            }
+            _ => face_normal(face_index),
+        };
+        let distance = (p - q).norm();
+        if (p - q).dot(&pseudonormal) < S::zero() {
+            -distance
+        } else {
+            distance
+        }
+    }
+}
+
+// The cell of a grid with the given spacing, that contains v. Non-finite
+// coordinates all land in the same cell.
+pub(crate) fn grid_cell<S: RealField + Float>(v: &[S; 3], spacing: S) -> [i64; 3] {
+    v.map(|x| Float::floor(x / spacing).to_i64().unwrap_or(i64::MAX))
+}
+
+// The cell of a grid and the 26 cells around it.
+pub(crate) fn neighbor_cells(cell: [i64; 3]) -> impl Iterator<Item = [i64; 3]> {
+    (0..27).map(move |i| {
+        let offset = [i % 3 - 1, i / 3 % 3 - 1, i / 9 - 1];
+        [0, 1, 2].map(|axis| cell[axis].saturating_add(offset[axis]))
+    })
+}
+
+// Splits face into triangles, quads along the diagonal from their
+// first vertex and other polygons as a fan around their first vertex.
+pub(crate) fn triangulate(face: &[usize]) -> SmallVec<[[usize; 3]; 2]> {
+    if 4 == face.len() {
+        SmallVec::from_buf([[face[0], face[1], face[2]], [face[2], face[3], face[0]]])
+    } else {
+        (1..face.len() - 1)
+            .map(|i| [face[0], face[i], face[i + 1]])
+            .collect()
+    }
+}
+
+// The signed volume enclosed by faces, positive if they face outwards.
+#[cfg(any(feature = "obj", feature = "ply", feature = "stl"))]
+fn signed_volume_f32<'f, S: AsPrimitive<f32>>(
+    vertices: &[[S; 3]],
+    faces: impl Iterator<Item = &'f [usize]>,
+) -> f32 {
+    let position = |i: usize| {
+        let v = &vertices[i];
+        na::Vector3::new(v[0].as_(), v[1].as_(), v[2].as_())
+    };
+    let mut volume = 0.;
+    for face in faces {
+        for i in 1..face.len().saturating_sub(1) {
+            volume += position(face[0]).dot(&position(face[i]).cross(&position(face[i + 1])));
+        }
     }
+    volume / 6.
+}
+
+// Returns the root of i in the union-find forest parents, compressing
+// the path on the way.
+fn find_root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
 }
 
 #[cfg(test)]
@@ -237,6 +2804,16 @@ mod test {
         true
     }
 
+    fn f64slice_eq(a: &[f64], b: &[f64]) -> bool {
+        assert_eq!(a.len(), b.len());
+        for i in 0..a.len() {
+            if (a[i] - b[i]).abs() > 1e-12 {
+                return false;
+            }
+        }
+        true
+    }
+
     #[test]
     fn simple() {
         let m = TriangleMesh {
@@ -248,4 +2825,1464 @@ mod test {
         assert!(f32slice_eq(&m.vertex::<f32>(1), &[1., 0., 0.]));
         assert!(f32slice_eq(&m.vertex::<f32>(2), &[0., 1., 0.]));
     }
+
+    #[test]
+    fn to_soup() {
+        let m = TriangleMesh {
+            vertices: vec![[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [1., 1., 0.]],
+            faces: vec![[0, 1, 2], [2, 1, 3]],
+        };
+        assert_eq!(
+            m.to_soup(),
+            vec![
+                [[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]],
+                [[0., 1., 0.], [1., 0., 0.], [1., 1., 0.]],
+            ]
+        );
+    }
+
+    #[cfg(feature = "stl")]
+    #[test]
+    fn write_stl() {
+        let m = TriangleMesh {
+            vertices: vec![[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [1., 1., 0.]],
+            faces: vec![[0, 1, 2], [2, 1, 3]],
+        };
+        assert_eq!(
+            String::from_utf8(m.to_stl(false, UpAxis::ZUp).unwrap()).unwrap(),
+            "solid SDFMesh\n\
+             facet normal 0 0 1\n  outer loop\n    vertex 0 0 0\n    vertex 1 0 0\n    \
+             vertex 0 1 0\n  endloop\nendfacet\n\
+             facet normal 0 0 1\n  outer loop\n    vertex 0 1 0\n    vertex 1 0 0\n    \
+             vertex 1 1 0\n  endloop\nendfacet\n\
+             endsolid SDFMesh\n"
+        );
+        let mut stl = Vec::new();
+        m.write_stl(&mut stl, true, UpAxis::YUp).unwrap();
+        let stl = String::from_utf8(stl).unwrap();
+        assert!(stl.starts_with(
+            "solid SDFMesh\nfacet normal 0 -1 0\n  outer loop\n    vertex 0 0 -1\n    \
+             vertex 1 0 0\n    vertex 0 0 0\n"
+        ));
+    }
+
+    #[cfg(feature = "obj")]
+    #[test]
+    fn write_obj_soup() {
+        let m = TriangleMesh {
+            vertices: vec![[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [1., 1., 0.]],
+            faces: vec![[0, 1, 2], [2, 1, 3]],
+        };
+        assert_eq!(
+            String::from_utf8(m.to_obj_soup(false, UpAxis::ZUp).unwrap()).unwrap(),
+            "o SDFMesh\nv 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 1 0\nv 1 0 0\nv 1 1 0\nf 1 2 3\nf 4 5 6\n"
+        );
+    }
+
+    #[cfg(feature = "gpu")]
+    #[test]
+    fn interleaved_bytes() {
+        let m = TriangleMesh {
+            vertices: vec![[0f64, 0., 0.], [1., 0., 0.], [0., 1., 0.]],
+            faces: vec![[0, 1, 2]],
+        };
+        let normals = m.vertex_normals(NormalWeighting::Uniform);
+        let bytes = m.to_interleaved_bytes(&normals);
+        assert_eq!(bytes.len(), 3 * std::mem::size_of::<GpuVertex>());
+        assert_eq!(std::mem::size_of::<GpuVertex>(), 24);
+        let floats: Vec<f32> = bytes
+            .chunks(4)
+            .map(|c| f32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+            .collect();
+        assert!(f32slice_eq(
+            &floats,
+            &[0., 0., 0., 0., 0., 1., 1., 0., 0., 0., 0., 1., 0., 1., 0., 0., 0., 1.]
+        ));
+        let indices: Vec<u32> = m
+            .index_bytes()
+            .unwrap()
+            .chunks(4)
+            .map(|c| u32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+            .collect();
+        assert_eq!(indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn bounds_checked_access() {
+        let m = TriangleMesh {
+            vertices: vec![[0f32, 0., 0.], [1., 0., 0.], [0., 1., 0.]],
+            faces: vec![[0, 1, 2], [0, 1, 3]],
+        };
+        assert!(f32slice_eq(&m.get_normal::<f32>(0).unwrap(), &[0., 0., 1.]));
+        assert_eq!(m.get_normal::<f32>(1), None);
+        assert_eq!(m.get_normal::<f32>(2), None);
+        assert!(f32slice_eq(&m.get_vertex::<f32>(1).unwrap(), &[1., 0., 0.]));
+        assert_eq!(m.get_vertex::<f32>(3), None);
+    }
+
+    #[test]
+    fn empty() {
+        let mut m: Mesh<f64> = Mesh {
+            vertices: Vec::new(),
+            faces: Vec::new(),
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        let t = m.to_triangle_mesh();
+        assert!(t.vertices.is_empty() && t.faces.is_empty());
+        assert_eq!(t.flat_topology(), Vec::<usize>::new());
+        assert_eq!(t.flat_topology_u16(), Ok(Vec::new()));
+        assert_eq!(t.indices_u32(), Ok(Vec::new()));
+        assert!(t.vertex_normals(NormalWeighting::ByAngle).is_empty());
+        assert!(t
+            .build_bvh()
+            .raycast(&na::Point3::origin(), &na::Vector3::x())
+            .is_none());
+        assert_eq!(t.signed_distance([0., 0., 0.]), f64::INFINITY);
+        let t32: TriangleMesh<f32> = TriangleMesh {
+            vertices: Vec::new(),
+            faces: Vec::new(),
+        };
+        assert_eq!(t32.get_normal::<f32>(0), None);
+        assert!(t32.positions_f32().is_empty());
+        assert_eq!(t.get_vertex::<f64>(0), None);
+        assert_eq!(m.iter_face_vertices().count(), 0);
+        assert_eq!(m.flat_topology(), (Vec::new(), Vec::new()));
+        assert!(m.to_half_edge().half_edges.is_empty());
+        assert!(m.split_connected_components().is_empty());
+        assert_eq!(m.closest_point([0., 0., 0.]), None);
+        assert!(m.generate_uvs(UvMode::Triplanar).is_empty());
+        assert!(m.approx_eq(&m.clone(), 0.));
+        m.merge_coplanar_faces(0.1);
+        assert_eq!(m.make_winding_consistent(), 0);
+        assert_eq!(m.fill_holes(4), 0);
+        assert_eq!(m.weld(0.1), 0);
+        assert_eq!(m.split_nonmanifold_vertices(), 0);
+        assert!(m.boundary_edges().is_empty() && m.boundary_loops().is_empty());
+        assert_eq!(m.normalize(), (na::Point3::origin(), 1.));
+        m.smooth_taubin(0.5, -0.53, 2);
+        m.transform(&na::Matrix4::new_scaling(2.));
+        assert!(m.vertices.is_empty() && m.faces.is_empty());
+        #[cfg(feature = "obj")]
+        assert_eq!(
+            String::from_utf8(m.to_obj(false, UpAxis::ZUp).unwrap()).unwrap(),
+            "o SDFMesh\n"
+        );
+    }
+
+    #[test]
+    fn gpu_buffers() {
+        let m = TriangleMesh {
+            vertices: vec![[0f32, 0., 0.], [1., 0., 0.], [0., 1., 0.], [1., 1., 0.]],
+            faces: vec![[0, 1, 2], [2, 1, 3]],
+        };
+        assert!(f32slice_eq(
+            &m.positions_f32(),
+            &[0., 0., 0., 1., 0., 0., 0., 1., 0., 1., 1., 0.]
+        ));
+        assert_eq!(m.indices_u32().unwrap(), &[0, 1, 2, 2, 1, 3]);
+        assert_eq!(m.flat_topology_u32().unwrap(), &[0, 1, 2, 2, 1, 3]);
+        assert_eq!(m.flat_topology_u16().unwrap(), &[0, 1, 2, 2, 1, 3]);
+        let large = TriangleMesh {
+            vertices: Vec::<[f32; 3]>::new(),
+            faces: vec![[0, 1, 2], [2, 1, 70000]],
+        };
+        assert_eq!(
+            large.flat_topology_u16(),
+            Err(TooManyVertices { index: 70000 })
+        );
+        assert_eq!(large.flat_topology_u32().unwrap()[5], 70000);
+        assert_eq!(
+            m.indices_u32().unwrap(),
+            m.flat_topology()
+                .iter()
+                .map(|&i| i as u32)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn iter_face_vertices() {
+        let m = Mesh {
+            vertices: vec![[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]],
+            faces: vec![
+                SmallVec::from_slice(&[0, 1, 2, 3]),
+                SmallVec::from_slice(&[0, 2, 3]),
+            ],
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        let faces: Vec<_> = m.iter_face_vertices().collect();
+        assert_eq!(faces.len(), 2);
+        assert_eq!(faces[0].len(), 4);
+        assert_eq!(faces[0][2], [1., 1., 0.]);
+        assert_eq!(
+            faces[1].as_slice(),
+            &[[0., 0., 0.], [1., 1., 0.], [0., 1., 0.]]
+        );
+
+        let triangles: Vec<_> = m.to_triangle_mesh().iter_face_vertices().collect();
+        assert_eq!(triangles.len(), 3);
+        assert_eq!(triangles[1], [[1., 1., 0.], [0., 1., 0.], [0., 0., 0.]]);
+
+        let pentagon = Mesh {
+            vertices: vec![[0., 0., 0.]; 5],
+            faces: vec![SmallVec::from_slice(&[0, 1, 2, 3, 4])],
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        assert_eq!(
+            pentagon.to_triangle_mesh().faces,
+            vec![[0, 1, 2], [0, 2, 3], [0, 3, 4]]
+        );
+        let triangle_mesh = m.to_triangle_mesh();
+        assert_eq!(m.into_triangle_mesh(), triangle_mesh);
+    }
+
+    #[test]
+    fn closest_point() {
+        let m = Mesh {
+            vertices: vec![
+                [0., 0., 0.],
+                [1., 0., 0.],
+                [1., 1., 0.],
+                [0., 1., 0.],
+                [0., 0., 5.],
+                [1., 0., 5.],
+                [0., 1., 5.],
+            ],
+            faces: vec![
+                SmallVec::from_slice(&[0, 1, 2, 3]),
+                SmallVec::from_slice(&[4, 5, 6]),
+            ],
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        assert_eq!(
+            m.closest_point([0.25, 0.75, 1.]),
+            Some(([0.25, 0.75, 0.], 0))
+        );
+        assert_eq!(m.closest_point([2., 0.5, -1.]), Some(([1., 0.5, 0.], 0)));
+        assert_eq!(m.closest_point([-1., -1., 6.]), Some(([0., 0., 5.], 1)));
+        let empty = Mesh::<f64> {
+            vertices: Vec::new(),
+            faces: Vec::new(),
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        assert_eq!(empty.closest_point([0., 0., 0.]), None);
+    }
+
+    #[test]
+    fn transform() {
+        let mut m = Mesh {
+            vertices: vec![[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]],
+            faces: vec![SmallVec::from_slice(&[0, 1, 2])],
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        m.scale(&na::Vector3::new(2., 3., 4.));
+        m.translate(&na::Vector3::new(1., 1., 1.));
+        assert_eq!(m.vertices, vec![[1., 1., 1.], [3., 1., 1.], [1., 4., 1.]]);
+        assert_eq!(m.faces[0].as_slice(), &[0, 1, 2]);
+
+        m.rotate(&na::UnitQuaternion::from_axis_angle(
+            &na::Vector3::z_axis(),
+            std::f64::consts::FRAC_PI_2,
+        ));
+        assert!(f64slice_eq(&m.vertices[1], &[-1., 3., 1.]));
+
+        // Mirroring reverses the winding.
+        m.scale(&na::Vector3::new(-1., 1., 1.));
+        assert_eq!(m.faces[0].as_slice(), &[2, 1, 0]);
+
+        let mut normals = [[0., 0., 1.], [1., 0., 0.]];
+        Mesh::transform_normals(
+            &na::Matrix4::new_nonuniform_scaling(&na::Vector3::new(2., 1., 1.)),
+            &mut normals,
+        );
+        assert_eq!(normals, [[0., 0., 1.], [1., 0., 0.]]);
+
+        // transform_parallel() yields the same result, also for projective
+        // transformations.
+        let mut affine = na::Matrix4::new_rotation(na::Vector3::new(0.1, 0.2, 0.3));
+        affine[(0, 3)] = 2.;
+        let mut projective = affine;
+        projective[(3, 2)] = 0.5;
+        for matrix in &[affine, projective, -na::Matrix4::identity()] {
+            let mut expected = m.clone();
+            expected.face_normals = Some(vec![[0., 0., 1.]]);
+            let mut parallel = expected.clone();
+            expected.transform(matrix);
+            parallel.transform_parallel(matrix);
+            assert!(parallel.approx_eq(&expected, 1e-12));
+            assert!(f64slice_eq(
+                &parallel.face_normals.unwrap()[0],
+                &expected.face_normals.unwrap()[0]
+            ));
+        }
+        // Shearing in x along y tilts normals of planes with constant x.
+        let mut shear = na::Matrix4::identity();
+        shear[(0, 1)] = 1.;
+        let mut normals = [[1., 0., 0.]];
+        Mesh::transform_normals(&shear, &mut normals);
+        let expected = na::Vector3::new(1., -1., 0.).normalize();
+        assert!(f64slice_eq(
+            &normals[0],
+            &[expected.x, expected.y, expected.z]
+        ));
+    }
+
+    #[test]
+    fn split_nonmanifold_vertices() {
+        // Three fans around vertex 0, the first with two faces.
+        let mut m = Mesh {
+            vertices: vec![
+                [0., 0., 0.],
+                [1., 0., 0.],
+                [1., 1., 0.],
+                [0., 1., 0.],
+                [-1., 0., 1.],
+                [-1., 1., 1.],
+                [0., -1., -1.],
+                [1., -1., -1.],
+            ],
+            faces: vec![
+                SmallVec::from_slice(&[0, 1, 2]),
+                SmallVec::from_slice(&[0, 2, 3]),
+                SmallVec::from_slice(&[0, 4, 5]),
+                SmallVec::from_slice(&[7, 6, 0]),
+            ],
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        assert_eq!(m.split_nonmanifold_vertices(), 1);
+        assert_eq!(m.vertices.len(), 10);
+        assert_eq!(m.vertices[8], [0., 0., 0.]);
+        assert_eq!(m.vertices[9], [0., 0., 0.]);
+        assert_eq!(
+            m.faces,
+            vec![
+                SmallVec::from_slice(&[0, 1, 2]),
+                SmallVec::from_slice(&[0, 2, 3]),
+                SmallVec::<[usize; 4]>::from_slice(&[8, 4, 5]),
+                SmallVec::from_slice(&[7, 6, 9]),
+            ]
+        );
+        assert_eq!(m.split_nonmanifold_vertices(), 0);
+    }
+
+    #[test]
+    fn split_connected_components() {
+        let m = Mesh {
+            vertices: vec![
+                [0., 0., 0.],
+                [5., 0., 0.],
+                [1., 0., 0.],
+                [0., 1., 0.],
+                [5., 1., 0.],
+                [1., 1., 0.],
+                [9., 9., 9.],
+                [6., 0., 0.],
+            ],
+            faces: vec![
+                SmallVec::from_slice(&[1, 7, 4]),
+                SmallVec::from_slice(&[0, 2, 3]),
+                SmallVec::from_slice(&[2, 5, 3]),
+            ],
+            labels: Some(vec![7, 8, 9]),
+            face_normals: None,
+            vertex_colors: None,
+        };
+        let components = m.split_connected_components();
+        assert_eq!(
+            components,
+            vec![
+                Mesh {
+                    vertices: vec![[5., 0., 0.], [6., 0., 0.], [5., 1., 0.]],
+                    faces: vec![SmallVec::from_slice(&[0, 1, 2])],
+                    labels: Some(vec![7]),
+                    face_normals: None,
+                    vertex_colors: None,
+                },
+                Mesh {
+                    vertices: vec![[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [1., 1., 0.]],
+                    faces: vec![
+                        SmallVec::from_slice(&[0, 1, 2]),
+                        SmallVec::from_slice(&[1, 3, 2])
+                    ],
+                    labels: Some(vec![8, 9]),
+                    face_normals: None,
+                    vertex_colors: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_coplanar_faces() {
+        // A 3 x 3 grid of points in z = 0 and quads between them.
+        let vertices: Vec<[f64; 3]> = (0..9)
+            .map(|i| [(i % 3) as f64, (i / 3) as f64, 0.])
+            .collect();
+        let quad = |x: usize, y: usize| {
+            let p = |x: usize, y: usize| x + 3 * y;
+            SmallVec::from_slice(&[p(x, y), p(x + 1, y), p(x + 1, y + 1), p(x, y + 1)])
+        };
+
+        let mut m = Mesh {
+            vertices: vertices.clone(),
+            faces: vec![quad(0, 0), quad(1, 0), quad(0, 1), quad(1, 1)],
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        m.merge_coplanar_faces(0.01);
+        assert_eq!(m.faces.len(), 1);
+        let mut face = m.faces[0].to_vec();
+        face.sort_unstable();
+        assert_eq!(face, vec![0, 1, 2, 3, 5, 6, 7, 8]);
+
+        // An L shape is not convex.
+        let mut m = Mesh {
+            vertices: vertices.clone(),
+            faces: vec![quad(0, 0), quad(1, 0), quad(0, 1)],
+            labels: Some(vec![1, 1, 1]),
+            face_normals: None,
+            vertex_colors: None,
+        };
+        m.merge_coplanar_faces(0.01);
+        assert_eq!(m.faces.len(), 2);
+        assert_eq!(m.labels, Some(vec![1, 1]));
+
+        // Faces with different labels or normals are kept apart.
+        let mut m = Mesh {
+            vertices: vertices.clone(),
+            faces: vec![quad(0, 0), quad(1, 0)],
+            labels: Some(vec![1, 2]),
+            face_normals: None,
+            vertex_colors: None,
+        };
+        m.merge_coplanar_faces(0.01);
+        assert_eq!(m.faces.len(), 2);
+        let mut vertices = vertices;
+        vertices[2][2] = 0.1;
+        let mut m = Mesh {
+            vertices,
+            faces: vec![quad(0, 0), quad(1, 0)],
+            labels: None,
+            face_normals: Some(vec![[0., 0., 1.], [0., 0., 1.]]),
+            vertex_colors: None,
+        };
+        m.merge_coplanar_faces(0.01);
+        assert_eq!(m.faces.len(), 2);
+        m.merge_coplanar_faces(0.2);
+        assert_eq!(m.faces.len(), 1);
+        assert_eq!(m.face_normals, Some(vec![[0., 0., 1.]]));
+    }
+
+    #[cfg(feature = "obj")]
+    #[test]
+    fn write_obj() {
+        let m = Mesh {
+            vertices: vec![[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]],
+            faces: vec![SmallVec::from_slice(&[0, 1, 2, 3])],
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        let mut obj = Vec::new();
+        m.write_obj(&mut obj, true, UpAxis::ZUp).unwrap();
+        assert_eq!(
+            String::from_utf8(obj.clone()).unwrap(),
+            "o SDFMesh\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 4 3 2 1\n"
+        );
+        assert_eq!(m.to_obj(true, UpAxis::ZUp).unwrap(), obj);
+        // Y-up keeps the winding and only rotates the coordinates.
+        assert_eq!(
+            String::from_utf8(m.to_obj(false, UpAxis::YUp).unwrap()).unwrap(),
+            "o SDFMesh\nv 0 0 0\nv 1 0 0\nv 1 0 -1\nv 0 0 -1\nf 1 2 3 4\n"
+        );
+        let m = Mesh {
+            vertex_colors: Some(vec![[1., 0., 0.], [0., 1., 0.], [0., 0., 1.], [1., 1., 1.]]),
+            ..m
+        };
+        assert_eq!(
+            String::from_utf8(m.to_obj(false, UpAxis::ZUp).unwrap()).unwrap(),
+            "o SDFMesh\nv 0 0 0 1 0 0\nv 1 0 0 0 1 0\nv 1 1 0 0 0 1\nv 0 1 0 1 1 1\nf 1 2 3 4\n"
+        );
+        // Auto only reverses inward facing meshes.
+        let mut tetrahedron = Mesh {
+            vertices: vec![[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [0., 0., 1.]],
+            faces: vec![
+                SmallVec::from_slice(&[0, 2, 1]),
+                SmallVec::from_slice(&[0, 1, 3]),
+                SmallVec::from_slice(&[0, 3, 2]),
+                SmallVec::from_slice(&[1, 2, 3]),
+            ],
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        assert_eq!(
+            tetrahedron.to_obj(Winding::Auto, UpAxis::ZUp).unwrap(),
+            tetrahedron.to_obj(Winding::Keep, UpAxis::ZUp).unwrap()
+        );
+        for face in &mut tetrahedron.faces {
+            face.reverse();
+        }
+        assert_eq!(
+            tetrahedron.to_obj(Winding::Auto, UpAxis::ZUp).unwrap(),
+            tetrahedron.to_obj(true, UpAxis::ZUp).unwrap()
+        );
+    }
+
+    #[cfg(feature = "ply")]
+    #[test]
+    fn write_ply() {
+        let m = Mesh {
+            vertices: vec![[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]],
+            faces: vec![SmallVec::from_slice(&[0, 1, 2, 3])],
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        let header = |colors: &str| {
+            format!(
+                "ply\nformat ascii 1.0\nelement vertex 4\nproperty float x\n\
+                 property float y\nproperty float z\n{}element face 1\n\
+                 property list uchar uint vertex_indices\nend_header\n",
+                colors
+            )
+        };
+        let mut ply = Vec::new();
+        m.write_ply(&mut ply, true, UpAxis::ZUp).unwrap();
+        assert_eq!(
+            String::from_utf8(ply.clone()).unwrap(),
+            header("") + "0 0 0\n1 0 0\n1 1 0\n0 1 0\n4 3 2 1 0\n"
+        );
+        assert_eq!(m.to_ply(true, UpAxis::ZUp).unwrap(), ply);
+        assert_eq!(
+            String::from_utf8(m.to_ply(false, UpAxis::YUp).unwrap()).unwrap(),
+            header("") + "0 0 0\n1 0 0\n1 0 -1\n0 0 -1\n4 0 1 2 3\n"
+        );
+        // Colors are clamped and scaled to bytes.
+        let m = Mesh {
+            vertex_colors: Some(vec![
+                [1., 0., 0.],
+                [0., 0.5, 0.],
+                [0., 0., 2.],
+                [-1., 1., 1.],
+            ]),
+            ..m
+        };
+        assert_eq!(
+            String::from_utf8(m.to_ply(false, UpAxis::ZUp).unwrap()).unwrap(),
+            header("property uchar red\nproperty uchar green\nproperty uchar blue\n")
+                + "0 0 0 255 0 0\n1 0 0 0 128 0\n1 1 0 0 0 255\n0 1 0 0 255 255\n4 0 1 2 3\n"
+        );
+        let m = Mesh {
+            faces: vec![(0..256).map(|i| i % 4).collect()],
+            ..m
+        };
+        assert!(m.to_ply(false, UpAxis::ZUp).is_err());
+    }
+
+    #[cfg(feature = "obj")]
+    #[test]
+    fn write_obj_by_component() {
+        // Two triangles, whose vertices are interleaved, and a third one
+        // sharing a vertex with the first.
+        let m = Mesh {
+            vertices: vec![
+                [0., 0., 0.],
+                [5., 0., 0.],
+                [1., 0., 0.],
+                [6., 0., 0.],
+                [0., 1., 0.],
+                [5., 1., 0.],
+                [-1., 0., 0.],
+            ],
+            faces: vec![
+                SmallVec::from_slice(&[0, 2, 4]),
+                SmallVec::from_slice(&[1, 3, 5]),
+                SmallVec::from_slice(&[0, 4, 6]),
+            ],
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        let mut obj = Vec::new();
+        m.write_obj_by_component(&mut obj, false, UpAxis::ZUp)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(obj.clone()).unwrap(),
+            "v 0 0 0\nv 5 0 0\nv 1 0 0\nv 6 0 0\nv 0 1 0\nv 5 1 0\nv -1 0 0\n\
+             o SDFMesh_0\nf 1 3 5\nf 1 5 7\n\
+             o SDFMesh_1\nf 2 4 6\n"
+        );
+        assert_eq!(m.to_obj_by_component(false, UpAxis::ZUp).unwrap(), obj);
+    }
+
+    #[cfg(feature = "obj")]
+    #[test]
+    fn write_obj_with_groups() {
+        let m = Mesh {
+            vertices: vec![[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]],
+            faces: vec![
+                SmallVec::from_slice(&[0, 1, 2]),
+                SmallVec::from_slice(&[0, 2, 3]),
+                SmallVec::from_slice(&[2, 1, 0]),
+            ],
+            labels: Some(vec![3, 0, 3]),
+            face_normals: None,
+            vertex_colors: None,
+        };
+        let vertices = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+        let mut obj = Vec::new();
+        m.write_obj_with_groups(&mut obj, false, UpAxis::ZUp, true, true)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(obj.clone()).unwrap(),
+            format!(
+                "o SDFMesh\ns 1\n{}g label_0\nf 1 3 4\ng label_3\nf 1 2 3\nf 3 2 1\n",
+                vertices
+            )
+        );
+        assert_eq!(
+            m.to_obj_with_groups(false, UpAxis::ZUp, true, true)
+                .unwrap(),
+            obj
+        );
+        // Without labels all faces stay in one group.
+        let m = Mesh { labels: None, ..m };
+        assert_eq!(
+            String::from_utf8(
+                m.to_obj_with_groups(false, UpAxis::ZUp, false, true)
+                    .unwrap()
+            )
+            .unwrap(),
+            format!("o SDFMesh\ns off\n{}f 1 2 3\nf 1 3 4\nf 3 2 1\n", vertices)
+        );
+    }
+
+    #[test]
+    fn generate_uvs() {
+        // A quad in z = 0 and a quad in x = 2.
+        let m = Mesh {
+            vertices: vec![
+                [0., 0., 0.],
+                [1., 0., 0.],
+                [1., 1., 0.],
+                [0., 1., 0.],
+                [2., 0., 0.],
+                [2., 1., 0.],
+                [2., 1., 1.],
+                [2., 0., 1.],
+            ],
+            faces: vec![
+                SmallVec::from_slice(&[0, 1, 2, 3]),
+                SmallVec::from_slice(&[4, 5, 6, 7]),
+            ],
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        let uvs = m.generate_uvs(UvMode::Planar(Axis::Y));
+        assert_eq!(uvs.len(), 8);
+        assert_eq!(uvs[2], [1., 0.]);
+        assert_eq!(uvs[6], [2., 1.]);
+        let uvs = m.generate_uvs(UvMode::Triplanar);
+        assert_eq!(&uvs[..4], &[[0., 0.], [1., 0.], [1., 1.], [0., 1.]]);
+        assert_eq!(&uvs[4..], &[[0., 0.], [1., 0.], [1., 1.], [0., 1.]]);
+
+        #[cfg(feature = "obj")]
+        {
+            let obj =
+                String::from_utf8(m.to_obj_with_uvs(true, UpAxis::ZUp, &uvs).unwrap()).unwrap();
+            assert!(obj.contains("\nvt 1 1\n"));
+            assert!(obj.ends_with("f 4/4 3/3 2/2 1/1\nf 8/8 7/7 6/6 5/5\n"));
+            assert!(m.to_obj_with_uvs(false, UpAxis::ZUp, &uvs[1..]).is_err());
+        }
+    }
+
+    #[test]
+    fn boundary_loops() {
+        // A cube without its top and with a triangular hole in its bottom.
+        let mut m = Mesh {
+            vertices: vec![
+                [0., 0., 0.],
+                [1., 0., 0.],
+                [1., 1., 0.],
+                [0., 1., 0.],
+                [0., 0., 1.],
+                [1., 0., 1.],
+                [1., 1., 1.],
+                [0., 1., 1.],
+            ],
+            faces: vec![
+                SmallVec::from_slice(&[0, 2, 1]),
+                SmallVec::from_slice(&[0, 1, 5, 4]),
+                SmallVec::from_slice(&[1, 2, 6, 5]),
+                SmallVec::from_slice(&[2, 3, 7, 6]),
+                SmallVec::from_slice(&[3, 0, 4, 7]),
+            ],
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        assert_eq!(
+            m.boundary_edges(),
+            vec![(0, 2), (2, 3), (3, 0), (4, 7), (5, 4), (6, 5), (7, 6)]
+        );
+        assert_eq!(m.boundary_loops(), vec![vec![0, 2, 3], vec![4, 7, 6, 5]]);
+        assert!(!m.is_closed_surface());
+        m.fill_holes(4);
+        assert!(m.boundary_edges().is_empty());
+        assert!(m.boundary_loops().is_empty());
+        assert_eq!(m.euler_characteristic(), 2);
+        assert!(m.is_closed_surface());
+    }
+
+    #[test]
+    fn euler_characteristic() {
+        let tetrahedron = Mesh {
+            vertices: vec![[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [0., 0., 1.]],
+            faces: vec![
+                SmallVec::from_slice(&[0, 2, 1]),
+                SmallVec::from_slice(&[0, 1, 3]),
+                SmallVec::from_slice(&[0, 3, 2]),
+                SmallVec::from_slice(&[1, 2, 3]),
+            ],
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        assert_eq!(tetrahedron.euler_characteristic(), 2);
+        assert!(tetrahedron.is_closed_surface());
+
+        // Two tetrahedra touching at vertex 0.
+        let mut touching = tetrahedron.clone();
+        touching
+            .vertices
+            .extend_from_slice(&[[-1., 0., 0.], [0., -1., 0.], [0., 0., -1.]]);
+        for face in tetrahedron.faces.iter() {
+            touching.faces.push(
+                face.iter()
+                    .map(|&i| if i == 0 { 0 } else { i + 3 })
+                    .collect(),
+            );
+        }
+        assert_eq!(touching.euler_characteristic(), 3);
+        assert!(!touching.is_closed_surface());
+
+        // A torus of 4 x 4 quads.
+        let mut torus = Mesh {
+            vertices: vec![[0., 0., 0.]; 16],
+            faces: Vec::new(),
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        for i in 0..4 {
+            for j in 0..4 {
+                let v = |i: usize, j: usize| (i % 4) * 4 + j % 4;
+                torus.faces.push(SmallVec::from_slice(&[
+                    v(i, j),
+                    v(i + 1, j),
+                    v(i + 1, j + 1),
+                    v(i, j + 1),
+                ]));
+            }
+        }
+        assert_eq!(torus.euler_characteristic(), 0);
+        assert!(torus.is_closed_surface());
+        // Inconsistent winding.
+        torus.faces[0].reverse();
+        assert!(!torus.is_closed_surface());
+        assert!(!Mesh::<f64> {
+            vertices: Vec::new(),
+            faces: Vec::new(),
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        }
+        .is_closed_surface());
+    }
+
+    #[test]
+    fn fill_holes() {
+        // A cube without its top and with a triangular hole in its bottom.
+        let mut m = Mesh {
+            vertices: vec![
+                [0., 0., 0.],
+                [1., 0., 0.],
+                [1., 1., 0.],
+                [0., 1., 0.],
+                [0., 0., 1.],
+                [1., 0., 1.],
+                [1., 1., 1.],
+                [0., 1., 1.],
+            ],
+            faces: vec![
+                SmallVec::from_slice(&[0, 2, 1]),
+                SmallVec::from_slice(&[0, 1, 5, 4]),
+                SmallVec::from_slice(&[1, 2, 6, 5]),
+                SmallVec::from_slice(&[2, 3, 7, 6]),
+                SmallVec::from_slice(&[3, 0, 4, 7]),
+            ],
+            labels: Some(vec![0, 1, 2, 3, 4]),
+            face_normals: Some(vec![[0., 0., -1.]; 5]),
+            vertex_colors: None,
+        };
+        assert_eq!(m.fill_holes(2), 0);
+        assert_eq!(m.faces.len(), 5);
+        assert_eq!(m.fill_holes(3), 1);
+        assert_eq!(m.faces.len(), 6);
+        assert_eq!(m.faces[5].len(), 3);
+        assert!(m.faces[5].contains(&3) && m.faces[5].contains(&0) && m.faces[5].contains(&2));
+        assert!(f64slice_eq(
+            &m.face_normals.as_ref().unwrap()[5],
+            &[0., 0., -1.]
+        ));
+        assert_eq!(m.fill_holes(4), 1);
+        assert_eq!(m.faces.len(), 10);
+        assert_eq!(m.vertices.len(), 9);
+        assert_eq!(m.vertices[8], [0.5, 0.5, 1.]);
+        assert!(f64slice_eq(
+            &m.face_normals.as_ref().unwrap()[9],
+            &[0., 0., 1.]
+        ));
+        assert_eq!(m.labels.as_ref().unwrap().len(), 10);
+        // Each new face of the top belongs to the side across its edge.
+        for (face, &label) in m.faces.iter().zip(m.labels.as_ref().unwrap()).skip(6) {
+            let side = &m.faces[label as usize];
+            assert_eq!(face.iter().filter(|i| side.contains(i)).count(), 2);
+        }
+        // The mesh is closed now.
+        let half_edge = m.to_half_edge();
+        assert!(half_edge.half_edges.iter().all(|e| e.twin.is_some()));
+        assert_eq!(m.fill_holes(4), 0);
+    }
+
+    #[test]
+    fn clip() {
+        let cube = Mesh {
+            vertices: vec![
+                [0., 0., 0.],
+                [1., 0., 0.],
+                [1., 1., 0.],
+                [0., 1., 0.],
+                [0., 0., 1.],
+                [1., 0., 1.],
+                [1., 1., 1.],
+                [0., 1., 1.],
+            ],
+            faces: vec![
+                SmallVec::from_slice(&[0, 3, 2, 1]),
+                SmallVec::from_slice(&[0, 1, 5, 4]),
+                SmallVec::from_slice(&[1, 2, 6, 5]),
+                SmallVec::from_slice(&[2, 3, 7, 6]),
+                SmallVec::from_slice(&[3, 0, 4, 7]),
+                SmallVec::from_slice(&[4, 5, 6, 7]),
+            ],
+            labels: Some(vec![0, 1, 2, 3, 4, 5]),
+            face_normals: None,
+            vertex_colors: Some((0..8).map(|i| [(i / 4) as f32; 3]).collect()),
+        };
+        let plane = |p: [f64; 3], n: [f64; 3]| Plane {
+            p: na::Point3::from(p),
+            n: na::Vector3::from(n),
+        };
+        let closed = |m: &Mesh<f64>| m.to_half_edge().half_edges.iter().all(|e| e.twin.is_some());
+
+        // Nothing to remove.
+        let mut m = cube.clone();
+        assert_eq!(m.clip(plane([0., 0., 2.], [0., 0., 1.]), true), 0);
+        assert_eq!(m, cube);
+        // Cut in half and cap.
+        let mut m = cube.clone();
+        assert_eq!(m.clip(plane([0., 0., 0.5], [0., 0., 2.]), false), 0);
+        assert_eq!(m.faces.len(), 5);
+        assert_eq!(m.labels, Some(vec![0, 1, 2, 3, 4]));
+        assert_eq!(m.vertices.len(), 12);
+        assert!(m.vertices[8..].iter().all(|v| v[2] == 0.5));
+        assert!(m.vertex_colors.as_ref().unwrap()[8..]
+            .iter()
+            .all(|c| *c == [0.5; 3]));
+        assert!(m.faces.iter().flatten().all(|&i| m.vertices[i][2] <= 0.5));
+        assert_eq!(m.boundary_loops().len(), 1);
+        assert_eq!(m.clip(plane([0., 0., 0.5], [0., 0., 1.]), true), 1);
+        assert_eq!(m.faces.len(), 9);
+        assert_eq!(m.vertices[12], [0.5, 0.5, 0.5]);
+        assert!(closed(&m));
+        assert_eq!(
+            m.polygon_normal(&m.faces[8]),
+            Some(na::Vector3::new(0., 0., 1.))
+        );
+        // Cut off a corner, which turns three quads into pentagons.
+        let mut m = cube.clone();
+        assert_eq!(m.clip(plane([0.9, 0.9, 0.9], [1., 1., 1.]), true), 1);
+        assert_eq!(m.faces.len(), 7);
+        assert_eq!(m.faces.iter().filter(|face| face.len() == 5).count(), 3);
+        assert_eq!(m.faces[6].len(), 3);
+        assert!(closed(&m));
+    }
+
+    #[test]
+    fn normalize() {
+        let mut m = Mesh {
+            vertices: vec![[1., 2., 3.], [5., 2., 3.], [1., 4., 4.]],
+            faces: vec![SmallVec::from_slice(&[0, 1, 2])],
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        let original = m.clone();
+        let (center, scale) = m.normalize();
+        assert_eq!(center, na::Point3::new(3., 3., 3.5));
+        assert_eq!(scale, 0.25);
+        assert_eq!(
+            m.vertices,
+            vec![
+                [-0.5, -0.25, -0.125],
+                [0.5, -0.25, -0.125],
+                [-0.5, 0.25, 0.125]
+            ]
+        );
+        m.scale(&na::Vector3::repeat(1. / scale));
+        m.translate(&center.coords);
+        assert_eq!(m, original);
+
+        let mut empty = Mesh::<f64> {
+            vertices: Vec::new(),
+            faces: Vec::new(),
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        assert_eq!(empty.normalize(), (na::Point3::origin(), 1.));
+    }
+
+    #[test]
+    fn smooth() {
+        // A noisy unit sphere of latitude/longitude quads, with a vertex at
+        // each pole.
+        let (rings, segments) = (12, 24);
+        let mut vertices = vec![[0., 0., 1.], [0., 0., -1.]];
+        for ring in 1..rings {
+            let theta = std::f64::consts::PI * ring as f64 / rings as f64;
+            for segment in 0..segments {
+                let phi = 2. * std::f64::consts::PI * segment as f64 / segments as f64;
+                let r = 1.
+                    + if (ring + segment) % 2 == 0 {
+                        0.05
+                    } else {
+                        -0.05
+                    };
+                vertices.push([
+                    r * theta.sin() * phi.cos(),
+                    r * theta.sin() * phi.sin(),
+                    r * theta.cos(),
+                ]);
+            }
+        }
+        let index = |ring: usize, segment: usize| 2 + (ring - 1) * segments + segment % segments;
+        let mut faces = Vec::new();
+        for segment in 0..segments {
+            faces.push(SmallVec::from_slice(&[
+                0,
+                index(1, segment),
+                index(1, segment + 1),
+            ]));
+            faces.push(SmallVec::from_slice(&[
+                1,
+                index(rings - 1, segment + 1),
+                index(rings - 1, segment),
+            ]));
+            for ring in 1..rings - 1 {
+                faces.push(SmallVec::from_slice(&[
+                    index(ring, segment),
+                    index(ring + 1, segment),
+                    index(ring + 1, segment + 1),
+                    index(ring, segment + 1),
+                ]));
+            }
+        }
+        // Unused vertices are not moved.
+        let unused = vertices.len();
+        vertices.push([5., 5., 5.]);
+        let sphere = Mesh {
+            vertices,
+            faces,
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        let radius = |m: &Mesh<f64>| {
+            m.vertices[..unused]
+                .iter()
+                .map(|&v| na::Vector3::from(v).norm())
+                .collect::<Vec<_>>()
+        };
+        let spread = |r: &[f64]| {
+            r.iter().cloned().fold(f64::MIN, f64::max) - r.iter().cloned().fold(f64::MAX, f64::min)
+        };
+        let mean = |r: &[f64]| r.iter().sum::<f64>() / r.len() as f64;
+        let original = radius(&sphere);
+
+        let mut laplacian = sphere.clone();
+        laplacian.smooth_laplacian(0.5, 10);
+        let mut taubin = sphere.clone();
+        taubin.smooth_taubin(0.5, -0.53, 10);
+        for m in &[&laplacian, &taubin] {
+            assert_eq!(m.faces, sphere.faces);
+            assert_eq!(m.vertices[unused], [5., 5., 5.]);
+            assert!(spread(&radius(m)) < spread(&original));
+        }
+        assert!(spread(&radius(&taubin)) < spread(&original) / 4.);
+        // Laplacian smoothing shrinks the mesh, Taubin smoothing hardly.
+        assert!(mean(&radius(&laplacian)) < 0.9);
+        assert!((mean(&radius(&taubin)) - 1.).abs() < 0.01);
+    }
+
+    #[test]
+    fn surface_error() {
+        let ground = crate::SplitFunction::new(
+            BoundingBox::new(
+                &na::Point3::new(-1., -1., -1.),
+                &na::Point3::new(1., 1., 1.),
+            ),
+            |p| p.z,
+            |_| na::Vector3::z(),
+        );
+        let mut m = Mesh {
+            vertices: vec![[0., 0., 0.1], [1., 0., -0.2], [0., 1., 0.2], [1., 1., 0.]],
+            faces: vec![SmallVec::from_slice(&[0, 1, 3, 2])],
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        let (max, rms) = m.surface_error(&ground);
+        assert!((max - 0.2).abs() < 1e-12);
+        assert!((rms - (0.09f64 / 4.).sqrt()).abs() < 1e-12);
+        m.vertices.clear();
+        assert_eq!(m.surface_error(&ground), (0., 0.));
+    }
+
+    #[test]
+    fn remove_duplicate_faces() {
+        let mut m = Mesh {
+            vertices: vec![[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]],
+            faces: vec![
+                SmallVec::from_slice(&[0, 1, 2, 3]),
+                // Rotated and reversed.
+                SmallVec::from_slice(&[2, 3, 0, 1]),
+                SmallVec::from_slice(&[1, 0, 3, 2]),
+                // The same vertices in a different order, i.e. a bowtie.
+                SmallVec::from_slice(&[0, 2, 1, 3]),
+                SmallVec::from_slice(&[0, 1, 2]),
+            ],
+            labels: Some(vec![0, 1, 2, 3, 4]),
+            face_normals: None,
+            vertex_colors: None,
+        };
+        assert_eq!(m.remove_duplicate_faces(), 2);
+        assert_eq!(m.labels, Some(vec![0, 3, 4]));
+        assert_eq!(m.remove_duplicate_faces(), 0);
+    }
+
+    #[test]
+    fn repair() {
+        // Two triangles sharing an edge, one of them wound the wrong way
+        // and duplicated, a third triangle touching them at a single
+        // welded vertex and a degenerate face.
+        let mut m = Mesh {
+            vertices: vec![
+                [0., 0., 0.],
+                [1., 0., 0.],
+                [0., 1., 0.],
+                [1., 1., 0.],
+                [-1., 0., 0.],
+                [-1., -1., 0.],
+                [0.001, 0., 0.],
+            ],
+            faces: vec![
+                SmallVec::from_slice(&[0, 1, 2]),
+                SmallVec::from_slice(&[1, 2, 3]),
+                SmallVec::from_slice(&[3, 2, 1]),
+                SmallVec::from_slice(&[6, 4, 5]),
+                SmallVec::from_slice(&[0, 1, 0, 1]),
+            ],
+            labels: Some(vec![0, 1, 2, 3, 4]),
+            face_normals: None,
+            vertex_colors: None,
+        };
+        let mut unwelded = m.clone();
+        let report = m.repair(RepairOptions {
+            weld_epsilon: Some(0.01),
+            ..Default::default()
+        });
+        assert_eq!(
+            report,
+            RepairReport {
+                num_welded_vertices: 1,
+                num_degenerate_faces: 1,
+                num_duplicate_faces: 1,
+                num_reversed_faces: 1,
+                num_split_vertices: 1,
+            }
+        );
+        assert_eq!(m.faces.len(), 3);
+        assert_eq!(m.labels, Some(vec![0, 1, 3]));
+        assert_eq!(m.vertices.len(), 7);
+        // Each directed edge is used once now.
+        let mut directed_edges = HashSet::new();
+        for face in &m.faces {
+            for i in 0..face.len() {
+                assert!(directed_edges.insert((face[i], face[(i + 1) % face.len()])));
+            }
+        }
+        // Without welding the third triangle stays separate.
+        let report = unwelded.repair(RepairOptions {
+            split_nonmanifold_vertices: false,
+            ..Default::default()
+        });
+        assert_eq!(report.num_welded_vertices, 0);
+        assert_eq!(report.num_split_vertices, 0);
+        assert_eq!(report.num_duplicate_faces, 1);
+        assert_eq!(unwelded.faces.len(), 3);
+        assert_eq!(unwelded.vertices.len(), 7);
+
+        // Running it again changes nothing.
+        assert_eq!(m.repair(Default::default()), RepairReport::default());
+    }
+
+    #[test]
+    fn weld() {
+        // Two triangles sharing an edge, with duplicated vertices, and a
+        // triangle collapsing to an edge.
+        let mut m = Mesh {
+            vertices: vec![
+                [0., 0., 0.],
+                [1., 0., 0.],
+                [0., 1., 0.],
+                [1.001, 0.001, 0.],
+                [1., 1., 0.],
+                [0.001, 1., 0.],
+                [0., 1.001, 0.],
+            ],
+            faces: vec![
+                SmallVec::from_slice(&[0, 1, 2]),
+                SmallVec::from_slice(&[3, 4, 5]),
+                SmallVec::from_slice(&[2, 5, 6]),
+            ],
+            labels: Some(vec![0, 1, 2]),
+            face_normals: None,
+            vertex_colors: Some((0..7).map(|i| [i as f32, 0., 0.]).collect()),
+        };
+        let mut copy = m.clone();
+        let mut unwelded = m.clone();
+        assert_eq!(unwelded.weld(0.), 0);
+        assert_eq!(unwelded, m);
+        assert_eq!(m.weld(0.01), 3);
+        assert_eq!(
+            m.vertex_colors,
+            Some(vec![[0., 0., 0.], [1., 0., 0.], [2., 0., 0.], [4., 0., 0.]])
+        );
+        assert_eq!(
+            m.vertices,
+            vec![[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [1., 1., 0.]]
+        );
+        assert_eq!(
+            m.faces,
+            vec![
+                SmallVec::from_slice(&[0, 1, 2]),
+                SmallVec::<[usize; 4]>::from_slice(&[1, 3, 2]),
+            ]
+        );
+        assert_eq!(m.labels, Some(vec![0, 1]));
+        // The result is the same for any number of threads.
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        pool.install(|| copy.weld(0.01));
+        assert_eq!(copy, m);
+    }
+
+    #[test]
+    fn approx_eq() {
+        let m = Mesh {
+            vertices: vec![[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]],
+            faces: vec![
+                SmallVec::from_slice(&[0, 1, 2]),
+                SmallVec::from_slice(&[0, 2, 3]),
+            ],
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        assert!(m.approx_eq(&m, 0.));
+        // Reordered vertices and faces, rotated faces and a small offset.
+        let other = Mesh {
+            vertices: vec![[1., 1.001, 0.], [0., 1., 0.], [0., 0., 0.], [1., 0., 0.]],
+            faces: vec![
+                SmallVec::from_slice(&[0, 1, 2]),
+                SmallVec::from_slice(&[3, 0, 2]),
+            ],
+            labels: Some(vec![0, 1]),
+            face_normals: None,
+            vertex_colors: None,
+        };
+        assert!(m.approx_eq(&other, 0.01));
+        assert!(!m.approx_eq(&other, 0.0001));
+        // Reversed winding.
+        let mut reversed = m.clone();
+        reversed.faces[1].reverse();
+        assert!(!m.approx_eq(&reversed, 0.01));
+        let mut fewer = m.clone();
+        fewer.faces.pop();
+        assert!(!m.approx_eq(&fewer, 0.01));
+        // Two vertices within position_eps of each other, listed in the
+        // opposite order. Pairing the first vertex within position_eps
+        // swaps them.
+        let clustered = Mesh {
+            vertices: vec![[0., 0., 0.], [0.0005, 0., 0.], [0., 1., 0.]],
+            faces: vec![SmallVec::from_slice(&[0, 1, 2])],
+            labels: None,
+            face_normals: None,
+            vertex_colors: None,
+        };
+        let permuted = Mesh {
+            vertices: vec![[0.0005, 0., 0.], [0., 0., 0.], [0., 1., 0.]],
+            faces: vec![SmallVec::from_slice(&[1, 0, 2])],
+            ..clustered.clone()
+        };
+        assert!(clustered.approx_eq(&permuted, 0.001));
+        assert!(permuted.approx_eq(&clustered, 0.001));
+    }
+
+    #[test]
+    fn weld_boundary() {
+        // Two unit squares meeting at x = 1, the right one with slightly
+        // offset vertices.
+        let mut left = Mesh {
+            vertices: vec![[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]],
+            faces: vec![SmallVec::from_slice(&[0, 1, 2, 3])],
+            labels: Some(vec![0]),
+            face_normals: None,
+            vertex_colors: None,
+        };
+        let mut right = Mesh {
+            vertices: vec![[1.001, 0., 0.], [2., 0., 0.], [2., 1., 0.], [1., 1.001, 0.]],
+            faces: vec![SmallVec::from_slice(&[0, 1, 2, 3])],
+            labels: Some(vec![1]),
+            face_normals: None,
+            vertex_colors: None,
+        };
+        let plane = Plane {
+            p: na::Point3::new(1., 0., 0.),
+            n: na::Vector3::new(2., 0., 0.),
+        };
+        assert_eq!(left.weld_boundary(&mut right, plane, 0.01), 2);
+        assert_eq!(left.vertices.len(), 6);
+        assert_eq!(left.vertices[4], [2., 0., 0.]);
+        assert_eq!(
+            left.faces,
+            vec![
+                SmallVec::from_slice(&[0, 1, 2, 3]),
+                SmallVec::<[usize; 4]>::from_slice(&[1, 4, 5, 2]),
+            ]
+        );
+        assert_eq!(left.labels, Some(vec![0, 1]));
+        assert!(right.vertices.is_empty() && right.faces.is_empty());
+        // Merged meshes have no boundary edges along the cut.
+        let half_edge = left.to_half_edge();
+        assert_eq!(
+            half_edge
+                .half_edges
+                .iter()
+                .filter(|e| e.twin.is_none())
+                .count(),
+            6
+        );
+    }
+
+    #[test]
+    fn signed_distance() {
+        // A tetrahedron with outward facing triangles.
+        let m = TriangleMesh {
+            vertices: vec![[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [0., 0., 1.]],
+            faces: vec![[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]],
+        };
+        assert!((m.signed_distance([0.1, 0.1, 0.1]) + 0.1).abs() < 1e-12);
+        assert!((m.signed_distance([0.2, 0.2, -0.5]) - 0.5).abs() < 1e-12);
+        // Closest to a vertex, an edge and the diagonal face.
+        assert!((m.signed_distance([-1., -1., -1.]) - 3f64.sqrt()).abs() < 1e-12);
+        assert!((m.signed_distance([0.5, -1., -1.]) - 2f64.sqrt()).abs() < 1e-12);
+        assert!((m.signed_distance([1., 1., 1.]) - 2. / 3f64.sqrt()).abs() < 1e-12);
+        // Just outside of an edge and a vertex, where the face normals
+        // alone could give either sign.
+        assert!(m.signed_distance([0.5, -1e-3, -1e-3]) > 0.);
+        assert!(m.signed_distance([-1e-3, -1e-3, -1e-3]) > 0.);
+        assert!(m.signed_distance([0.5, 1e-3, 1e-3]) < 0.);
+        // One query for many points.
+        let query = m.distance_query();
+        for i in 0..1000 {
+            let p = [i % 10, i / 10 % 10, i / 100].map(|k| k as f64 / 5. - 0.45);
+            let inside = p.iter().all(|&x| x > 0.) && p.iter().sum::<f64>() < 1.;
+            assert_eq!(query.signed_distance(p) < 0., inside, "{:?}", p);
+            assert_eq!(query.signed_distance(p), m.signed_distance(p));
+        }
+        let empty = TriangleMesh::<f64> {
+            vertices: Vec::new(),
+            faces: Vec::new(),
+        };
+        assert_eq!(empty.signed_distance([0., 0., 0.]), f64::INFINITY);
+    }
+
+    #[test]
+    fn vertex_normals() {
+        // Two triangles meeting at vertex 0: one facing +z with a right
+        // angle at 0, one facing +x with twice the area and half the
+        // angle. Plus a degenerate triangle and an unused vertex.
+        let m = TriangleMesh {
+            vertices: vec![
+                [0., 0., 0.],
+                [1., 0., 0.],
+                [0., 1., 0.],
+                [0., 2., 0.],
+                [0., 1., 1.],
+                [5., 5., 5.],
+            ],
+            faces: vec![[0, 1, 2], [0, 3, 4], [0, 1, 1]],
+        };
+        let check = |weighting, expected: [f64; 3]| {
+            let normals = m.vertex_normals(weighting);
+            assert_eq!(normals.len(), m.vertices.len());
+            let expected = na::Vector3::from(expected).normalize();
+            assert_relative_eq!(na::Vector3::from(normals[0]), expected, epsilon = 1e-12);
+            assert_eq!(normals[1], [0., 0., 1.]);
+            assert_eq!(normals[3], [1., 0., 0.]);
+            assert_eq!(normals[5], [0., 0., 0.]);
+        };
+        check(NormalWeighting::Uniform, [1., 0., 1.]);
+        check(NormalWeighting::ByArea, [2., 0., 1.]);
+        check(NormalWeighting::ByAngle, [1., 0., 2.]);
+    }
+
+    #[test]
+    fn curvatures() {
+        // A UV sphere of radius 2 with outward facing triangles.
+        let (rings, segments, r) = (24, 48, 2.);
+        let mut vertices = vec![[0., 0., r]];
+        for i in 1..rings {
+            let theta = std::f64::consts::PI * i as f64 / rings as f64;
+            for j in 0..segments {
+                let phi = 2. * std::f64::consts::PI * j as f64 / segments as f64;
+                vertices.push([
+                    r * theta.sin() * phi.cos(),
+                    r * theta.sin() * phi.sin(),
+                    r * theta.cos(),
+                ]);
+            }
+        }
+        vertices.push([0., 0., -r]);
+        let south = vertices.len() - 1;
+        let ring = |i: usize, j: usize| 1 + (i - 1) * segments + j % segments;
+        let mut faces = Vec::new();
+        for j in 0..segments {
+            faces.push([0, ring(1, j), ring(1, j + 1)]);
+            for i in 1..rings - 1 {
+                faces.push([ring(i, j), ring(i + 1, j), ring(i + 1, j + 1)]);
+                faces.push([ring(i, j), ring(i + 1, j + 1), ring(i, j + 1)]);
+            }
+            faces.push([ring(rings - 1, j), south, ring(rings - 1, j + 1)]);
+        }
+        let sphere = TriangleMesh { vertices, faces };
+        let (mean, gaussian) = sphere.curvatures();
+        assert_eq!(mean.len(), sphere.vertices.len());
+        // Away from the poles, where the triangles are thin.
+        for i in ring(4, 0)..ring(rings - 4, 0) {
+            assert!((mean[i] - 0.5).abs() < 0.02, "{}", mean[i]);
+            assert!((gaussian[i] - 0.25).abs() < 0.02, "{}", gaussian[i]);
+        }
+
+        // A flat fan around vertex 0.
+        let mut fan = TriangleMesh {
+            vertices: vec![[0., 0., 0.]],
+            faces: Vec::new(),
+        };
+        for j in 0..6 {
+            let phi = std::f64::consts::PI * j as f64 / 3.;
+            fan.vertices.push([phi.cos(), phi.sin(), 0.]);
+            fan.faces.push([0, 1 + j, 1 + (j + 1) % 6]);
+        }
+        let (mean, gaussian) = fan.curvatures();
+        assert!(mean[0].abs() < 1e-12 && gaussian[0].abs() < 1e-12);
+        // The rim is on the boundary.
+        assert!(mean[1..].iter().chain(&gaussian[1..]).all(|c| c.is_nan()));
+    }
+
+    #[test]
+    fn make_winding_consistent() {
+        let cube = Mesh {
+            vertices: vec![
+                [0., 0., 0.],
+                [1., 0., 0.],
+                [1., 1., 0.],
+                [0., 1., 0.],
+                [0., 0., 1.],
+                [1., 0., 1.],
+                [1., 1., 1.],
+                [0., 1., 1.],
+            ],
+            faces: vec![
+                SmallVec::from_slice(&[0, 3, 2, 1]),
+                SmallVec::from_slice(&[4, 5, 6, 7]),
+                SmallVec::from_slice(&[0, 1, 5, 4]),
+                SmallVec::from_slice(&[1, 2, 6, 5]),
+                SmallVec::from_slice(&[2, 3, 7, 6]),
+                SmallVec::from_slice(&[3, 0, 4, 7]),
+            ],
+            labels: None,
+            face_normals: Some(vec![
+                [0., 0., -1.],
+                [0., 0., 1.],
+                [0., -1., 0.],
+                [1., 0., 0.],
+                [0., 1., 0.],
+                [-1., 0., 0.],
+            ]),
+            vertex_colors: None,
+        };
+        let mut m = cube.clone();
+        assert_eq!(m.make_winding_consistent(), 0);
+        assert_eq!(m, cube);
+        // Two reversed faces, whose normals are negated with them.
+        let negate = |n: &mut [f64; 3]| n.iter_mut().for_each(|c| *c = -*c);
+        for &i in &[1, 4] {
+            m.faces[i].reverse();
+            negate(&mut m.face_normals.as_mut().unwrap()[i]);
+        }
+        assert_eq!(m.make_winding_consistent(), 2);
+        assert!(m.approx_eq(&cube, 0.));
+        assert_eq!(m.face_normals, cube.face_normals);
+        // Consistent, but facing inwards.
+        for face in &mut m.faces {
+            face.reverse();
+        }
+        m.face_normals.as_mut().unwrap().iter_mut().for_each(negate);
+        assert_eq!(m.make_winding_consistent(), 6);
+        assert!(m.approx_eq(&cube, 0.));
+        assert_eq!(m.face_normals, cube.face_normals);
+    }
 }
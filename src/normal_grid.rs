@@ -0,0 +1,126 @@
+use crate::{vertex_index::Index, RealField};
+use nalgebra as na;
+use num_traits::Float;
+use std::collections::HashMap;
+
+/// Precomputed normals of an implicit function on a regular grid.
+///
+/// Supplied via [`ManifoldDualContouring::with_normal_grid()`](crate::ManifoldDualContouring::with_normal_grid())
+/// to avoid evaluating an expensive [`normal()`](crate::ImplicitFunction::normal()). Normals
+/// between grid points are interpolated trilinearly. The grid may be sparse, e.g. only cover the
+/// surface.
+#[derive(Clone, Debug)]
+pub struct NormalGrid<S: RealField> {
+    origin: na::Point3<S>,
+    res: S,
+    normals: HashMap<Index, na::Vector3<S>>,
+}
+
+impl<S: From<f32> + RealField + Float> NormalGrid<S> {
+    /// Create an empty grid, whose grid point [0, 0, 0] is at origin and whose neighboring grid
+    /// points are res apart.
+    pub fn new(origin: na::Point3<S>, res: S) -> Self {
+        NormalGrid {
+            origin,
+            res,
+            normals: HashMap::new(),
+        }
+    }
+
+    /// Set the normal at the grid point at index.
+    pub fn insert(&mut self, index: Index, normal: na::Vector3<S>) {
+        self.normals.insert(index, normal);
+    }
+
+    /// The position of grid point [0, 0, 0].
+    pub fn origin(&self) -> na::Point3<S> {
+        self.origin
+    }
+
+    /// The distance between neighboring grid points.
+    pub fn res(&self) -> S {
+        self.res
+    }
+
+    /// The number of grid points with a normal.
+    pub fn len(&self) -> usize {
+        self.normals.len()
+    }
+
+    /// Returns true, if the grid has no normals.
+    pub fn is_empty(&self) -> bool {
+        self.normals.is_empty()
+    }
+
+    /// The position of the grid point at index.
+    pub fn position(&self, index: Index) -> na::Point3<S> {
+        self.origin
+            + na::Vector3::new(
+                From::from(index[0] as f32),
+                From::from(index[1] as f32),
+                From::from(index[2] as f32),
+            ) * self.res
+    }
+
+    /// The normal at p, interpolated trilinearly between the normals of the corners of the grid
+    /// cell containing p. None, if p is outside of the grid or a corner has no normal.
+    pub fn normal(&self, p: &na::Point3<S>) -> Option<na::Vector3<S>> {
+        let relative = (p - self.origin) / self.res;
+        let mut cell = [0; 3];
+        let mut weights = na::Vector3::zeros();
+        for i in 0..3 {
+            let floor = Float::floor(relative[i]);
+            cell[i] = floor.to_usize()?;
+            weights[i] = relative[i] - floor;
+        }
+        let mut sum = na::Vector3::zeros();
+        for corner in 0..8 {
+            let mut index = cell;
+            let mut weight = S::one();
+            for (i, index) in index.iter_mut().enumerate() {
+                if corner >> i & 1 == 1 {
+                    *index += 1;
+                    weight *= weights[i];
+                } else {
+                    weight *= S::one() - weights[i];
+                }
+            }
+            sum += self.normals.get(&index)? * weight;
+        }
+        Some(sum.normalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NormalGrid;
+    use nalgebra as na;
+
+    #[test]
+    fn normal() {
+        let mut grid = NormalGrid::new(na::Point3::new(-1f64, -1., -1.), 0.5);
+        for z in 0..2 {
+            for y in 0..2 {
+                for x in 0..2 {
+                    let n = if x == 0 {
+                        na::Vector3::z()
+                    } else {
+                        na::Vector3::x()
+                    };
+                    grid.insert([x, y, z], n);
+                }
+            }
+        }
+        assert_eq!(grid.len(), 8);
+        assert_eq!(grid.position([1, 0, 1]), na::Point3::new(-0.5, -1., -0.5));
+        assert_eq!(
+            grid.normal(&na::Point3::new(-1., -0.9, -0.8)),
+            Some(na::Vector3::z())
+        );
+        let n = grid.normal(&na::Point3::new(-0.75, -0.9, -0.8)).unwrap();
+        assert!((n - na::Vector3::new(1., 0., 1.).normalize()).norm() < 1e-12);
+        // Outside of the grid and in cells with missing corners.
+        assert_eq!(grid.normal(&na::Point3::new(-1.1, -0.9, -0.8)), None);
+        assert_eq!(grid.normal(&na::Point3::new(-0.4, -0.9, -0.8)), None);
+    }
+}
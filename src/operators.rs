@@ -0,0 +1,281 @@
+use crate::{ImplicitFunction, RealField};
+use bbox::BoundingBox;
+use nalgebra as na;
+use num_traits::Float;
+use std::fmt::{self, Debug};
+
+/// Turns a solid into a hollow shell of the given thickness, centered on its surface.
+#[derive(Clone, Debug)]
+pub struct Shell<S: RealField + Debug, F> {
+    function: F,
+    thickness: S,
+    bbox: BoundingBox<S>,
+}
+
+impl<S: RealField + Float + Debug, F: ImplicitFunction<S>> Shell<S, F> {
+    /// Create a shell of thickness around the surface of function.
+    pub fn new(function: F, thickness: S) -> Self {
+        let mut bbox = function.bbox().clone();
+        bbox.dilate(thickness / (S::one() + S::one()));
+        Shell {
+            function,
+            thickness,
+            bbox,
+        }
+    }
+}
+
+impl<S: RealField + Float + Debug, F: ImplicitFunction<S>> ImplicitFunction<S> for Shell<S, F> {
+    fn bbox(&self) -> &BoundingBox<S> {
+        &self.bbox
+    }
+    fn value(&self, p: &na::Point3<S>) -> S {
+        Float::abs(self.function.value(p)) - self.thickness / (S::one() + S::one())
+    }
+    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+        self.function.normal(p) * Float::signum(self.function.value(p))
+    }
+    fn is_exact_distance(&self) -> bool {
+        self.function.is_exact_distance()
+    }
+}
+
+/// Moves the surface of a function outwards (dilate) or inwards (erode) by a distance.
+#[derive(Clone, Debug)]
+pub struct Offset<S: RealField + Debug, F> {
+    function: F,
+    distance: S,
+    bbox: BoundingBox<S>,
+}
+
+impl<S: RealField + Float + Debug, F: ImplicitFunction<S>> Offset<S, F> {
+    /// Move the surface of function outwards by distance. Negative distances move it inwards.
+    pub fn new(function: F, distance: S) -> Self {
+        let mut bbox = function.bbox().clone();
+        // Eroding keeps the bbox, which is still conservative.
+        if distance > S::zero() {
+            bbox.dilate(distance);
+        }
+        Offset {
+            function,
+            distance,
+            bbox,
+        }
+    }
+}
+
+impl<S: RealField + Float + Debug, F: ImplicitFunction<S>> ImplicitFunction<S> for Offset<S, F> {
+    fn bbox(&self) -> &BoundingBox<S> {
+        &self.bbox
+    }
+    fn value(&self, p: &na::Point3<S>) -> S {
+        self.function.value(p) - self.distance
+    }
+    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+        self.function.normal(p)
+    }
+    fn is_exact_distance(&self) -> bool {
+        self.function.is_exact_distance()
+    }
+}
+
+/// The symmetric difference (xor) of two functions: everything inside exactly one of them.
+#[derive(Clone, Debug)]
+pub struct SymmetricDifference<S: RealField + Debug, A, B> {
+    a: A,
+    b: B,
+    bbox: BoundingBox<S>,
+}
+
+impl<S: RealField + Float + Debug, A: ImplicitFunction<S>, B: ImplicitFunction<S>>
+    SymmetricDifference<S, A, B>
+{
+    /// Create the symmetric difference of a and b.
+    pub fn new(a: A, b: B) -> Self {
+        let bbox = a.bbox().union(b.bbox());
+        SymmetricDifference { a, b, bbox }
+    }
+}
+
+impl<S: RealField + Float + Debug, A: ImplicitFunction<S>, B: ImplicitFunction<S>>
+    ImplicitFunction<S> for SymmetricDifference<S, A, B>
+{
+    fn bbox(&self) -> &BoundingBox<S> {
+        &self.bbox
+    }
+    // xor(a, b) = max(min(a, b), -max(a, b))
+    fn value(&self, p: &na::Point3<S>) -> S {
+        let (a, b) = (self.a.value(p), self.b.value(p));
+        Float::max(Float::min(a, b), -Float::max(a, b))
+    }
+    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+        let (a, b) = (self.a.value(p), self.b.value(p));
+        if Float::min(a, b) >= -Float::max(a, b) {
+            // Outside both or inside exactly one: the surface of the nearer one.
+            if a < b {
+                self.a.normal(p)
+            } else {
+                self.b.normal(p)
+            }
+        } else if a > b {
+            // Inside both: the inverted surface of the one, whose surface is nearer.
+            -self.a.normal(p)
+        } else {
+            -self.b.normal(p)
+        }
+    }
+    fn is_exact_distance(&self) -> bool {
+        self.a.is_exact_distance() && self.b.is_exact_distance()
+    }
+}
+
+type ValueFn<'a, S> = dyn Fn(&na::Point3<S>) -> S + 'a;
+type NormalFn<'a, S> = dyn Fn(&na::Point3<S>) -> na::Vector3<S> + 'a;
+
+/// A function, whose value and normal are computed by two separate closures, e.g. because they
+/// come from different systems.
+pub struct SplitFunction<'a, S: RealField + Debug> {
+    value_fn: Box<ValueFn<'a, S>>,
+    normal_fn: Box<NormalFn<'a, S>>,
+    bbox: BoundingBox<S>,
+    exact_distance: bool,
+}
+
+impl<'a, S: RealField + Debug> SplitFunction<'a, S> {
+    /// Create a function within bbox, whose value at p is value_fn(p) and whose normal is
+    /// normal_fn(p).
+    pub fn new(
+        bbox: BoundingBox<S>,
+        value_fn: impl Fn(&na::Point3<S>) -> S + 'a,
+        normal_fn: impl Fn(&na::Point3<S>) -> na::Vector3<S> + 'a,
+    ) -> Self {
+        SplitFunction {
+            value_fn: Box::new(value_fn),
+            normal_fn: Box::new(normal_fn),
+            bbox,
+            exact_distance: false,
+        }
+    }
+
+    /// Declare value_fn an exact distance, see
+    /// [`ImplicitFunction::is_exact_distance()`]. Disabled by default.
+    pub fn with_exact_distance(mut self, exact_distance: bool) -> Self {
+        self.exact_distance = exact_distance;
+        self
+    }
+}
+
+impl<'a, S: RealField + Debug> Debug for SplitFunction<'a, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SplitFunction")
+            .field("bbox", &self.bbox)
+            .field("exact_distance", &self.exact_distance)
+            .finish()
+    }
+}
+
+impl<'a, S: RealField + Debug> ImplicitFunction<S> for SplitFunction<'a, S> {
+    fn bbox(&self) -> &BoundingBox<S> {
+        &self.bbox
+    }
+    fn value(&self, p: &na::Point3<S>) -> S {
+        (self.value_fn)(p)
+    }
+    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+        (self.normal_fn)(p)
+    }
+    fn is_exact_distance(&self) -> bool {
+        self.exact_distance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Offset, Shell, SplitFunction, SymmetricDifference};
+    use crate::{test_functions::Sphere, ImplicitFunction, ManifoldDualContouring};
+    use nalgebra as na;
+
+    #[test]
+    fn shell() {
+        let shell = Shell::new(Sphere::unit(), 0.4);
+        assert_eq!(shell.bbox().max, na::Point3::new(1.2, 1.2, 1.2));
+        assert!((shell.value(&na::Point3::new(1., 0., 0.)) + 0.2).abs() < 1e-12);
+        assert!((shell.value(&na::Point3::new(0.6, 0., 0.)) - 0.2).abs() < 1e-12);
+        assert!((shell.value(&na::Point3::new(1.4, 0., 0.)) - 0.2).abs() < 1e-12);
+        // The normal points out of the shell on both sides.
+        assert_eq!(
+            shell.normal(&na::Point3::new(0.6, 0., 0.)),
+            na::Vector3::new(-1., 0., 0.)
+        );
+        assert_eq!(
+            shell.normal(&na::Point3::new(1.4, 0., 0.)),
+            na::Vector3::new(1., 0., 0.)
+        );
+        // The inner and the outer surface.
+        let mesh = ManifoldDualContouring::new(&shell, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        assert_eq!(mesh.split_connected_components().len(), 2);
+    }
+
+    #[test]
+    fn offset() {
+        let dilated = Offset::new(Sphere::unit(), 0.5);
+        assert_eq!(dilated.bbox().max, na::Point3::new(1.5, 1.5, 1.5));
+        assert!(dilated.value(&na::Point3::new(1.5, 0., 0.)).abs() < 1e-12);
+        let eroded = Offset::new(Sphere::unit(), -0.5);
+        assert_eq!(eroded.bbox().max, na::Point3::new(1., 1., 1.));
+        assert!(eroded.value(&na::Point3::new(0.5, 0., 0.)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn split_function() {
+        let sphere = Sphere::unit();
+        let split = SplitFunction::new(
+            sphere.bbox().clone(),
+            |p| p.coords.norm() - 1.,
+            |p| p.coords.normalize(),
+        )
+        .with_exact_distance(true);
+        assert!(split.is_exact_distance());
+        assert!((split.value(&na::Point3::new(2., 0., 0.)) - 1.).abs() < 1e-12);
+        assert_eq!(
+            split.normal(&na::Point3::new(0., 3., 0.)),
+            na::Vector3::new(0., 1., 0.)
+        );
+        // Composes with the other operators.
+        let dilated = Offset::new(split, 0.5);
+        let mesh = ManifoldDualContouring::new(&dilated, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        let expected = ManifoldDualContouring::new(&Offset::new(sphere, 0.5), 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        assert_eq!(mesh.vertices.len(), expected.vertices.len());
+        assert_eq!(mesh.faces.len(), expected.faces.len());
+    }
+
+    #[test]
+    fn symmetric_difference() {
+        let xor = SymmetricDifference::new(
+            Sphere::new(na::Point3::new(-0.5, 0., 0.), 1.),
+            Sphere::new(na::Point3::new(0.5, 0., 0.), 1.),
+        );
+        assert_eq!(xor.bbox().min, na::Point3::new(-1.5, -1., -1.));
+        // Inside both.
+        assert!(xor.value(&na::Point3::origin()) > 0.);
+        // Inside only one.
+        assert!(xor.value(&na::Point3::new(-1., 0., 0.)) < 0.);
+        // Outside both.
+        assert!(xor.value(&na::Point3::new(2., 0., 0.)) > 0.);
+        // On the surface of the right sphere inside the left one, facing into the left sphere.
+        assert_eq!(
+            xor.normal(&na::Point3::new(-0.4, 0., 0.)),
+            na::Vector3::new(1., 0., 0.)
+        );
+        assert_eq!(
+            xor.normal(&na::Point3::new(1.4, 0., 0.)),
+            na::Vector3::new(1., 0., 0.)
+        );
+    }
+}
@@ -2,8 +2,12 @@ use alga::general::RealField;
 use nalgebra as na;
 use std::fmt::Debug;
 
+/// A plane through a point, e.g. the plane a mesh was cut along.
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Plane<S: RealField + Debug> {
+    /// A point on the plane.
     pub p: na::Point3<S>,
+    /// The normal of the plane.
     pub n: na::Vector3<S>,
 }
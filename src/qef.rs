@@ -4,10 +4,9 @@ use nalgebra as na;
 use num_traits::Float;
 use std::{convert, fmt::Debug};
 
-pub const EPSILON: f32 = 1e-10;
-
 /// Quadratic error function
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Qef<S: RealField + Debug> {
     // Point closest to all planes.
     pub solution: na::Vector3<S>,
@@ -20,9 +19,34 @@ pub struct Qef<S: RealField + Debug> {
     // Scalar BT * B
     btb: S,
     pub error: S,
+    #[cfg_attr(feature = "serde", serde(with = "bbox_serde"))]
     bbox: BoundingBox<S>,
 }
 
+// BoundingBox does not implement serde, so (de)serialize it as its min and max corners.
+#[cfg(feature = "serde")]
+mod bbox_serde {
+    use crate::RealField;
+    use bbox::BoundingBox;
+    use nalgebra as na;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: RealField + Serialize, Ser: Serializer>(
+        bbox: &BoundingBox<S>,
+        serializer: Ser,
+    ) -> Result<Ser::Ok, Ser::Error> {
+        (&bbox.min, &bbox.max).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, S: RealField + Deserialize<'de>, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BoundingBox<S>, D::Error> {
+        let (min, max) = <(na::Point3<S>, na::Point3<S>)>::deserialize(deserializer)?;
+        // Not BoundingBox::new(), which would reorder the corners of an empty bbox.
+        Ok(BoundingBox { min, max })
+    }
+}
+
 impl<S: RealField + Float + Debug + From<f32>> Qef<S> {
     pub fn new(planes: &[Plane<S>], bbox: BoundingBox<S>) -> Qef<S> {
         let mut qef = Qef {
@@ -62,13 +86,41 @@ impl<S: RealField + Float + Debug + From<f32>> Qef<S> {
         }
         qef
     }
-    pub fn solve(&mut self) {
+    // The mean of the points on the planes.
+    pub fn mass_point(&self) -> na::Vector3<S> {
+        let num: S = convert::From::from(self.num as f32);
+        self.sum / num
+    }
+    // Forget the solution, so the qef can be solved again.
+    pub fn reset(&mut self) {
+        self.solution = na::Vector3::new(S::nan(), S::nan(), S::nan());
+        self.error = S::nan();
+    }
+    // Returns whether the qef or its solution was not finite and the solution fell back to the
+    // mass point. The error is infinite then.
+    // regularization adds regularization * |x - mass point|^2 per plane to the minimized error,
+    // which biases the solution toward the mass point. Zero solves the plain qef. The reported
+    // error is the one of the plain qef.
+    pub fn solve(&mut self, regularization: S) -> bool {
         let m = &self.ata;
         let ma = na::Matrix3::new(m[0], m[1], m[2], m[1], m[3], m[4], m[2], m[4], m[5]);
-        let sum_as_s: S = convert::From::from(self.num as f32);
-        let mean: na::Vector3<S> = self.sum / sum_as_s;
-        if let Some(inv) = ma.try_inverse() {
-            let b_rel_mean: na::Vector3<S> = self.atb - ma * mean;
+        let mean = self.mass_point();
+        let num: S = convert::From::from(self.num as f32);
+        let ma_reg = ma + na::Matrix3::identity() * (regularization * num);
+        let atb_reg = self.atb + mean * (regularization * num);
+        // E.g. NaN normals make the search below end up in an arbitrary corner of the bbox.
+        if !self
+            .ata
+            .iter()
+            .chain(self.atb.iter())
+            .all(|x| Float::is_finite(*x))
+        {
+            self.solution = mean;
+            self.error = S::infinity();
+            return true;
+        }
+        if let Some(inv) = ma_reg.try_inverse() {
+            let b_rel_mean: na::Vector3<S> = atb_reg - ma_reg * mean;
             self.solution = inv * b_rel_mean + mean;
         }
 
@@ -80,26 +132,37 @@ impl<S: RealField + Float + Debug + From<f32>> Qef<S> {
             self.solution.z,
         )) {
             let accuracy = (self.bbox.max.x - self.bbox.min.x) / convert::From::from(100.0);
-            self.solution = self.search_solution(accuracy, &mut self.bbox.clone(), &ma);
+            self.solution =
+                Self::search_solution(accuracy, &mut self.bbox.clone(), &ma_reg, &atb_reg);
+            // Dilate a copy, so solving again yields the same solution.
             debug_assert!(
-                self.bbox.dilate(accuracy).contains(&na::Point3::new(
-                    self.solution.x,
-                    self.solution.y,
-                    self.solution.z
-                )),
+                self.bbox
+                    .clone()
+                    .dilate(accuracy)
+                    .contains(&na::Point3::new(
+                        self.solution.x,
+                        self.solution.y,
+                        self.solution.z
+                    )),
                 "{:?} outside of {:?}",
                 self.solution,
                 self
             );
         }
+        if !self.solution.iter().all(|x| Float::is_finite(*x)) {
+            self.solution = mean;
+            self.error = S::infinity();
+            return true;
+        }
         self.error = self.error(&self.solution, &ma);
+        false
     }
     // Do a binary search. Stop, if bbox is smaller then accuracy.
     fn search_solution(
-        &self,
         accuracy: S,
         bbox: &mut BoundingBox<S>,
         ma: &na::Matrix3<S>,
+        atb: &na::Vector3<S>,
     ) -> na::Vector3<S> {
         // Generate bbox mid-point and error value on mid-point.
         // TODO: use proper apis
@@ -112,22 +175,20 @@ impl<S: RealField + Float + Debug + From<f32>> Qef<S> {
         if bbox.max.x - bbox.min.x <= accuracy {
             return na_mid;
         }
-        let mid_error = self.error(&na_mid, ma);
-        // For each dimension generate delta and error on delta - which results in the gradient for
-        // that direction. Based on the gradient sign choose proper half of the bbox.
+        // Based on the sign of the gradient of the error on mid choose proper half of the bbox.
+        // The gradient is computed analytically, since a finite difference vanishes below the
+        // precision of S, e.g. for f32.
         // TODO: Verify this is the right thing to do. Error is essentially an Elipsoid, so we
         // might need to do something more clever here.
+        let gradient = *ma * na_mid - *atb;
         for dim in 0..3 {
-            let mut d_mid = na_mid;
-            d_mid[dim] += convert::From::from(EPSILON);
-            let d_error = self.error(&d_mid, ma);
-            if d_error < mid_error {
+            if gradient[dim] < convert::From::from(0.) {
                 bbox.min[dim] = mid[dim];
             } else {
                 bbox.max[dim] = mid[dim];
             }
         }
-        self.search_solution(accuracy, bbox, ma)
+        Self::search_solution(accuracy, bbox, ma, atb)
     }
     fn error(&self, point: &na::Vector3<S>, ma: &na::Matrix3<S>) -> S {
         let _2_as_s: S = convert::From::from(2f32);
@@ -172,7 +233,7 @@ mod tests {
             ],
             BoundingBox::<f64>::new(&na::Point3::new(0., 0., 0.), &na::Point3::new(1., 1., 1.)),
         );
-        qef.solve();
+        qef.solve(0.);
         assert!(
             qef.solution.norm() < 0.01,
             "{:?} nowhere near origin",
@@ -199,8 +260,17 @@ mod tests {
             ],
             BoundingBox::<f64>::new(&na::Point3::new(0., 0., 0.), &na::Point3::new(1., 1., 1.)),
         );
-        qef.solve();
+        qef.solve(0.);
         assert!(relative_eq!(qef.solution, &na::Vector3::new(0., 0., 0.)));
+        let error = qef.error;
+        qef.reset();
+        // Regularization pulls the solution toward the mass point, but the error stays the one of
+        // the plain qef.
+        qef.solve(1.);
+        let mass_point = na::Vector3::new(1., 1., 1.) / 3.;
+        assert!(qef.solution.norm() > 0.1);
+        assert!((qef.solution - mass_point).norm() < mass_point.norm());
+        assert!(qef.error > error);
     }
 
     #[test]
@@ -222,7 +292,7 @@ mod tests {
             ],
             BoundingBox::<f64>::new(&na::Point3::new(0., 0., 0.), &na::Point3::new(1., 2., 3.)),
         );
-        qef.solve();
+        qef.solve(0.);
         let expected_solution = na::Vector3::new(1., 2., 3.);
         assert!(
             relative_eq!(qef.solution, &expected_solution),
@@ -231,4 +301,23 @@ mod tests {
             expected_solution
         );
     }
+
+    #[test]
+    fn non_finite_solution_falls_back_to_mass_point() {
+        let mut qef = Qef::new(
+            &[
+                Plane {
+                    p: na::Point3::new(0.2, 0.4, 0.6),
+                    n: na::Vector3::new(f64::NAN, 0., 1.),
+                },
+                Plane {
+                    p: na::Point3::new(0.4, 0.6, 0.8),
+                    n: na::Vector3::new(0., 1., 0.),
+                },
+            ],
+            BoundingBox::<f64>::new(&na::Point3::new(0., 0., 0.), &na::Point3::new(1., 1., 1.)),
+        );
+        assert!(qef.solve(0.));
+        assert!(relative_eq!(qef.solution, &na::Vector3::new(0.3, 0.5, 0.7)));
+    }
 }
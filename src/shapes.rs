@@ -0,0 +1,278 @@
+use crate::{ImplicitFunction, RealField};
+use bbox::BoundingBox;
+use nalgebra as na;
+use num_traits::Float;
+use std::fmt::Debug;
+
+/// A torus around the z axis, centered at the origin.
+#[derive(Clone, Debug)]
+pub struct Torus<S: RealField + Debug> {
+    major_radius: S,
+    minor_radius: S,
+    bbox: BoundingBox<S>,
+}
+
+impl<S: RealField + Float + Debug> Torus<S> {
+    /// Create a torus, whose tube of radius minor_radius runs around the z axis at major_radius.
+    pub fn new(major_radius: S, minor_radius: S) -> Self {
+        let r = major_radius + minor_radius;
+        Torus {
+            major_radius,
+            minor_radius,
+            bbox: BoundingBox::new(
+                &na::Point3::new(-r, -r, -minor_radius),
+                &na::Point3::new(r, r, minor_radius),
+            ),
+        }
+    }
+
+    // The vector from the nearest point on the center circle of the tube to p.
+    fn tube_offset(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+        let radial = na::Vector2::new(p.x, p.y);
+        let length = radial.norm();
+        // On the z axis every point on the center circle is nearest, pick the one on the x axis.
+        let direction = if length > S::zero() {
+            radial / length
+        } else {
+            na::Vector2::x()
+        };
+        let center = direction * self.major_radius;
+        na::Vector3::new(p.x - center.x, p.y - center.y, p.z)
+    }
+}
+
+impl<S: RealField + Float + Debug> ImplicitFunction<S> for Torus<S> {
+    fn bbox(&self) -> &BoundingBox<S> {
+        &self.bbox
+    }
+    fn value(&self, p: &na::Point3<S>) -> S {
+        self.tube_offset(p).norm() - self.minor_radius
+    }
+    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+        self.tube_offset(p).normalize()
+    }
+    fn is_exact_distance(&self) -> bool {
+        true
+    }
+}
+
+/// An axis aligned box, centered at the origin.
+#[derive(Clone, Debug)]
+pub struct BoxSDF<S: RealField + Debug> {
+    half_size: na::Vector3<S>,
+    bbox: BoundingBox<S>,
+}
+
+impl<S: RealField + Float + Debug> BoxSDF<S> {
+    /// Create a box reaching from -half_size to half_size.
+    pub fn new(half_size: na::Vector3<S>) -> Self {
+        BoxSDF {
+            half_size,
+            bbox: BoundingBox::new(&na::Point3::from(-half_size), &na::Point3::from(half_size)),
+        }
+    }
+
+    // Per axis distance of p to the faces of the box, negative inside.
+    fn face_distances(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+        p.coords.map(Float::abs) - self.half_size
+    }
+}
+
+impl<S: RealField + Float + Debug> ImplicitFunction<S> for BoxSDF<S> {
+    fn bbox(&self) -> &BoundingBox<S> {
+        &self.bbox
+    }
+    fn value(&self, p: &na::Point3<S>) -> S {
+        let q = self.face_distances(p);
+        let outside = q.map(|x| Float::max(x, S::zero())).norm();
+        let inside = Float::min(Float::max(q.x, Float::max(q.y, q.z)), S::zero());
+        outside + inside
+    }
+    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+        let q = self.face_distances(p);
+        let n = if q.x > S::zero() || q.y > S::zero() || q.z > S::zero() {
+            // Outside: away from the nearest point on the box.
+            q.map(|x| Float::max(x, S::zero()))
+        } else {
+            // Inside: the normal of the nearest face.
+            let mut n = na::Vector3::zeros();
+            n[q.imax()] = S::one();
+            n
+        };
+        n.zip_map(&p.coords, |n, p| if p < S::zero() { -n } else { n })
+            .normalize()
+    }
+    fn is_exact_distance(&self) -> bool {
+        true
+    }
+}
+
+/// A sheet of the given thickness around the gyroid minimal surface, clipped to a box.
+///
+/// The value is a lower bound of the distance to the surface, not the exact distance.
+#[derive(Clone, Debug)]
+pub struct Gyroid<S: RealField + Debug> {
+    // 2 pi / period
+    frequency: S,
+    thickness: S,
+    clip: BoxSDF<S>,
+}
+
+impl<S: RealField + Float + From<f32> + Debug> Gyroid<S> {
+    /// Create a gyroid sheet, that repeats every period along each axis and is clipped to the
+    /// box from -half_size to half_size.
+    pub fn new(period: S, thickness: S, half_size: na::Vector3<S>) -> Self {
+        Gyroid {
+            frequency: <S as na::RealField>::two_pi() / period,
+            thickness,
+            clip: BoxSDF::new(half_size),
+        }
+    }
+
+    // The value of the gyroid function and its gradient with respect to the scaled position.
+    fn gyroid(&self, p: &na::Point3<S>) -> (S, na::Vector3<S>) {
+        let (sx, cx) = Float::sin_cos(p.x * self.frequency);
+        let (sy, cy) = Float::sin_cos(p.y * self.frequency);
+        let (sz, cz) = Float::sin_cos(p.z * self.frequency);
+        (
+            sx * cy + sy * cz + sz * cx,
+            na::Vector3::new(cx * cy - sz * sx, cy * cz - sx * sy, cz * cx - sy * sz),
+        )
+    }
+
+    // The gradient of the gyroid function is at most sqrt(3) long, so this never overestimates
+    // the distance to the surface.
+    fn sheet_value(&self, g: S) -> S {
+        let three: S = From::from(3f32);
+        Float::abs(g) / (self.frequency * Float::sqrt(three))
+            - self.thickness / (S::one() + S::one())
+    }
+}
+
+impl<S: RealField + Float + From<f32> + Debug> ImplicitFunction<S> for Gyroid<S> {
+    fn bbox(&self) -> &BoundingBox<S> {
+        self.clip.bbox()
+    }
+    // max(sheet, box)
+    fn value(&self, p: &na::Point3<S>) -> S {
+        let (g, _) = self.gyroid(p);
+        Float::max(self.sheet_value(g), self.clip.value(p))
+    }
+    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+        let (g, gradient) = self.gyroid(p);
+        if self.sheet_value(g) >= self.clip.value(p) {
+            (gradient * Float::signum(g)).normalize()
+        } else {
+            self.clip.normal(p)
+        }
+    }
+    // Not exact, but never overestimates the distance, which is all sampling relies on.
+    fn is_exact_distance(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BoxSDF, Gyroid, Torus};
+    use crate::{ImplicitFunction, ManifoldDualContouring, Mesh};
+    use nalgebra as na;
+
+    // Compare normal to the normalized central difference of value at a few points.
+    fn check_normals(f: &dyn ImplicitFunction<f64>, points: &[na::Point3<f64>]) {
+        let h = 1e-6;
+        for p in points {
+            let gradient = na::Vector3::new(
+                f.value(&(p + na::Vector3::x() * h)) - f.value(&(p - na::Vector3::x() * h)),
+                f.value(&(p + na::Vector3::y() * h)) - f.value(&(p - na::Vector3::y() * h)),
+                f.value(&(p + na::Vector3::z() * h)) - f.value(&(p - na::Vector3::z() * h)),
+            );
+            assert!(
+                (f.normal(p) - gradient.normalize()).norm() < 1e-6,
+                "{:?}: {:?} != {:?}",
+                p,
+                f.normal(p),
+                gradient.normalize()
+            );
+        }
+    }
+
+    // Euler characteristic of a closed mesh. Panics if the mesh has boundary edges.
+    fn euler_characteristic(mesh: &Mesh<f64>) -> isize {
+        let half_edge = mesh.to_half_edge();
+        assert!(half_edge.half_edges.iter().all(|e| e.twin.is_some()));
+        mesh.vertices.len() as isize - half_edge.half_edges.len() as isize / 2
+            + mesh.faces.len() as isize
+    }
+
+    #[test]
+    fn torus() {
+        let torus = Torus::new(1f64, 0.25);
+        assert_eq!(torus.bbox().max, na::Point3::new(1.25, 1.25, 0.25));
+        assert!(torus.value(&na::Point3::new(1.25, 0., 0.)).abs() < 1e-12);
+        assert!(torus.value(&na::Point3::new(0., -0.75, 0.)).abs() < 1e-12);
+        assert!((torus.value(&na::Point3::origin()) - 0.75).abs() < 1e-12);
+        check_normals(
+            &torus,
+            &[
+                na::Point3::new(1.3, 0.1, 0.2),
+                na::Point3::new(-0.5, 0.6, -0.1),
+                na::Point3::new(0.2, 0.1, 0.),
+            ],
+        );
+        let mesh = ManifoldDualContouring::new(&torus, 0.05, 0.1)
+            .tessellate()
+            .unwrap();
+        assert_eq!(mesh.split_connected_components().len(), 1);
+        assert_eq!(euler_characteristic(&mesh), 0);
+    }
+
+    #[test]
+    fn box_sdf() {
+        let b = BoxSDF::new(na::Vector3::new(1f64, 0.5, 0.25));
+        assert_eq!(b.bbox().min, na::Point3::new(-1., -0.5, -0.25));
+        assert!((b.value(&na::Point3::origin()) + 0.25).abs() < 1e-12);
+        assert!((b.value(&na::Point3::new(2., 0., 0.)) - 1.).abs() < 1e-12);
+        assert!((b.value(&na::Point3::new(2., 1.5, 0.)) - 2f64.sqrt()).abs() < 1e-12);
+        check_normals(
+            &b,
+            &[
+                na::Point3::new(0.9, 0.1, 0.),
+                na::Point3::new(-0.2, 0.1, -0.2),
+                na::Point3::new(1.5, -1., 0.),
+                na::Point3::new(-1.5, 1., 1.),
+            ],
+        );
+        let mesh = ManifoldDualContouring::new(&b, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        assert_eq!(mesh.split_connected_components().len(), 1);
+        assert_eq!(euler_characteristic(&mesh), 2);
+    }
+
+    #[test]
+    fn gyroid() {
+        let gyroid = Gyroid::new(1f64, 0.1, na::Vector3::new(1., 1., 1.));
+        assert_eq!(gyroid.bbox().max, na::Point3::new(1., 1., 1.));
+        // The gyroid passes through the origin.
+        assert!((gyroid.value(&na::Point3::origin()) + 0.05).abs() < 1e-12);
+        // Outside of the box.
+        assert!((gyroid.value(&na::Point3::new(0., 0., 1.5)) - 0.5).abs() < 1e-12);
+        check_normals(
+            &gyroid,
+            &[
+                na::Point3::new(0.1, 0.2, 0.3),
+                na::Point3::new(-0.4, 0.15, 0.05),
+                na::Point3::new(0.1, 0.1, 1.5),
+            ],
+        );
+        let mesh = ManifoldDualContouring::new(&gyroid, 0.05, 0.1)
+            .tessellate()
+            .unwrap();
+        assert!(!mesh.faces.is_empty());
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|v| v.iter().all(|c| c.abs() < 1.1)));
+    }
+}
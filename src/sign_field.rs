@@ -0,0 +1,120 @@
+use crate::{
+    manifold_dual_contouring::{is_inside, DualContouringError},
+    vertex_index::Index,
+    AsUSize, ImplicitFunction, ManifoldDualContouring, RealField, Sign,
+};
+use nalgebra as na;
+use num_traits::Float;
+use std::collections::HashMap;
+
+/// The sparse, adaptively sampled values of an implicit function on a regular grid.
+///
+/// Far from the surface only a sparse subset of grid points is sampled. Returned from
+/// [`sample()`] or [`ManifoldDualContouring::sample_sign_field()`] and can be tessellated later
+/// via [`ManifoldDualContouring::with_sign_field()`].
+#[derive(Clone, Debug)]
+pub struct SignField<S: RealField> {
+    pub(crate) origin: na::Point3<S>,
+    pub(crate) res: S,
+    pub(crate) dim: [usize; 3],
+    pub(crate) values: HashMap<Index, S>,
+    // Number of cubes, that were not refined because of min_cell_size.
+    pub(crate) num_clipped_cubes: usize,
+    // The sign of the values inside the object.
+    pub(crate) inside_sign: Sign,
+}
+
+/// Sample f with resolution res, without generating a mesh.
+pub fn sample<S: From<f32> + RealField + Float + AsUSize>(
+    f: &dyn ImplicitFunction<S>,
+    res: S,
+) -> Result<SignField<S>, DualContouringError> {
+    ManifoldDualContouring::new(f, res, From::from(0.1f32)).sample_sign_field()
+}
+
+impl<S: From<f32> + RealField + Float> SignField<S> {
+    /// The position of grid point [0, 0, 0].
+    pub fn origin(&self) -> na::Point3<S> {
+        self.origin
+    }
+
+    /// The distance between neighboring grid points.
+    pub fn res(&self) -> S {
+        self.res
+    }
+
+    /// The number of grid points along each axis.
+    pub fn dim(&self) -> [usize; 3] {
+        self.dim
+    }
+
+    /// The number of sampled grid points.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns true, if no grid points were sampled.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The position of the grid point at index.
+    pub fn position(&self, index: Index) -> na::Point3<S> {
+        self.origin
+            + na::Vector3::new(
+                From::from(index[0] as f32),
+                From::from(index[1] as f32),
+                From::from(index[2] as f32),
+            ) * self.res
+    }
+
+    /// The function value at index, if it was sampled.
+    pub fn value(&self, index: Index) -> Option<S> {
+        self.values.get(&index).cloned()
+    }
+
+    /// Whether index is inside the object, i.e. whether the function has the sign set via
+    /// [`ManifoldDualContouring::with_inside_sign()`] there, if it was sampled.
+    pub fn is_inside(&self, index: Index) -> Option<bool> {
+        self.value(index)
+            .map(|value| is_inside(value, self.inside_sign))
+    }
+
+    /// Iterate over all sampled grid points and their values.
+    pub fn iter(&self) -> impl Iterator<Item = (Index, S)> + '_ {
+        self.values.iter().map(|(&index, &value)| (index, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sample;
+    use crate::{
+        test_functions::{Probe, Sphere},
+        ImplicitFunction, ManifoldDualContouring,
+    };
+
+    #[test]
+    fn sample_and_tessellate() {
+        let unit = Sphere::unit();
+        let sphere = Probe::new(&unit).with_exact_distance(false);
+        let field = sample(&sphere, 0.2).unwrap();
+        assert!(!field.is_empty());
+        assert_eq!(field.iter().count(), field.len());
+        for (index, value) in field.iter() {
+            assert_eq!(field.value(index), Some(value));
+            assert_eq!(field.is_inside(index), Some(value < 0.));
+            assert!((sphere.value(&field.position(index)) - value).abs() < 1e-12);
+        }
+
+        let expected = ManifoldDualContouring::new(&sphere, 0.2, 0.1)
+            .tessellate()
+            .unwrap();
+        let mesh = ManifoldDualContouring::new(&sphere, 0.2, 0.1)
+            .with_sign_field(field)
+            .tessellate()
+            .unwrap();
+        assert_eq!(mesh.faces.len(), expected.faces.len());
+        assert_eq!(mesh.vertices.len(), expected.vertices.len());
+    }
+}
@@ -0,0 +1,138 @@
+// Implicit functions shared by the tests of all modules.
+use crate::{BoundingBox, ImplicitFunction, RealField};
+use nalgebra as na;
+use num_traits::Float;
+use std::{cell::Cell, fmt::Debug};
+
+// A sphere, whose value is the exact distance to its surface.
+pub struct Sphere<S: RealField> {
+    center: na::Point3<S>,
+    radius: S,
+    bbox: BoundingBox<S>,
+}
+
+impl<S: RealField + Float + Debug> Sphere<S> {
+    // A sphere with center and radius within its tight bounding box.
+    pub fn new(center: na::Point3<S>, radius: S) -> Self {
+        let extent = na::Vector3::new(radius, radius, radius);
+        Sphere {
+            center,
+            radius,
+            bbox: BoundingBox::new(&(center - extent), &(center + extent)),
+        }
+    }
+
+    // Search for the surface within bbox instead of the tight bounding box.
+    pub fn with_bbox(mut self, bbox: BoundingBox<S>) -> Self {
+        self.bbox = bbox;
+        self
+    }
+}
+
+impl Sphere<f64> {
+    // The sphere with radius one around the origin.
+    pub fn unit() -> Self {
+        Self::new(na::Point3::origin(), 1.)
+    }
+}
+
+impl<S: RealField + Debug> ImplicitFunction<S> for Sphere<S> {
+    fn bbox(&self) -> &BoundingBox<S> {
+        &self.bbox
+    }
+    fn value(&self, p: &na::Point3<S>) -> S {
+        (p - self.center).norm() - self.radius
+    }
+    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+        (p - self.center).normalize()
+    }
+    fn is_exact_distance(&self) -> bool {
+        true
+    }
+}
+
+type ValueOverride<'a, S> = dyn Fn(&na::Point3<S>, S) -> S + 'a;
+type NormalOverride<'a, S> = dyn Fn(&na::Point3<S>, na::Vector3<S>) -> na::Vector3<S> + 'a;
+
+// Wraps a function to count its evaluations and to override its value, normal or exactness. The
+// overrides are called with the point and the result of the wrapped function there.
+pub struct Probe<'a, S: RealField> {
+    function: &'a dyn ImplicitFunction<S>,
+    value: Option<Box<ValueOverride<'a, S>>>,
+    normal: Option<Box<NormalOverride<'a, S>>>,
+    exact_distance: Option<bool>,
+    // Number of values evaluated, either one by one or in batches.
+    pub values: Cell<usize>,
+    // Number of calls of values() and the number of points passed to them.
+    pub batches: Cell<usize>,
+    pub batch_points: Cell<usize>,
+    // Number of normals evaluated.
+    pub normals: Cell<usize>,
+}
+
+impl<'a, S: RealField + Debug> Probe<'a, S> {
+    pub fn new(function: &'a dyn ImplicitFunction<S>) -> Self {
+        Probe {
+            function,
+            value: None,
+            normal: None,
+            exact_distance: None,
+            values: Cell::new(0),
+            batches: Cell::new(0),
+            batch_points: Cell::new(0),
+            normals: Cell::new(0),
+        }
+    }
+
+    pub fn with_value(mut self, value: impl Fn(&na::Point3<S>, S) -> S + 'a) -> Self {
+        self.value = Some(Box::new(value));
+        self
+    }
+
+    pub fn with_normal(
+        mut self,
+        normal: impl Fn(&na::Point3<S>, na::Vector3<S>) -> na::Vector3<S> + 'a,
+    ) -> Self {
+        self.normal = Some(Box::new(normal));
+        self
+    }
+
+    pub fn with_exact_distance(mut self, exact_distance: bool) -> Self {
+        self.exact_distance = Some(exact_distance);
+        self
+    }
+}
+
+impl<'a, S: RealField + Debug> ImplicitFunction<S> for Probe<'a, S> {
+    fn bbox(&self) -> &BoundingBox<S> {
+        self.function.bbox()
+    }
+    fn value(&self, p: &na::Point3<S>) -> S {
+        self.values.set(self.values.get() + 1);
+        let value = self.function.value(p);
+        match self.value {
+            Some(ref f) => f(p, value),
+            None => value,
+        }
+    }
+    fn values(&self, points: &[na::Point3<S>], out: &mut [S]) {
+        self.batches.set(self.batches.get() + 1);
+        self.batch_points
+            .set(self.batch_points.get() + points.len());
+        for (p, value) in points.iter().zip(out.iter_mut()) {
+            *value = self.value(p);
+        }
+    }
+    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+        self.normals.set(self.normals.get() + 1);
+        let normal = self.function.normal(p);
+        match self.normal {
+            Some(ref f) => f(p, normal),
+            None => normal,
+        }
+    }
+    fn is_exact_distance(&self) -> bool {
+        self.exact_distance
+            .unwrap_or_else(|| self.function.is_exact_distance())
+    }
+}
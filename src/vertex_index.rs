@@ -40,6 +40,7 @@ fn egdes_on_neighbor(neighbor_index: usize, edges: BitSet) -> BitSet {
     }
 }
 
+/// Index of a point in the sampling grid.
 pub type Index = [usize; 3];
 
 pub fn offset(idx: Index, offset: Index) -> Index {
@@ -51,6 +52,7 @@ pub fn neg_offset(idx: Index, offset: Index) -> Index {
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VertexIndex {
     pub edges: BitSet,
     pub index: Index,
@@ -64,10 +66,13 @@ impl VertexIndex {
         }
         let mut off = [0, 0, 0];
         off[face / 2] = 1;
+        // There is no neighbor beyond the minimal boundary planes of the grid.
         let neighbor_index = if (face & 1) == 1 {
             offset(self.index, off)
-        } else {
+        } else if self.index[face / 2] > 0 {
             neg_offset(self.index, off)
+        } else {
+            return None;
         };
         Some(VertexIndex {
             edges: neighbor_edge_set,
@@ -77,6 +82,7 @@ impl VertexIndex {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum VarIndex {
     VertexIndex(VertexIndex),
     Index(usize),
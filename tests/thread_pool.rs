@@ -0,0 +1,51 @@
+// Runs in its own process, so no other test touches rayon's global thread pool.
+use nalgebra as na;
+use std::sync::Arc;
+use tessellation::{BoundingBox, ImplicitFunction, ManifoldDualContouring};
+
+struct UnitSphere {
+    bbox: BoundingBox<f64>,
+}
+
+impl ImplicitFunction<f64> for UnitSphere {
+    fn bbox(&self) -> &BoundingBox<f64> {
+        &self.bbox
+    }
+    fn value(&self, p: &na::Point3<f64>) -> f64 {
+        p.coords.norm() - 1.
+    }
+    fn normal(&self, p: &na::Point3<f64>) -> na::Vector3<f64> {
+        p.coords.normalize()
+    }
+    fn is_exact_distance(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn tessellation_stays_in_thread_pool() {
+    let sphere = UnitSphere {
+        bbox: BoundingBox::new(
+            &na::Point3::new(-1., -1., -1.),
+            &na::Point3::new(1., 1., 1.),
+        ),
+    };
+    let pool = Arc::new(
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .build()
+            .unwrap(),
+    );
+    let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.1)
+        .with_thread_pool(pool)
+        .with_face_normals(true);
+    let (mesh, triangles) = mdc.tessellate_both().unwrap();
+    assert!(!mesh.faces.is_empty());
+    assert!(!triangles.faces.is_empty());
+    let attributes = mdc.sample_attribute(&mesh, |p| p[0]);
+    assert_eq!(attributes.len(), mesh.vertices.len());
+    // Building the global pool fails, once any work ran on it.
+    rayon::ThreadPoolBuilder::new()
+        .build_global()
+        .expect("work ran on the global thread pool");
+}